@@ -0,0 +1,118 @@
+// Crash-safe replacement for `fs::write`: the data goes to a temporary file next to the target,
+// is flushed to disk, and is then renamed over it. Readers (and a crash) see either the old
+// file or the new one, never a truncated mix.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// Write `contents` to `path` atomically. A symlink is followed, so the link stays in place.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let target = if path.is_symlink() {
+        path.canonicalize()?
+    } else {
+        path.to_path_buf()
+    };
+    let temp = temp_path(&target)?;
+    crate::watcher::expect_write(path, contents.as_ref());
+
+    let result =
+        write_temp(&temp, &target, contents.as_ref()).and_then(|_| fs::rename(&temp, &target));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = target.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// A hidden, unique sibling of `target`, so the rename stays on one filesystem and the
+/// watchers (which only look at `.md` files) ignore it.
+fn temp_path(target: &Path) -> io::Result<PathBuf> {
+    let name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    Ok(target.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+fn write_temp(temp: &Path, target: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(temp)?;
+    file.write_all(contents)?;
+    // Keep the mode of the file being replaced
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn creates_and_replaces_without_leaving_temp_files() {
+        let dir = TempDir::new("atomic-replace");
+        let path = dir.join("note.md");
+        write(&path, "first").unwrap();
+        write(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(entries(&dir), ["note.md"]);
+    }
+
+    #[test]
+    fn failed_writes_leave_the_target_alone() {
+        let dir = TempDir::new("atomic-fail");
+        // A directory can't be renamed over
+        fs::create_dir(dir.join("note.md")).unwrap();
+        assert!(write(&dir.join("note.md"), "text").is_err());
+        assert!(dir.join("note.md").is_dir());
+        assert_eq!(entries(&dir), ["note.md"]);
+        // Nor can a file be written where there's no folder
+        assert!(write(&dir.join("missing").join("note.md"), "text").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = TempDir::new("atomic-symlink");
+        let target = dir.join("target.md");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        let link = dir.join("link.md");
+        symlink(&target, &link).unwrap();
+
+        write(&link, "new").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(entries(&dir), ["link.md", "target.md"]);
+    }
+}
@@ -0,0 +1,4 @@
+// Headless command line sharing the app's vault logic; see `gitnotes-cli --help`
+fn main() {
+    std::process::exit(gitnotes_lib::run_cli())
+}
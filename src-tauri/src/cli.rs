@@ -0,0 +1,462 @@
+// Headless command line for scripts and CI. Every command goes through the same functions
+// the app's commands use, so notes get the same frontmatter and section ordering.
+
+use crate::error::GitNotesError;
+use crate::mcp::McpServer;
+use crate::search::SearchIndex;
+use crate::{
+    create_note_in, create_note_smart_in, find_vault_by_path, get_notes_path, git,
+    list_all_notes_in, list_notes_in, load_settings, pull_uses_merge, search_index_root,
+    sync_repo, Settings, SyncOutcome,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: gitnotes-cli [--vault <path>] [--json] <command> [args]
+
+Commands:
+  list [<section>]             List every note, or one section's notes in its sort order
+  new <section> [<name>]       Create a note (named like the app's new-note button if no name)
+  search <query> [--limit <n>] Full-text search
+  commit [-m <message>]        Commit all changes in the vault
+  sync [-m <message>]          Commit, pull (rebase or merge, per vault) and push
+  reindex                      Rebuild the search index from scratch
+  mcp [--socket <path>]        Serve the vault to agents over MCP (stdio, or a Unix socket)
+
+The vault defaults to $GITNOTES_VAULT, then the app's active vault.";
+
+struct Args {
+    vault: PathBuf,
+    json: bool,
+    limit: usize,
+    message: Option<String>,
+    socket: Option<PathBuf>,
+    command: String,
+    params: Vec<String>,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Option<Args>, String> {
+    let mut vault = std::env::var_os("GITNOTES_VAULT").map(PathBuf::from);
+    let mut json = false;
+    let mut limit = 20;
+    let mut message = None;
+    let mut socket = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-h" | "--help" | "help" => return Ok(None),
+            "--vault" => vault = Some(PathBuf::from(value("--vault")?)),
+            "--json" => json = true,
+            "--limit" => {
+                limit = value("--limit")?
+                    .parse()
+                    .map_err(|_| "--limit must be a number".to_string())?
+            }
+            "-m" | "--message" => message = Some(value("--message")?),
+            "--socket" => socket = Some(PathBuf::from(value("--socket")?)),
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unknown option: {}", flag))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if positional.is_empty() {
+        return Ok(None);
+    }
+    let command = positional.remove(0);
+
+    Ok(Some(Args {
+        vault: vault.unwrap_or_else(get_notes_path),
+        json,
+        limit,
+        message,
+        socket,
+        command,
+        params: positional,
+    }))
+}
+
+/// Run the CLI and return the process exit code: 0 on success, 1 on errors, 2 when a sync
+/// couldn't complete (conflicts, auth or network).
+pub fn run(args: Vec<String>) -> i32 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("gitnotes: {}\n\n{}", e, USAGE);
+            return 1;
+        }
+    };
+
+    let config = Config {
+        settings: load_settings(),
+        index_root: search_index_root(),
+    };
+    match execute(&args, &config) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("gitnotes: {}", e);
+            1
+        }
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn section_path(vault: &Path, section: &str) -> Result<PathBuf, String> {
+    let path = vault.join(section);
+    if !path.is_dir() {
+        return Err(format!("Section not found: {}", section));
+    }
+    Ok(path)
+}
+
+/// What the CLI takes from the app's configuration.
+struct Config {
+    settings: Settings,
+    /// Where the app keeps its search indexes, one folder per vault
+    index_root: PathBuf,
+}
+
+/// The CLI's own index for a vault, keyed like the app's: by id for a configured vault, by
+/// path for any other folder. The app holds its index's writer lock for as long as it runs,
+/// and `mcp` can run just as long, so the two never share one.
+fn open_search_index(vault: &Path, config: &Config) -> Result<SearchIndex, GitNotesError> {
+    let vault_id = find_vault_by_path(&config.settings, vault)
+        .map(|v| v.id)
+        .unwrap_or_else(|| {
+            let canonical = vault.canonicalize().unwrap_or_else(|_| vault.to_path_buf());
+            let digest = Sha256::digest(canonical.to_string_lossy().as_bytes());
+            format!("path-{}", &format!("{:x}", digest)[..12])
+        });
+    let index_path = config.index_root.join(format!("{}-cli", vault_id));
+    SearchIndex::new(&index_path, &vault.to_path_buf())
+}
+
+fn execute(args: &Args, config: &Config) -> Result<i32, String> {
+    let vault = &args.vault;
+    if !vault.is_dir() {
+        return Err(format!("Vault not found: {}", vault.display()));
+    }
+
+    match (args.command.as_str(), args.params.as_slice()) {
+        ("list", []) => {
+            let notes = list_all_notes_in(vault)?;
+            if args.json {
+                return print_json(&notes).map(|_| 0);
+            }
+            for note in notes {
+                println!("{}/{}", note.section, note.name);
+            }
+        }
+        ("list", [section]) => {
+            let notes = list_notes_in(vault, &section_path(vault, section)?.to_string_lossy())?;
+            if args.json {
+                return print_json(&notes).map(|_| 0);
+            }
+            for note in notes {
+                match note.subfolder {
+                    Some(sub) => println!("{}/{}", sub, note.name),
+                    None => println!("{}", note.name),
+                }
+            }
+        }
+        ("new", [section, name @ ..]) if name.len() <= 1 => {
+            let section = section_path(vault, section)?.to_string_lossy().to_string();
+            let note = match name.first() {
+                Some(name) => create_note_in(vault, &section, name.clone())?,
+                None => create_note_smart_in(vault, &section)?,
+            };
+            if args.json {
+                return print_json(&note).map(|_| 0);
+            }
+            println!("{}", note.path);
+        }
+        ("search", query) if !query.is_empty() => {
+            let results = open_search_index(vault, config)?.search(
+                &query.join(" "),
+                args.limit,
+                &config.settings.search,
+            )?;
+            if args.json {
+                return print_json(&results).map(|_| 0);
+            }
+            for result in results {
+                let location = match result.match_line {
+                    Some(line) => format!("{}:{}", result.path, line),
+                    None => result.path,
+                };
+                match result.snippet {
+                    Some(snippet) => println!("{}\t{}", location, snippet.replace('\n', " ")),
+                    None => println!("{}", location),
+                }
+            }
+        }
+        ("commit", []) => {
+            let repo = git::open(vault)?;
+            let message = args.message.as_deref().unwrap_or("Update notes");
+            let commit = git::commit_all(&repo, message)?.map(|oid| oid.to_string());
+            if args.json {
+                return print_json(&commit).map(|_| 0);
+            }
+            match commit {
+                Some(hash) => println!("{}", &hash[..7]),
+                None => println!("Nothing to commit"),
+            }
+        }
+        ("sync", []) => {
+            let outcome = sync_repo(
+                vault,
+                pull_uses_merge(&config.settings, vault),
+                args.message.clone(),
+            )?;
+            let code = match outcome {
+                SyncOutcome::UpToDate | SyncOutcome::Pushed | SyncOutcome::Pulled => 0,
+                _ => 2,
+            };
+            if args.json {
+                return print_json(&outcome).map(|_| code);
+            }
+            match outcome {
+                SyncOutcome::UpToDate => println!("Up to date"),
+                SyncOutcome::Pushed => println!("Pushed"),
+                SyncOutcome::Pulled => println!("Pulled remote changes"),
+                SyncOutcome::Conflicts(files) => {
+                    println!("Conflicts:");
+                    for file in files {
+                        println!("  {}", file);
+                    }
+                }
+                SyncOutcome::AuthFailed => println!("Authentication failed"),
+                SyncOutcome::Offline => println!("Could not reach the remote"),
+            }
+            return Ok(code);
+        }
+        ("reindex", []) => {
+            let count = open_search_index(vault, config)?.rebuild(&vault.to_path_buf())?;
+            if args.json {
+                return print_json(&count).map(|_| 0);
+            }
+            println!("Indexed {} notes", count);
+        }
+        ("mcp", []) => {
+            let server = McpServer::new(
+                vault.clone(),
+                open_search_index(vault, config)?,
+                config.settings.search.clone(),
+            );
+            match &args.socket {
+                #[cfg(unix)]
+                Some(socket) => server.serve_socket(socket)?,
+                #[cfg(not(unix))]
+                Some(_) => return Err("--socket is only supported on Unix".to_string()),
+                None => server.serve_stdio()?,
+            }
+        }
+        (command, _) => {
+            return Err(format!("Unknown command or wrong arguments: {}\n\n{}", command, USAGE))
+        }
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::configure;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    fn args(line: &str) -> Result<Option<Args>, String> {
+        parse_args(line.split_whitespace().map(String::from).collect())
+    }
+
+    /// A git vault with a `work` section, and a configuration that knows it as `brain`.
+    struct Fixture {
+        root: TempDir,
+        config: Config,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = TempDir::new(&format!("cli-{}", name));
+            let vault = root.join("vault");
+            fs::create_dir_all(vault.join("work")).unwrap();
+            git::init(&vault).unwrap();
+            configure(&vault);
+            let settings = serde_json::from_value(serde_json::json!({
+                "vaults": [{ "id": "brain", "name": "Brain", "path": vault }],
+            }))
+            .unwrap();
+            let config = Config {
+                settings,
+                index_root: root.join("indexes"),
+            };
+            Fixture { root, config }
+        }
+
+        fn vault(&self) -> PathBuf {
+            self.root.join("vault")
+        }
+
+        fn run(&self, line: &str) -> Result<i32, String> {
+            let line = format!("--vault {} {}", self.vault().display(), line);
+            execute(&args(&line).unwrap().unwrap(), &self.config)
+        }
+    }
+
+    #[test]
+    fn parses_options_around_the_command() {
+        let parsed = args("--json search --vault /notes river bank --limit 5 -m Hi")
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.vault, PathBuf::from("/notes"));
+        assert!(parsed.json);
+        assert_eq!(parsed.limit, 5);
+        assert_eq!(parsed.message.as_deref(), Some("Hi"));
+        assert_eq!(parsed.command, "search");
+        assert_eq!(parsed.params, ["river", "bank"]);
+
+        let parsed = args("--vault /notes mcp --socket /tmp/gitnotes.sock")
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.socket, Some(PathBuf::from("/tmp/gitnotes.sock")));
+        assert_eq!(parsed.limit, 20);
+        // A lone `-` is an argument, not an option
+        assert_eq!(args("--vault /notes new -").unwrap().unwrap().params, ["-"]);
+    }
+
+    #[test]
+    fn asks_for_usage_or_reports_bad_options() {
+        for line in ["", "--json", "--help", "--vault /notes list -h", "help"] {
+            assert!(args(line).unwrap().is_none(), "{:?}", line);
+        }
+        for (line, error) in [
+            ("list --verbose", "Unknown option: --verbose"),
+            ("search tent --limit", "--limit needs a value"),
+            ("search tent --limit ten", "--limit must be a number"),
+            ("commit -m", "--message needs a value"),
+        ] {
+            assert_eq!(args(line).err().as_deref(), Some(error), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn creates_and_lists_notes() {
+        let cli = Fixture::new("notes");
+        let work = cli.vault().join("work");
+
+        assert_eq!(cli.run("new work plan"), Ok(0));
+        assert!(fs::read_to_string(work.join("plan.md"))
+            .unwrap()
+            .starts_with("---\ncreated: "));
+        assert!(cli
+            .run("new work plan")
+            .unwrap_err()
+            .starts_with("Already exists"));
+        assert_eq!(cli.run("new work"), Ok(0));
+        assert!(work.join("Untitled.md").exists());
+        assert_eq!(
+            cli.run("new missing plan"),
+            Err("Section not found: missing".to_string())
+        );
+        assert!(cli
+            .run("new work ../../escape")
+            .unwrap_err()
+            .starts_with("Path is outside the vault"));
+
+        assert_eq!(cli.run("list"), Ok(0));
+        assert_eq!(cli.run("--json list work"), Ok(0));
+        assert_eq!(
+            cli.run("list missing"),
+            Err("Section not found: missing".to_string())
+        );
+    }
+
+    #[test]
+    fn searches_its_own_index_next_to_the_apps() {
+        let cli = Fixture::new("search");
+        fs::write(
+            cli.vault().join("work/plan.md"),
+            "Pitch the tent by the river\n",
+        )
+        .unwrap();
+        // The app has the vault open, holding its index's lock
+        let app_index = cli.config.index_root.join("brain");
+        let _app = SearchIndex::new(&app_index, &cli.vault()).unwrap();
+
+        assert_eq!(cli.run("reindex"), Ok(0));
+        assert_eq!(cli.run("search tent --limit 1"), Ok(0));
+        let results = open_search_index(&cli.vault(), &cli.config)
+            .unwrap()
+            .search("tent", 5, &cli.config.settings.search)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("plan.md"));
+        assert!(cli.config.index_root.join("brain-cli").is_dir());
+
+        // Any other folder gets an index keyed by its path
+        let other = cli.root.join("other");
+        fs::create_dir(&other).unwrap();
+        open_search_index(&other, &cli.config).unwrap();
+        let mut indexes: Vec<_> = fs::read_dir(&cli.config.index_root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        indexes.sort();
+        assert_eq!(indexes.len(), 3);
+        assert_eq!(indexes[..2], ["brain", "brain-cli"]);
+        assert!(indexes[2].starts_with("path-") && indexes[2].ends_with("-cli"));
+    }
+
+    #[test]
+    fn commits_the_vault() {
+        let cli = Fixture::new("commit");
+        let repo = git2::Repository::open(cli.vault()).unwrap();
+        fs::write(cli.vault().join("work/plan.md"), "plan\n").unwrap();
+
+        assert_eq!(cli.run("commit -m Plans"), Ok(0));
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("Plans"));
+        // Nothing left to commit
+        assert_eq!(cli.run("commit"), Ok(0));
+        assert_eq!(
+            repo.head().unwrap().peel_to_commit().unwrap().id(),
+            head.id()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_missing_vaults() {
+        let cli = Fixture::new("errors");
+        let usage = |command: &str| {
+            Err(format!(
+                "Unknown command or wrong arguments: {}\n\n{}",
+                command, USAGE
+            ))
+        };
+        assert_eq!(cli.run("frobnicate"), usage("frobnicate"));
+        assert_eq!(cli.run("new"), usage("new"));
+        assert_eq!(cli.run("commit now"), usage("commit"));
+
+        let missing = cli.root.join("missing");
+        let line = format!("--vault {} list", missing.display());
+        assert_eq!(
+            execute(&args(&line).unwrap().unwrap(), &cli.config),
+            Err(format!("Vault not found: {}", missing.display()))
+        );
+    }
+}
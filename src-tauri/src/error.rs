@@ -0,0 +1,201 @@
+// Errors returned to the frontend. They serialize as `{ code, message, ...context }`, so the
+// UI can branch on a stable `code` and still show `message` as-is.
+
+use crate::paths::PathOutsideVault;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitErrorKind {
+    NotARepository,
+    NoUpstream,
+    AuthFailed,
+    Offline,
+    /// The remote refused the push, usually because it has commits we don't.
+    Rejected,
+    /// A merge or rebase is stopped on conflicts.
+    Conflicts,
+    Other,
+}
+
+#[derive(Debug)]
+pub enum GitNotesError {
+    NotFound { path: String },
+    AlreadyExists { path: String },
+    InvalidName { name: String },
+    /// One of the built-in sections, which can't be renamed or deleted.
+    Protected { name: String },
+    PathOutsideVault { path: String },
+    Git { kind: GitErrorKind, message: String },
+    Io { path: Option<String>, message: String },
+    /// Unparseable input, such as a search query or a note's frontmatter.
+    Parse { message: String },
+    /// The search index couldn't be opened, read or written.
+    Index { message: String },
+    /// Another process, usually the running app, is writing to the search index.
+    IndexLocked { message: String },
+    /// Errors from code that still reports plain strings.
+    Other { message: String },
+}
+
+impl GitNotesError {
+    pub fn git(kind: GitErrorKind, message: impl Into<String>) -> Self {
+        GitNotesError::Git {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn io(path: &std::path::Path, e: std::io::Error) -> Self {
+        GitNotesError::Io {
+            path: Some(path.to_string_lossy().to_string()),
+            message: e.to_string(),
+        }
+    }
+
+    /// Stable identifier for the frontend; don't rename these.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GitNotesError::NotFound { .. } => "not_found",
+            GitNotesError::AlreadyExists { .. } => "already_exists",
+            GitNotesError::InvalidName { .. } => "invalid_name",
+            GitNotesError::Protected { .. } => "protected",
+            GitNotesError::PathOutsideVault { .. } => "path_outside_vault",
+            GitNotesError::Git { .. } => "git",
+            GitNotesError::Io { .. } => "io",
+            GitNotesError::Parse { .. } => "parse",
+            GitNotesError::Index { .. } => "index",
+            GitNotesError::IndexLocked { .. } => "index_locked",
+            GitNotesError::Other { .. } => "other",
+        }
+    }
+}
+
+impl fmt::Display for GitNotesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitNotesError::NotFound { path } => write!(f, "Not found: {}", path),
+            GitNotesError::AlreadyExists { path } => write!(f, "Already exists: {}", path),
+            GitNotesError::InvalidName { name } => write!(f, "Invalid characters in name: {}", name),
+            GitNotesError::Protected { name } => {
+                write!(f, "{} is a built-in section and cannot be changed", name)
+            }
+            GitNotesError::PathOutsideVault { path } => {
+                write!(f, "Path is outside the vault: {}", path)
+            }
+            GitNotesError::Io {
+                path: Some(path),
+                message,
+            } => write!(f, "{}: {}", path, message),
+            GitNotesError::Git { message, .. }
+            | GitNotesError::Io { message, .. }
+            | GitNotesError::Parse { message }
+            | GitNotesError::Index { message }
+            | GitNotesError::IndexLocked { message }
+            | GitNotesError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for GitNotesError {}
+
+impl Serialize for GitNotesError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            GitNotesError::NotFound { path }
+            | GitNotesError::AlreadyExists { path }
+            | GitNotesError::PathOutsideVault { path } => map.serialize_entry("path", path)?,
+            GitNotesError::InvalidName { name } | GitNotesError::Protected { name } => {
+                map.serialize_entry("name", name)?
+            }
+            GitNotesError::Git { kind, .. } => map.serialize_entry("kind", kind)?,
+            GitNotesError::Io {
+                path: Some(path), ..
+            } => map.serialize_entry("path", path)?,
+            _ => {}
+        }
+        map.end()
+    }
+}
+
+impl From<String> for GitNotesError {
+    fn from(message: String) -> Self {
+        GitNotesError::Other { message }
+    }
+}
+
+impl From<&str> for GitNotesError {
+    fn from(message: &str) -> Self {
+        GitNotesError::Other {
+            message: message.to_string(),
+        }
+    }
+}
+
+// Lets functions that still return `Result<_, String>` use `?` on typed errors
+impl From<GitNotesError> for String {
+    fn from(e: GitNotesError) -> String {
+        e.to_string()
+    }
+}
+
+impl From<std::io::Error> for GitNotesError {
+    fn from(e: std::io::Error) -> Self {
+        GitNotesError::Io {
+            path: None,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<PathOutsideVault> for GitNotesError {
+    fn from(e: PathOutsideVault) -> Self {
+        GitNotesError::PathOutsideVault {
+            path: e.0.to_string_lossy().to_string(),
+        }
+    }
+}
+
+impl From<git2::Error> for GitNotesError {
+    fn from(e: git2::Error) -> Self {
+        use git2::ErrorCode;
+        let kind = match e.code() {
+            ErrorCode::Auth | ErrorCode::Certificate => GitErrorKind::AuthFailed,
+            ErrorCode::Conflict | ErrorCode::MergeConflict | ErrorCode::Unmerged => {
+                GitErrorKind::Conflicts
+            }
+            ErrorCode::NotFastForward => GitErrorKind::Rejected,
+            _ => GitErrorKind::Other,
+        };
+        GitNotesError::git(kind, e.message())
+    }
+}
+
+impl From<tantivy::TantivyError> for GitNotesError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        use tantivy::directory::error::LockError;
+
+        match e {
+            tantivy::TantivyError::LockFailure(LockError::LockBusy, _) => {
+                GitNotesError::IndexLocked {
+                    message: e.to_string(),
+                }
+            }
+            e => GitNotesError::Index {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<tantivy::query::QueryParserError> for GitNotesError {
+    fn from(e: tantivy::query::QueryParserError) -> Self {
+        GitNotesError::Parse {
+            message: e.to_string(),
+        }
+    }
+}
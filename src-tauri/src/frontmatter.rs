@@ -0,0 +1,395 @@
+// Frontmatter kept as the text the user (or another tool) wrote. Updating a field rewrites
+// only that key's lines, so key order, comments, quoting and unknown keys survive untouched
+// and git diffs show just what changed.
+
+use serde_yaml::{Mapping, Value};
+
+#[derive(Debug, Clone, Default)]
+pub struct Frontmatter {
+    /// YAML between the `---` fences, exactly as read.
+    text: String,
+    /// Parsed view of `text`, for reading fields.
+    fields: Mapping,
+    /// Whether the file uses `\r\n` line endings. `text` always uses `\n`.
+    crlf: bool,
+}
+
+impl Frontmatter {
+    /// Parse the YAML between the fences. `None` if it isn't a YAML mapping.
+    pub fn parse(yaml: &str) -> Option<Self> {
+        let fields = match serde_yaml::from_str::<Value>(yaml).ok()? {
+            Value::Mapping(m) => m,
+            Value::Null => Mapping::new(),
+            _ => return None,
+        };
+        Some(Frontmatter {
+            text: yaml.to_string(),
+            fields,
+            crlf: false,
+        })
+    }
+
+    pub fn fields(&self) -> &Mapping {
+        &self.fields
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).and_then(|v| v.as_str())
+    }
+
+    /// Set a top-level scalar such as `modified`, rewriting just its line and keeping the old
+    /// quote style. A missing `created` goes first and a missing `modified` right after it,
+    /// other new keys go last.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = match self.span(key) {
+            Some((start, _)) => {
+                let old = self.lines()[start][key.len() + 1..].trim().to_string();
+                match old.chars().next() {
+                    Some(q @ ('\'' | '"')) => format!("{}: {}{}{}", key, q, value, q),
+                    _ => format!("{}: {}", key, value),
+                }
+            }
+            None => format!("{}: {}", key, value),
+        };
+        self.replace(key, vec![line]);
+        self.fields.insert(
+            Value::String(key.to_string()),
+            Value::String(value.to_string()),
+        );
+    }
+
+    /// Set a top-level key to any YAML value, serialized (and escaped) by serde_yaml.
+    pub fn set_value(&mut self, key: &str, value: &Value) {
+        let mut entry = Mapping::new();
+        entry.insert(Value::String(key.to_string()), value.clone());
+        let yaml = serde_yaml::to_string(&entry).unwrap_or_default();
+        self.replace(key, yaml.lines().map(String::from).collect());
+        self.fields
+            .insert(Value::String(key.to_string()), value.clone());
+    }
+
+    /// Remove a top-level key and its value.
+    pub fn remove(&mut self, key: &str) {
+        if let Some((start, end)) = self.span(key) {
+            let mut lines = self.lines();
+            lines.drain(start..end);
+            self.text = lines.join("\n");
+        }
+        self.fields.remove(key);
+    }
+
+    /// The frontmatter with its fences, without a trailing newline.
+    pub fn to_block(&self) -> String {
+        let block = if self.text.is_empty() {
+            "---\n---".to_string()
+        } else {
+            format!("---\n{}\n---", self.text)
+        };
+        if self.crlf {
+            block.replace('\n', "\r\n")
+        } else {
+            block
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        if self.text.is_empty() {
+            Vec::new()
+        } else {
+            self.text.split('\n').map(String::from).collect()
+        }
+    }
+
+    fn span(&self, key: &str) -> Option<(usize, usize)> {
+        key_span(&self.lines(), key)
+    }
+
+    /// Swap a key's lines for `new_lines`, or insert them where a new key of that name goes.
+    fn replace(&mut self, key: &str, new_lines: Vec<String>) {
+        let mut lines = self.lines();
+        match key_span(&lines, key) {
+            Some((start, end)) => {
+                lines.splice(start..end, new_lines);
+            }
+            None => {
+                let at = match key {
+                    "created" => 0,
+                    "modified" => key_span(&lines, "created").map(|(_, end)| end).unwrap_or(0),
+                    // Before any trailing blank lines
+                    _ => lines
+                        .iter()
+                        .rposition(|l| !l.trim().is_empty())
+                        .map_or(0, |i| i + 1),
+                };
+                lines.splice(at..at, new_lines);
+            }
+        }
+        self.text = lines.join("\n");
+    }
+}
+
+/// Lines `[start, end)` holding a top-level key and its value, including any indented
+/// continuation lines (block scalars, nested maps, sequences) and blank lines inside them.
+fn key_span(lines: &[String], key: &str) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|line| {
+        line.strip_prefix(key)
+            .is_some_and(|rest| rest.starts_with(':'))
+    })?;
+    let continues =
+        |line: &str| line.starts_with(' ') || line.starts_with('\t') || line.starts_with("- ");
+    let mut end = start + 1;
+    let mut scan = end;
+    while scan < lines.len() {
+        let line = &lines[scan];
+        if line.trim().is_empty() {
+            scan += 1;
+            continue;
+        }
+        if !continues(line) {
+            break;
+        }
+        scan += 1;
+        end = scan;
+    }
+    Some((start, end))
+}
+
+/// Split a file into its frontmatter and everything after the closing fence, untouched.
+/// `None` if the file doesn't start with frontmatter or it isn't a valid YAML mapping.
+pub fn split_raw(content: &str) -> Option<(Frontmatter, &str)> {
+    let crlf = content.starts_with("---\r\n");
+    let newline = if crlf { "\r\n" } else { "\n" };
+    let after_open = content.strip_prefix("---")?.strip_prefix(newline)?;
+    let (yaml, rest) = match after_open.strip_prefix("---") {
+        // Empty frontmatter
+        Some(rest) => ("", rest),
+        None => {
+            let fence = format!("{}---", newline);
+            let end = after_open.find(&fence)?;
+            (&after_open[..end], &after_open[end + fence.len()..])
+        }
+    };
+    let mut fm = Frontmatter::parse(&yaml.replace("\r\n", "\n"))?;
+    fm.crlf = crlf;
+    Some((fm, rest))
+}
+
+/// Split a note into its frontmatter and body. Content without valid frontmatter is all body.
+pub fn split(content: &str) -> (Option<Frontmatter>, &str) {
+    match split_raw(content) {
+        Some((fm, rest)) => (Some(fm), rest.trim_start_matches(['\r', '\n'])),
+        None => (None, content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLODEX: &str = "\
+created: 2024-03-01T09:15:00
+modified: '2024-05-12T18:40:11'
+title: Dr.
+company: \"Acme, Inc.\"
+role: CTO
+# imported from the old CRM
+emails:
+  - label: work
+    value: ada@acme.test
+  - label: home
+    value: ada@example.test
+phones:
+- label: mobile
+  value: \"+1 555 0100\"
+birthday: 1815-12-10
+addresses:
+  - label: office
+    value: |-
+      12 Engine Street
+      London
+social: []
+imported: crm-2023
+last_call: 2024-05-01";
+
+    const JOURNAL: &str = "\
+# journal entry
+mood: ok
+tags: [travel, family]
+weather:
+  morning: rain
+  evening: clear
+summary: >
+  Long day on the train,
+  arrived late.
+created: 2024-06-02T07:00:00";
+
+    fn round_trip(yaml: &str) -> Frontmatter {
+        let fm = Frontmatter::parse(yaml).expect("valid frontmatter");
+        assert_eq!(fm.to_block(), format!("---\n{}\n---", yaml));
+        fm
+    }
+
+    #[test]
+    fn round_trips_rolodex_and_journal_unchanged() {
+        let fm = round_trip(ROLODEX);
+        assert_eq!(fm.get_str("company"), Some("Acme, Inc."));
+        assert_eq!(
+            fm.fields()["emails"][1]["value"].as_str(),
+            Some("ada@example.test")
+        );
+        let fm = round_trip(JOURNAL);
+        assert_eq!(
+            fm.get_str("summary"),
+            Some("Long day on the train, arrived late.\n")
+        );
+    }
+
+    #[test]
+    fn setting_modified_touches_only_its_line() {
+        let mut fm = round_trip(ROLODEX);
+        fm.set("modified", "2024-06-01T10:00:00");
+        let expected = ROLODEX.replace(
+            "modified: '2024-05-12T18:40:11'",
+            "modified: '2024-06-01T10:00:00'",
+        );
+        assert_eq!(fm.to_block(), format!("---\n{}\n---", expected));
+        assert_eq!(fm.get_str("modified"), Some("2024-06-01T10:00:00"));
+    }
+
+    #[test]
+    fn missing_timestamps_are_inserted_without_moving_other_keys() {
+        let mut fm = round_trip(JOURNAL);
+        fm.set("modified", "2024-06-02T08:00:00");
+        // `created` comes last here, and `modified` goes right after it
+        assert_eq!(
+            fm.to_block(),
+            format!("---\n{}\nmodified: 2024-06-02T08:00:00\n---", JOURNAL)
+        );
+
+        let mut fm = Frontmatter::parse("tags: [a]").unwrap();
+        fm.set("created", "2024-01-01T00:00:00");
+        fm.set("modified", "2024-01-01T00:00:00");
+        assert_eq!(
+            fm.to_block(),
+            "---\ncreated: 2024-01-01T00:00:00\nmodified: 2024-01-01T00:00:00\ntags: [a]\n---"
+        );
+    }
+
+    #[test]
+    fn empty_frontmatter_gets_timestamps() {
+        let mut fm = Frontmatter::default();
+        fm.set("created", "2024-01-01T00:00:00");
+        fm.set("modified", "2024-01-02T00:00:00");
+        assert_eq!(
+            fm.to_block(),
+            "---\ncreated: 2024-01-01T00:00:00\nmodified: 2024-01-02T00:00:00\n---"
+        );
+    }
+
+    #[test]
+    fn similar_key_prefixes_are_not_confused() {
+        let mut fm = round_trip("modified_by: ada\nmodified: 2024-01-01T00:00:00");
+        fm.set("modified", "2024-02-02T00:00:00");
+        assert_eq!(
+            fm.to_block(),
+            "---\nmodified_by: ada\nmodified: 2024-02-02T00:00:00\n---"
+        );
+    }
+
+    #[test]
+    fn split_separates_body_and_rejects_non_mappings() {
+        let content = format!("---\n{}\n---\n\n# Ada\n", ROLODEX);
+        let (fm, body) = split(&content);
+        assert_eq!(fm.unwrap().to_block(), format!("---\n{}\n---", ROLODEX));
+        assert_eq!(body, "# Ada\n");
+
+        let (fm, body) = split("---\n- just a list\n---\nbody");
+        assert!(fm.is_none());
+        assert_eq!(body, "---\n- just a list\n---\nbody");
+    }
+
+    const SECTION: &str = "---
+title: \"Reading\"
+gitbrain_id: 42 # set by GitBrain
+order:
+  - a.md
+  - b.md
+agent_instructions: |
+  Keep summaries short.
+
+  One note per book.
+sort: manual
+---
+
+# Reading list
+
+Notes below the frontmatter.
+";
+
+    #[test]
+    fn set_value_escapes_and_keeps_the_rest_of_the_file() {
+        let (mut fm, rest) = split_raw(SECTION).unwrap();
+        fm.set_value("title", &Value::String("Say \"hi\": now".into()));
+        let updated = format!("{}{}", fm.to_block(), rest);
+        assert_eq!(
+            updated,
+            SECTION.replace("title: \"Reading\"", "title: 'Say \"hi\": now'")
+        );
+        let (fm, _) = split_raw(&updated).unwrap();
+        assert_eq!(fm.get_str("title"), Some("Say \"hi\": now"));
+    }
+
+    #[test]
+    fn replacing_and_removing_multi_line_values() {
+        let (mut fm, rest) = split_raw(SECTION).unwrap();
+        fm.set_value(
+            "order",
+            &Value::Sequence(vec![
+                Value::String("b.md".into()),
+                Value::String("a.md".into()),
+            ]),
+        );
+        fm.remove("agent_instructions");
+        let updated = format!("{}{}", fm.to_block(), rest);
+        let expected = SECTION
+            .replace("order:\n  - a.md\n  - b.md\n", "order:\n- b.md\n- a.md\n")
+            .replace(
+                "agent_instructions: |\n  Keep summaries short.\n\n  One note per book.\n",
+                "",
+            );
+        assert_eq!(updated, expected);
+    }
+
+    #[test]
+    fn new_keys_go_after_existing_ones() {
+        let (mut fm, rest) = split_raw("---\n---\nbody").unwrap();
+        fm.set_value("color", &Value::String("#ff0000".into()));
+        fm.set_value("sort", &Value::String("manual".into()));
+        assert_eq!(
+            format!("{}{}", fm.to_block(), rest),
+            "---\ncolor: '#ff0000'\nsort: manual\n---\nbody"
+        );
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let content = SECTION.replace('\n', "\r\n");
+        let (mut fm, rest) = split_raw(&content).unwrap();
+        assert_eq!(fm.get_str("sort"), Some("manual"));
+        assert_eq!(
+            fm.get_str("agent_instructions"),
+            Some("Keep summaries short.\n\nOne note per book.\n")
+        );
+        assert_eq!(format!("{}{}", fm.to_block(), rest), content);
+
+        fm.set("sort", "alphabetical");
+        assert_eq!(
+            format!("{}{}", fm.to_block(), rest),
+            content.replace("sort: manual", "sort: alphabetical")
+        );
+
+        let (fm, body) = split("---\r\n---\r\n\r\n# Empty\r\n");
+        assert!(fm.unwrap().fields().is_empty());
+        assert_eq!(body, "# Empty\r\n");
+    }
+}
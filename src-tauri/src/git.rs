@@ -0,0 +1,1845 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    AnnotatedCommit, BranchType, Commit, Cred, CredentialType, Diff, DiffFindOptions, DiffFormat,
+    DiffOptions, ErrorClass, ErrorCode, FetchOptions, FetchPrune, Index, IndexAddOption,
+    ObjectType, Oid, PushOptions, Rebase, RemoteCallbacks, Repository, RepositoryState, Signature,
+    Sort, Status, StatusOptions, Time, Tree,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{GitErrorKind, GitNotesError};
+
+/// Open the repository containing `path` (a directory or a file inside the work tree).
+pub fn open(path: &Path) -> Result<Repository, GitNotesError> {
+    let start = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    Repository::discover(start).map_err(|e| match e.code() {
+        ErrorCode::NotFound => GitNotesError::git(GitErrorKind::NotARepository, e.message()),
+        _ => GitNotesError::from(e),
+    })
+}
+
+pub fn init(path: &Path) -> Result<(), GitNotesError> {
+    Repository::init(path)?;
+    Ok(())
+}
+
+/// `path` relative to the repository's work tree, with `/` separators.
+pub fn relative_path(repo: &Repository, path: &Path) -> Result<String, GitNotesError> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitNotesError::git(GitErrorKind::Other, "Repository has no working tree"))?;
+    // Compare canonical forms (e.g. /tmp vs /private/tmp); the file itself may not exist any more
+    let canonical_root = workdir.canonicalize()?;
+    let dir = path.parent().ok_or("Invalid path")?;
+    let canonical_dir = dir.canonicalize().map_err(|e| GitNotesError::io(dir, e))?;
+    let rel_dir = canonical_dir
+        .strip_prefix(&canonical_root)
+        .map_err(|_| GitNotesError::PathOutsideVault {
+            path: path.to_string_lossy().to_string(),
+        })?;
+    let rel = rel_dir.join(path.file_name().ok_or("Invalid path")?);
+
+    Ok(rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+fn format_time(time: Time) -> String {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    DateTime::from_timestamp(time.seconds(), 0)
+        .unwrap_or_default()
+        .with_timezone(&offset)
+        .format("%Y-%m-%dT%H:%M:%S%:z")
+        .to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitInfo {
+    pub hash: String,
+    pub full_hash: String,
+    pub message: String,
+    pub date: String,
+    pub author: String,
+    pub author_email: String,
+}
+
+impl CommitInfo {
+    fn from_commit(commit: &Commit) -> Self {
+        let full_hash = commit.id().to_string();
+        let author = commit.author();
+        CommitInfo {
+            hash: full_hash.chars().take(7).collect(),
+            full_hash,
+            message: commit.summary().unwrap_or("").to_string(),
+            date: format_time(author.when()),
+            author: author.name().unwrap_or("").to_string(),
+            author_email: author.email().unwrap_or("").to_string(),
+        }
+    }
+}
+
+fn head_commit(repo: &Repository) -> Option<Commit<'_>> {
+    repo.head().ok()?.peel_to_commit().ok()
+}
+
+fn head_tree(repo: &Repository) -> Option<Tree<'_>> {
+    head_commit(repo)?.tree().ok()
+}
+
+pub fn head(repo: &Repository) -> Option<CommitInfo> {
+    head_commit(repo).map(|c| CommitInfo::from_commit(&c))
+}
+
+/// Commits reachable from HEAD, newest first. An unborn branch has no history.
+pub fn log(repo: &Repository, limit: usize) -> Result<Vec<CommitInfo>, GitNotesError> {
+    if head_commit(repo).is_none() {
+        return Ok(vec![]);
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+    revwalk
+        .take(limit)
+        .map(|oid| {
+            let commit = repo.find_commit(oid?)?;
+            Ok(CommitInfo::from_commit(&commit))
+        })
+        .collect()
+}
+
+pub fn commit_count(repo: &Repository) -> u32 {
+    let Ok(mut revwalk) = repo.revwalk() else {
+        return 0;
+    };
+    if revwalk.push_head().is_err() {
+        return 0;
+    }
+    revwalk.count() as u32
+}
+
+/// The oldest root commit reachable from HEAD.
+pub fn first_commit(repo: &Repository) -> Option<CommitInfo> {
+    let mut revwalk = repo.revwalk().ok()?;
+    revwalk.push_head().ok()?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::TIME | Sort::REVERSE)
+        .ok()?;
+    let oid = revwalk.flatten().next()?;
+    repo.find_commit(oid).ok().map(|c| CommitInfo::from_commit(&c))
+}
+
+/// Distinct author emails in the history, stopping once `limit` have been seen.
+pub fn author_emails(repo: &Repository, limit: usize) -> HashSet<String> {
+    let mut emails = HashSet::new();
+    let Ok(mut revwalk) = repo.revwalk() else {
+        return emails;
+    };
+    if revwalk.push_head().is_err() {
+        return emails;
+    }
+    for oid in revwalk.flatten() {
+        if let Ok(commit) = repo.find_commit(oid) {
+            if let Some(email) = commit.author().email() {
+                emails.insert(email.to_string());
+            }
+        }
+        if emails.len() >= limit {
+            break;
+        }
+    }
+    emails
+}
+
+/// Branch HEAD is on, even before its first commit; `None` when detached.
+pub fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target()?
+        .strip_prefix("refs/heads/")
+        .map(|s| s.to_string())
+}
+
+pub fn branch_count(repo: &Repository) -> u32 {
+    repo.branches(Some(BranchType::Local))
+        .map(|branches| branches.count() as u32)
+        .unwrap_or(0)
+}
+
+pub fn remote_url(repo: &Repository, name: &str) -> Option<String> {
+    let remote = repo.find_remote(name).ok()?;
+    remote.url().map(|s| s.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileStatus {
+    pub path: String,
+    /// Porcelain-style code: M, A, D, R, ??, UU, AA, ...
+    pub status: String,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+fn status_code(status: Status) -> String {
+    if status.contains(Status::WT_NEW) && !status.intersects(Status::INDEX_NEW) {
+        return "??".to_string();
+    }
+    let index = if status.contains(Status::INDEX_NEW) {
+        'A'
+    } else if status.contains(Status::INDEX_MODIFIED) {
+        'M'
+    } else if status.contains(Status::INDEX_DELETED) {
+        'D'
+    } else if status.contains(Status::INDEX_RENAMED) {
+        'R'
+    } else if status.contains(Status::INDEX_TYPECHANGE) {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.contains(Status::WT_MODIFIED) {
+        'M'
+    } else if status.contains(Status::WT_DELETED) {
+        'D'
+    } else if status.contains(Status::WT_RENAMED) {
+        'R'
+    } else if status.contains(Status::WT_TYPECHANGE) {
+        'T'
+    } else {
+        ' '
+    };
+    format!("{}{}", index, worktree).trim().to_string()
+}
+
+/// Line counts per path for everything that differs from HEAD (staged or not).
+fn changes_since_head<'r>(repo: &'r Repository, pathspec: Option<&str>) -> Result<Diff<'r>, GitNotesError> {
+    let mut opts = DiffOptions::new();
+    if let Some(pathspec) = pathspec {
+        opts.pathspec(pathspec).disable_pathspec_match(true);
+    }
+    repo.diff_tree_to_workdir_with_index(head_tree(repo).as_ref(), Some(&mut opts))
+        .map_err(GitNotesError::from)
+}
+
+fn line_stats(diff: &Diff) -> HashMap<String, (u32, u32)> {
+    let mut stats = HashMap::new();
+    for idx in 0..diff.deltas().len() {
+        let Ok(Some(patch)) = git2::Patch::from_diff(diff, idx) else {
+            continue;
+        };
+        let Some(path) = patch.delta().new_file().path().or(patch.delta().old_file().path())
+        else {
+            continue;
+        };
+        let (_, insertions, deletions) = patch.line_stats().unwrap_or((0, 0, 0));
+        stats.insert(
+            path.to_string_lossy().to_string(),
+            (insertions as u32, deletions as u32),
+        );
+    }
+    stats
+}
+
+/// Working tree status, like `git status --porcelain`: untracked folders are reported once,
+/// with a trailing `/`.
+pub fn status(repo: &Repository) -> Result<Vec<FileStatus>, GitNotesError> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false)
+        .renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut opts))?;
+
+    let conflicts = conflict_kinds(repo);
+    let stats = changes_since_head(repo, None)
+        .map(|diff| line_stats(&diff))
+        .unwrap_or_default();
+
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| {
+            let path = entry
+                .head_to_index()
+                .and_then(|d| d.new_file().path().map(|p| p.to_string_lossy().to_string()))
+                .or_else(|| entry.path().map(|p| p.to_string()))?;
+            let status = if entry.status().contains(Status::CONFLICTED) {
+                conflicts.get(&path).cloned().unwrap_or_else(|| "UU".to_string())
+            } else {
+                status_code(entry.status())
+            };
+            let (insertions, deletions) = stats
+                .get(path.trim_end_matches('/'))
+                .copied()
+                .unwrap_or((0, 0));
+            Some(FileStatus {
+                path,
+                status,
+                insertions,
+                deletions,
+            })
+        })
+        .collect())
+}
+
+/// What git knows about one file in the work tree.
+pub struct PathStatus {
+    pub is_tracked: bool,
+    pub is_dirty: bool,
+}
+
+pub fn path_status(repo: &Repository, rel_path: &str) -> PathStatus {
+    let is_tracked = repo
+        .index()
+        .ok()
+        .and_then(|index| index.get_path(Path::new(rel_path), 0))
+        .is_some();
+    let changed = repo
+        .status_file(Path::new(rel_path))
+        .map(|s| !s.is_empty() && !s.contains(Status::IGNORED))
+        .unwrap_or(false);
+    PathStatus {
+        is_tracked,
+        is_dirty: changed || !is_tracked,
+    }
+}
+
+fn patch_text(diff: &Diff) -> Result<String, GitNotesError> {
+    let mut text = Vec::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            text.push(line.origin() as u8);
+        }
+        text.extend_from_slice(line.content());
+        true
+    })?;
+    Ok(String::from_utf8_lossy(&text).to_string())
+}
+
+/// Unified diff of one path against HEAD, covering staged and unstaged changes.
+pub fn diff_path(repo: &Repository, rel_path: &str) -> Result<String, GitNotesError> {
+    patch_text(&changes_since_head(repo, Some(rel_path))?)
+}
+
+/// Changes a commit introduced relative to its first parent, with renames detected like `git show`.
+fn commit_changes<'r>(repo: &'r Repository, commit: &Commit) -> Result<Diff<'r>, git2::Error> {
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+    let tree = commit.tree()?;
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+    diff.find_similar(None)?;
+    Ok(diff)
+}
+
+/// Unified diff a commit introduced, relative to its first parent.
+pub fn commit_diff(repo: &Repository, rev: &str) -> Result<String, GitNotesError> {
+    let commit = repo.revparse_single(rev).and_then(|o| o.peel_to_commit())?;
+    patch_text(&commit_changes(repo, &commit)?)
+}
+
+/// Lines added and removed by a commit, relative to its first parent.
+pub fn commit_stats(repo: &Repository, full_hash: &str) -> (u32, u32) {
+    Oid::from_str(full_hash)
+        .and_then(|oid| repo.find_commit(oid))
+        .and_then(|commit| commit_changes(repo, &commit)?.stats())
+        .map(|s| (s.insertions() as u32, s.deletions() as u32))
+        .unwrap_or((0, 0))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileRevision {
+    #[serde(flatten)]
+    pub commit: CommitInfo,
+    /// Repo-relative path of the file in this commit (differs from today's after a rename)
+    pub path: String,
+    pub status: String, // A, M, R, D
+}
+
+/// Commits that touched a file, newest first, following it across renames.
+pub fn file_history(
+    repo: &Repository,
+    rel_path: &str,
+    limit: Option<usize>,
+) -> Result<Vec<FileRevision>, GitNotesError> {
+    walk_file_history(repo, rel_path, None, limit).map(|(revisions, _)| revisions)
+}
+
+/// Where the file now at `rel_path` was as of `rev` (a hash, branch or `HEAD~3`), following
+/// renames back through only the commits since then.
+pub fn path_at(repo: &Repository, rel_path: &str, rev: &str) -> Result<String, GitNotesError> {
+    let target = repo.revparse_single(rev).and_then(|o| o.peel_to_commit())?;
+    walk_file_history(repo, rel_path, Some(target.id()), None).map(|(_, path)| path)
+}
+
+/// The file's history from HEAD back to (not including) `since` and its ancestors, or to
+/// the root, along with the path it had where the walk stopped.
+fn walk_file_history(
+    repo: &Repository,
+    rel_path: &str,
+    since: Option<Oid>,
+    limit: Option<usize>,
+) -> Result<(Vec<FileRevision>, String), GitNotesError> {
+    let mut revisions = Vec::new();
+    if head_commit(repo).is_none() {
+        return Ok((revisions, rel_path.to_string()));
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    if let Some(since) = since {
+        revwalk.hide(since)?;
+    }
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+    let blob_at = |tree: Option<&Tree>, path: &str| -> Option<Oid> {
+        tree?.get_path(Path::new(path)).ok().map(|e| e.id())
+    };
+
+    let mut current = rel_path.to_string();
+    for oid in revwalk {
+        if limit.is_some_and(|limit| revisions.len() >= limit) {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let parent_trees: Vec<Tree> = commit.parents().filter_map(|p| p.tree().ok()).collect();
+
+        let here = blob_at(Some(&tree), &current);
+        // Unchanged relative to any parent (including the side a merge took it from)
+        if parent_trees
+            .iter()
+            .any(|parent| blob_at(Some(parent), &current) == here)
+        {
+            continue;
+        }
+        let parent_tree = parent_trees.first();
+
+        let status = match (blob_at(parent_tree, &current), here) {
+            (Some(_), Some(_)) => "M",
+            (Some(_), None) => "D",
+            (None, None) => continue,
+            (None, Some(_)) => {
+                // New at this path: either added, or renamed from somewhere else
+                match renamed_from(repo, parent_tree, &tree, &current) {
+                    Some(old_path) => {
+                        revisions.push(FileRevision {
+                            commit: CommitInfo::from_commit(&commit),
+                            path: current.clone(),
+                            status: "R".to_string(),
+                        });
+                        current = old_path;
+                        continue;
+                    }
+                    None => "A",
+                }
+            }
+        };
+
+        revisions.push(FileRevision {
+            commit: CommitInfo::from_commit(&commit),
+            path: current.clone(),
+            status: status.to_string(),
+        });
+    }
+
+    Ok((revisions, current))
+}
+
+/// The newest commit that changed `rel_path`, looking back through at most `max_commits`
+/// commits. Unlike `file_history` it doesn't follow renames.
+pub fn last_commit(repo: &Repository, rel_path: &str, max_commits: usize) -> Option<CommitInfo> {
+    let mut revwalk = repo.revwalk().ok()?;
+    revwalk.push_head().ok()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME).ok()?;
+
+    let blob_at = |tree: Tree| tree.get_path(Path::new(rel_path)).ok().map(|e| e.id());
+    revwalk.flatten().take(max_commits).find_map(|oid| {
+        let commit = repo.find_commit(oid).ok()?;
+        let here = blob_at(commit.tree().ok()?)?;
+        // The same as in a parent means an older commit made this change
+        let changed = commit
+            .parents()
+            .all(|parent| parent.tree().ok().and_then(blob_at) != Some(here));
+        changed.then(|| CommitInfo::from_commit(&commit))
+    })
+}
+
+/// Author (`Name <email>`) of the last commit to touch each of `rel_paths`, found in a single
+/// walk of the history, or of its newest `max_commits` commits. Paths that were never committed
+/// (or not in that stretch) are left out.
+pub fn last_authors(
+    repo: &Repository,
+    rel_paths: &HashSet<String>,
+    max_commits: Option<usize>,
+) -> HashMap<String, String> {
+    let mut authors = HashMap::new();
+    let Ok(mut revwalk) = repo.revwalk() else {
+        return authors;
+    };
+    if revwalk.push_head().is_err() || revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME).is_err() {
+        return authors;
+    }
+
+    for oid in revwalk.flatten().take(max_commits.unwrap_or(usize::MAX)) {
+        if authors.len() == rel_paths.len() {
+            break;
+        }
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        let Ok(tree) = commit.tree() else {
+            continue;
+        };
+        let parent_tree = commit.parents().next().and_then(|p| p.tree().ok());
+        let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None) else {
+            continue;
+        };
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            let path = path.to_string_lossy().to_string();
+            if rel_paths.contains(&path) && !authors.contains_key(&path) {
+                let author = commit.author();
+                authors.insert(
+                    path,
+                    format!(
+                        "{} <{}>",
+                        author.name().unwrap_or(""),
+                        author.email().unwrap_or("")
+                    ),
+                );
+            }
+        }
+    }
+    authors
+}
+
+fn renamed_from(
+    repo: &Repository,
+    old_tree: Option<&Tree>,
+    new_tree: &Tree,
+    new_path: &str,
+) -> Option<String> {
+    let mut diff = repo
+        .diff_tree_to_tree(old_tree, Some(new_tree), None)
+        .ok()?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .ok()?;
+    diff.deltas()
+        .filter(|d| d.status() == git2::Delta::Renamed)
+        .find(|d| d.new_file().path() == Some(Path::new(new_path)))
+        .and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().to_string()))
+}
+
+/// Content of a file as of `rev`.
+pub fn file_at(repo: &Repository, rev: &str, rel_path: &str) -> Result<String, GitNotesError> {
+    let tree = repo.revparse_single(rev).and_then(|o| o.peel_to_tree())?;
+    let entry = tree.get_path(Path::new(rel_path)).map_err(|_| {
+        GitNotesError::git(
+            GitErrorKind::Other,
+            format!("{} does not exist at {}", rel_path, rev),
+        )
+    })?;
+    let blob = repo.find_blob(entry.id())?;
+    Ok(String::from_utf8_lossy(blob.content()).to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlameLine {
+    pub line: usize,
+    pub content: String,
+    /// `None` for lines that haven't been committed yet
+    pub commit: Option<CommitInfo>,
+}
+
+/// Who last changed each line of the file as it is on disk now.
+pub fn blame(repo: &Repository, rel_path: &str, content: &str) -> Result<Vec<BlameLine>, GitNotesError> {
+    // A file that was never committed has no blame; every line is simply new
+    let committed = match repo.blame_file(Path::new(rel_path), None) {
+        Ok(committed) => Some(committed),
+        Err(e) if e.code() == ErrorCode::NotFound => None,
+        Err(e) => return Err(GitNotesError::from(e)),
+    };
+    let blame = committed
+        .as_ref()
+        .map(|c| c.blame_buffer(content.as_bytes()))
+        .transpose()?;
+
+    let mut commits: HashMap<Oid, Option<CommitInfo>> = HashMap::new();
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(i, text)| {
+            let commit = blame.as_ref().and_then(|b| b.get_line(i + 1)).and_then(|hunk| {
+                let oid = hunk.final_commit_id();
+                commits
+                    .entry(oid)
+                    .or_insert_with(|| {
+                        repo.find_commit(oid)
+                            .ok()
+                            .map(|c| CommitInfo::from_commit(&c))
+                    })
+                    .clone()
+            });
+            BlameLine {
+                line: i + 1,
+                content: text.to_string(),
+                commit,
+            }
+        })
+        .collect())
+}
+
+fn signature(repo: &Repository) -> Result<Signature<'static>, GitNotesError> {
+    repo.signature()
+        .map_err(|_| {
+            GitNotesError::git(
+                GitErrorKind::Other,
+                "Set user.name and user.email in your git config to commit",
+            )
+        })
+}
+
+fn commit_tree(repo: &Repository, tree_id: Oid, message: &str) -> Result<Option<Oid>, GitNotesError> {
+    let parent = head_commit(repo);
+    if parent.as_ref().map(|p| p.tree_id()) == Some(tree_id) {
+        return Ok(None);
+    }
+    let tree = repo.find_tree(tree_id)?;
+    let sig = signature(repo)?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+        .map(Some)
+        .map_err(GitNotesError::from)
+}
+
+/// Stage everything (`git add -A`) and commit. Returns `None` when there was nothing to commit.
+pub fn commit_all(repo: &Repository, message: &str) -> Result<Option<Oid>, GitNotesError> {
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    index.update_all(["*"].iter(), None)?;
+    index.write()?;
+    if index.has_conflicts() {
+        return Err(GitNotesError::git(
+            GitErrorKind::Conflicts,
+            "Resolve conflicts before committing",
+        ));
+    }
+    let tree_id = index.write_tree()?;
+    commit_tree(repo, tree_id, message)
+}
+
+/// Commit just this path (`git commit -- <path>`), leaving anything else staged as it was.
+pub fn commit_path(repo: &Repository, rel_path: &str, message: &str) -> Result<Option<Oid>, GitNotesError> {
+    commit_paths(repo, &[rel_path], message)
+}
+
+/// Commit just these paths in one commit (`git commit -- <paths>`), e.g. a renamed note and
+/// the notes whose links to it were rewritten.
+pub fn commit_paths(
+    repo: &Repository,
+    rel_paths: &[&str],
+    message: &str,
+) -> Result<Option<Oid>, GitNotesError> {
+    let mut index = repo.index()?;
+    for rel_path in rel_paths {
+        let path = Path::new(rel_path);
+        let exists = repo
+            .workdir()
+            .map(|w| w.join(rel_path).exists())
+            .unwrap_or(false);
+        if exists {
+            index.add_path(path)?;
+        } else {
+            index.remove_path(path)?;
+        }
+    }
+    index.write()?;
+
+    // Start from HEAD's tree and apply only these paths' staged entries
+    let mut partial = Index::new()?;
+    if let Some(tree) = head_tree(repo) {
+        partial.read_tree(&tree)?;
+    }
+    for rel_path in rel_paths {
+        let path = Path::new(rel_path);
+        match index.get_path(path, 0) {
+            Some(entry) => partial.add(&entry)?,
+            None => {
+                let _ = partial.remove_path(path);
+            }
+        }
+    }
+    let tree_id = partial.write_tree_to(repo)?;
+    commit_tree(repo, tree_id, message)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncState {
+    Synced,
+    Ahead,
+    Behind,
+    Diverged,
+    NoRemote,
+}
+
+/// How the current branch compares to its upstream, as of the last fetch.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncStatus {
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub last_fetch: Option<String>,
+    pub sync_state: SyncState,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        SyncStatus {
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            last_fetch: None,
+            sync_state: SyncState::NoRemote,
+        }
+    }
+}
+
+/// The current branch's full ref name and its upstream's, if one is configured.
+fn upstream_refs(repo: &Repository) -> Option<(String, String)> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let local = head.name()?.to_string();
+    let upstream = git2::Branch::wrap(head).upstream().ok()?;
+    let upstream = upstream.get().name()?.to_string();
+    Some((local, upstream))
+}
+
+pub fn sync_status(repo: &Repository) -> SyncStatus {
+    // Last fetch time is the mtime of FETCH_HEAD
+    let last_fetch = fs::metadata(repo.path().join("FETCH_HEAD"))
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|time| DateTime::<Utc>::from(time).format("%Y-%m-%dT%H:%M:%S%:z").to_string());
+
+    let Some((_, upstream)) = upstream_refs(repo) else {
+        return SyncStatus {
+            last_fetch,
+            ..SyncStatus::default()
+        };
+    };
+
+    let (ahead, behind) = (|| {
+        let local = repo.head().ok()?.target()?;
+        let remote = repo.refname_to_id(&upstream).ok()?;
+        repo.graph_ahead_behind(local, remote).ok()
+    })()
+    .unwrap_or((0, 0));
+    let (ahead, behind) = (ahead as u32, behind as u32);
+
+    let sync_state = match (ahead, behind) {
+        (0, 0) => SyncState::Synced,
+        (_, 0) => SyncState::Ahead,
+        (0, _) => SyncState::Behind,
+        _ => SyncState::Diverged,
+    };
+
+    SyncStatus {
+        upstream: Some(
+            upstream
+                .strip_prefix("refs/remotes/")
+                .unwrap_or(&upstream)
+                .to_string(),
+        ),
+        ahead,
+        behind,
+        last_fetch,
+        sync_state,
+    }
+}
+
+/// Why a fetch, push or clone failed.
+#[derive(Debug)]
+pub enum RemoteError {
+    AuthFailed,
+    Offline,
+    Other(String),
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteError::AuthFailed => write!(f, "Authentication failed"),
+            RemoteError::Offline => write!(f, "Could not reach the remote"),
+            RemoteError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<RemoteError> for GitNotesError {
+    fn from(e: RemoteError) -> Self {
+        let kind = match e {
+            RemoteError::AuthFailed => GitErrorKind::AuthFailed,
+            RemoteError::Offline => GitErrorKind::Offline,
+            RemoteError::Other(_) => GitErrorKind::Other,
+        };
+        GitNotesError::git(kind, e.to_string())
+    }
+}
+
+/// Credential lookup shared by fetch, push and clone: ssh-agent then default key files for
+/// ssh, git's credential helpers for https. Each source is tried once so a rejected
+/// credential fails instead of looping.
+struct Credentials {
+    attempts: Cell<u32>,
+    asked: Cell<bool>,
+}
+
+impl Credentials {
+    fn new() -> Self {
+        Credentials {
+            attempts: Cell::new(0),
+            asked: Cell::new(false),
+        }
+    }
+
+    fn callbacks(&self) -> RemoteCallbacks<'_> {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, allowed| {
+            self.asked.set(true);
+            let attempt = self.attempts.get();
+            self.attempts.set(attempt + 1);
+
+            if allowed.contains(CredentialType::USERNAME) {
+                return Cred::username(username.unwrap_or("git"));
+            }
+            if allowed.contains(CredentialType::SSH_KEY) {
+                let user = username.unwrap_or("git");
+                if attempt == 0 {
+                    return Cred::ssh_key_from_agent(user);
+                }
+                let keys = ["id_ed25519", "id_ecdsa", "id_rsa"];
+                let key = dirs::home_dir().and_then(|home| {
+                    keys.iter()
+                        .map(|k| home.join(".ssh").join(k))
+                        .filter(|k| k.exists())
+                        .nth(attempt as usize - 1)
+                });
+                if let Some(key) = key {
+                    return Cred::ssh_key(user, None, &key, None);
+                }
+            } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && attempt == 0 {
+                let config = git2::Config::open_default()?;
+                return Cred::credential_helper(&config, url, username);
+            } else if allowed.contains(CredentialType::DEFAULT) && attempt == 0 {
+                return Cred::default();
+            }
+            Err(git2::Error::from_str("authentication failed"))
+        });
+        callbacks
+    }
+
+    fn classify(&self, e: git2::Error) -> RemoteError {
+        let message = e.message().to_lowercase();
+        let auth_markers = ["authentication", "permission denied", "401", "403"];
+        let offline_markers = [
+            "resolve",
+            "unreachable",
+            "connection refused",
+            "timed out",
+            "failed to connect",
+        ];
+
+        if e.code() == ErrorCode::Auth
+            || (self.asked.get() && auth_markers.iter().any(|m| message.contains(m)))
+        {
+            RemoteError::AuthFailed
+        } else if e.class() == ErrorClass::Net
+            || offline_markers.iter().any(|m| message.contains(m))
+        {
+            RemoteError::Offline
+        } else {
+            RemoteError::Other(e.message().to_string())
+        }
+    }
+}
+
+fn upstream_remote_name(repo: &Repository) -> String {
+    upstream_refs(repo)
+        .and_then(|(local, _)| repo.branch_upstream_remote(&local).ok())
+        .and_then(|name| name.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| "origin".to_string())
+}
+
+/// Fetch the current branch's remote (origin if there's no upstream).
+pub fn fetch(repo: &Repository, prune: bool) -> Result<(), RemoteError> {
+    let mut remote = repo
+        .find_remote(&upstream_remote_name(repo))
+        .map_err(|e| RemoteError::Other(e.message().to_string()))?;
+    let credentials = Credentials::new();
+    let mut opts = FetchOptions::new();
+    opts.remote_callbacks(credentials.callbacks());
+    if prune {
+        opts.prune(FetchPrune::On);
+    }
+    remote
+        .fetch::<&str>(&[], Some(&mut opts), None)
+        .map_err(|e| credentials.classify(e))
+}
+
+/// Push the current branch to its upstream branch.
+pub fn push(repo: &Repository) -> Result<(), RemoteError> {
+    let (local, upstream) = upstream_refs(repo)
+        .ok_or_else(|| RemoteError::Other("No upstream branch configured".to_string()))?;
+    let remote_name = upstream_remote_name(repo);
+    let merge_ref = repo
+        .branch_upstream_merge(&local)
+        .ok()
+        .and_then(|r| r.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| {
+            let prefix = format!("refs/remotes/{}/", remote_name);
+            format!("refs/heads/{}", upstream.trim_start_matches(&prefix))
+        });
+
+    let mut remote = repo
+        .find_remote(&remote_name)
+        .map_err(|e| RemoteError::Other(e.message().to_string()))?;
+    let credentials = Credentials::new();
+    let rejected: RefCell<Option<String>> = RefCell::new(None);
+    {
+        let mut callbacks = credentials.callbacks();
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                *rejected.borrow_mut() = Some(format!("{} rejected: {}", refname, status));
+            }
+            Ok(())
+        });
+        let mut opts = PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        remote
+            .push(&[format!("{}:{}", local, merge_ref)], Some(&mut opts))
+            .map_err(|e| credentials.classify(e))?;
+    }
+
+    match rejected.into_inner() {
+        Some(reason) => Err(RemoteError::Other(reason)),
+        None => Ok(()),
+    }
+}
+
+pub fn clone(url: &str, path: &Path) -> Result<(), RemoteError> {
+    let credentials = Credentials::new();
+    let mut opts = FetchOptions::new();
+    opts.remote_callbacks(credentials.callbacks());
+    let mut builder = RepoBuilder::new();
+    builder.fetch_options(opts);
+    let result = builder.clone(url, path);
+    result.map(|_| ()).map_err(|e| credentials.classify(e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PendingOperation {
+    Merge,
+    Rebase,
+}
+
+/// Which integration, if any, is stopped waiting for conflict resolution.
+pub fn pending_operation(repo: &Repository) -> Option<PendingOperation> {
+    match repo.state() {
+        RepositoryState::Merge => Some(PendingOperation::Merge),
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge
+        | RepositoryState::ApplyMailboxOrRebase => Some(PendingOperation::Rebase),
+        _ => None,
+    }
+}
+
+fn entry_path(entry: &Option<git2::IndexEntry>) -> Option<String> {
+    entry
+        .as_ref()
+        .map(|e| String::from_utf8_lossy(&e.path).to_string())
+}
+
+/// Porcelain codes for conflicted paths: UU, AA, DU, UD, ...
+fn conflict_kinds(repo: &Repository) -> HashMap<String, String> {
+    let mut kinds = HashMap::new();
+    let Ok(index) = repo.index() else {
+        return kinds;
+    };
+    let Ok(conflicts) = index.conflicts() else {
+        return kinds;
+    };
+    for conflict in conflicts.flatten() {
+        let code = match (&conflict.ancestor, &conflict.our, &conflict.their) {
+            (None, Some(_), Some(_)) => "AA",
+            (Some(_), None, Some(_)) => "DU",
+            (Some(_), Some(_), None) => "UD",
+            (None, Some(_), None) => "AU",
+            (None, None, Some(_)) => "UA",
+            _ => "UU",
+        };
+        let path = entry_path(&conflict.our)
+            .or_else(|| entry_path(&conflict.their))
+            .or_else(|| entry_path(&conflict.ancestor));
+        if let Some(path) = path {
+            kinds.insert(path, code.to_string());
+        }
+    }
+    kinds
+}
+
+pub fn conflicted_paths(repo: &Repository) -> Vec<String> {
+    let mut paths: Vec<String> = conflict_kinds(repo).into_keys().collect();
+    paths.sort();
+    paths
+}
+
+/// Each side of a conflicted file. A side is `None` when the file doesn't exist there.
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<String>,
+    /// This repository's version
+    pub ours: Option<String>,
+    /// The incoming (remote) version
+    pub theirs: Option<String>,
+}
+
+pub fn conflicts(repo: &Repository) -> Result<Vec<Conflict>, GitNotesError> {
+    let index = repo.index()?;
+    let blob = |entry: &Option<git2::IndexEntry>| -> Option<String> {
+        let blob = repo.find_blob(entry.as_ref()?.id).ok()?;
+        Some(String::from_utf8_lossy(blob.content()).to_string())
+    };
+    // A rebase replays local commits onto upstream, so git's "ours" there is the remote side
+    let rebasing = pending_operation(repo) == Some(PendingOperation::Rebase);
+
+    let mut result = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let Some(path) = entry_path(&conflict.our)
+            .or_else(|| entry_path(&conflict.their))
+            .or_else(|| entry_path(&conflict.ancestor))
+        else {
+            continue;
+        };
+        let (ours, theirs) = if rebasing {
+            (blob(&conflict.their), blob(&conflict.our))
+        } else {
+            (blob(&conflict.our), blob(&conflict.their))
+        };
+        result.push(Conflict {
+            path,
+            base: blob(&conflict.ancestor),
+            ours,
+            theirs,
+        });
+    }
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+/// Three-way merge of plain text, outside any repository. Returns the merged text and whether
+/// it merged cleanly; if not, the text carries the usual `<<<<<<<` conflict markers.
+pub fn merge_text(base: &str, ours: &str, theirs: &str) -> Result<(String, bool), GitNotesError> {
+    let odb = git2::Odb::new()?;
+    odb.add_new_mempack_backend(1)?;
+    let repo = Repository::from_odb(odb)?;
+
+    let entry = |text: &str| -> Result<git2::IndexEntry, GitNotesError> {
+        Ok(git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: text.len() as u32,
+            id: repo.blob(text.as_bytes())?,
+            flags: 0,
+            flags_extended: 0,
+            path: b"note.md".to_vec(),
+        })
+    };
+    let mut options = git2::MergeFileOptions::new();
+    options.our_label("yours").their_label("on disk");
+    let result = repo.merge_file_from_index(
+        &entry(base)?,
+        &entry(ours)?,
+        &entry(theirs)?,
+        Some(&mut options),
+    )?;
+    Ok((
+        String::from_utf8_lossy(result.content()).to_string(),
+        result.is_automergeable(),
+    ))
+}
+
+/// Mark a conflicted path resolved with whatever is now in the work tree (or its absence).
+pub fn mark_resolved(repo: &Repository, rel_path: &str) -> Result<(), GitNotesError> {
+    let mut index = repo.index()?;
+    let path = Path::new(rel_path);
+    let exists = repo
+        .workdir()
+        .map(|w| w.join(rel_path).exists())
+        .unwrap_or(false);
+    if exists {
+        index.add_path(path)?;
+    } else {
+        index.conflict_remove(path)?;
+        let _ = index.remove_path(path);
+    }
+    index.write().map_err(GitNotesError::from)
+}
+
+/// Outcome of integrating upstream changes into the current branch.
+pub enum Integration {
+    Done,
+    Conflicts(Vec<String>),
+}
+
+/// Bring the current branch up to date with its upstream, by merge or by rebasing local
+/// commits. Conflicts leave the merge/rebase in progress for `continue_operation` or
+/// `abort_operation`.
+pub fn integrate_upstream(repo: &Repository, use_merge: bool) -> Result<Integration, GitNotesError> {
+    let (_, upstream) = upstream_refs(repo).ok_or_else(|| {
+        GitNotesError::git(GitErrorKind::NoUpstream, "No upstream branch configured")
+    })?;
+    let upstream_ref = repo.find_reference(&upstream)?;
+    let theirs = repo.reference_to_annotated_commit(&upstream_ref)?;
+
+    let (analysis, _) = repo.merge_analysis(&[&theirs])?;
+    if analysis.is_up_to_date() {
+        return Ok(Integration::Done);
+    }
+    if analysis.is_fast_forward() || analysis.is_unborn() {
+        return fast_forward(repo, &theirs).map(|_| Integration::Done);
+    }
+
+    if use_merge {
+        repo.merge(&[&theirs], None, None)?;
+        // libgit2 names the full ref; match git's "Merge remote-tracking branch 'origin/main'"
+        let short_name = upstream.strip_prefix("refs/remotes/").unwrap_or(&upstream);
+        fs::write(
+            repo.path().join("MERGE_MSG"),
+            format!("Merge remote-tracking branch '{}'\n", short_name),
+        )?;
+        let conflicts = conflicted_paths(repo);
+        if !conflicts.is_empty() {
+            return Ok(Integration::Conflicts(conflicts));
+        }
+        commit_merge(repo)?;
+        Ok(Integration::Done)
+    } else {
+        let mut rebase = repo.rebase(None, Some(&theirs), None, None)?;
+        run_rebase(repo, &mut rebase)
+    }
+}
+
+fn fast_forward(repo: &Repository, target: &AnnotatedCommit) -> Result<(), GitNotesError> {
+    let commit = repo.find_object(target.id(), Some(ObjectType::Commit))?;
+    repo.checkout_tree(&commit, Some(CheckoutBuilder::new().safe()))?;
+    let head = repo.head()?;
+    let name = head
+        .name()
+        .ok_or_else(|| GitNotesError::git(GitErrorKind::Other, "Invalid HEAD"))?
+        .to_string();
+    repo.reference(&name, target.id(), true, "fast-forward")?;
+    Ok(())
+}
+
+fn commit_merge(repo: &Repository) -> Result<(), GitNotesError> {
+    let merge_heads = fs::read_to_string(repo.path().join("MERGE_HEAD")).unwrap_or_default();
+    let heads: Vec<Oid> = merge_heads
+        .lines()
+        .filter_map(|line| Oid::from_str(line.trim()).ok())
+        .collect();
+
+    let mut index = repo.index()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let head = head_commit(repo)
+        .ok_or_else(|| GitNotesError::git(GitErrorKind::Other, "No commit to merge into"))?;
+    let mut parents = vec![head];
+    for oid in heads {
+        parents.push(repo.find_commit(oid)?);
+    }
+    let parents: Vec<&Commit> = parents.iter().collect();
+
+    let message = repo
+        .message()
+        .unwrap_or_else(|_| "Merge remote changes".to_string());
+    let sig = signature(repo)?;
+    repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        message.trim_end(),
+        &tree,
+        &parents,
+    )?;
+    repo.cleanup_state().map_err(GitNotesError::from)
+}
+
+/// Commit the current rebase step, skipping it if its changes are already upstream.
+fn commit_rebase_step(rebase: &mut Rebase, sig: &Signature) -> Result<(), GitNotesError> {
+    match rebase.commit(None, sig, None) {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::Applied => Ok(()),
+        Err(e) => Err(GitNotesError::from(e)),
+    }
+}
+
+fn run_rebase(repo: &Repository, rebase: &mut Rebase) -> Result<Integration, GitNotesError> {
+    let sig = signature(repo)?;
+    while let Some(op) = rebase.next() {
+        op?;
+        let conflicts = conflicted_paths(repo);
+        if !conflicts.is_empty() {
+            return Ok(Integration::Conflicts(conflicts));
+        }
+        commit_rebase_step(rebase, &sig)?;
+    }
+    rebase.finish(Some(&sig))?;
+    Ok(Integration::Done)
+}
+
+/// Finish a merge or rebase once its conflicts are resolved. A rebase may stop again on the
+/// next conflicting commit.
+pub fn continue_operation(repo: &Repository) -> Result<Integration, GitNotesError> {
+    let remaining = conflicted_paths(repo);
+    if !remaining.is_empty() {
+        return Err(GitNotesError::git(
+            GitErrorKind::Conflicts,
+            format!("Unresolved conflicts: {}", remaining.join(", ")),
+        ));
+    }
+
+    match pending_operation(repo) {
+        Some(PendingOperation::Merge) => {
+            commit_merge(repo)?;
+            Ok(Integration::Done)
+        }
+        Some(PendingOperation::Rebase) => {
+            let mut rebase = repo.open_rebase(None)?;
+            commit_rebase_step(&mut rebase, &signature(repo)?)?;
+            run_rebase(repo, &mut rebase)
+        }
+        None => Err(GitNotesError::git(
+            GitErrorKind::Other,
+            "No merge or rebase in progress",
+        )),
+    }
+}
+
+/// Abandon an in-progress merge or rebase, restoring the branch as it was before. Aborting a
+/// merge only restores the paths the merge staged or left conflicted, so edits to other
+/// notes survive.
+pub fn abort_operation(repo: &Repository) -> Result<(), GitNotesError> {
+    match pending_operation(repo) {
+        Some(PendingOperation::Merge) => abort_merge(repo),
+        Some(PendingOperation::Rebase) => repo
+            .open_rebase(None)
+            .and_then(|mut rebase| rebase.abort())
+            .map_err(GitNotesError::from),
+        None => Err(GitNotesError::git(
+            GitErrorKind::Other,
+            "No merge or rebase in progress",
+        )),
+    }
+}
+
+fn abort_merge(repo: &Repository) -> Result<(), GitNotesError> {
+    let head = repo.head().and_then(|h| h.peel(ObjectType::Commit))?;
+    let tree = head.peel_to_tree()?;
+
+    // The merge stages everything it changed, so the index differs from HEAD exactly there
+    let mut touched: HashSet<String> = conflict_kinds(repo).into_keys().collect();
+    let index = repo.index()?;
+    let staged = repo.diff_tree_to_index(Some(&tree), Some(&index), None)?;
+    for delta in staged.deltas() {
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path() {
+                touched.insert(path.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+
+    if !touched.is_empty() {
+        let paths: Vec<&String> = touched.iter().collect();
+        repo.reset_default(Some(&head), &paths)?;
+
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force();
+        let mut restore = false;
+        for path in &touched {
+            if tree.get_path(Path::new(path)).is_ok() {
+                checkout.path(path);
+                restore = true;
+            } else {
+                // Added by the merge; HEAD has nothing to restore it to
+                let file = workdir_path(repo, path)?;
+                if file.exists() {
+                    fs::remove_file(&file).map_err(|e| GitNotesError::io(&file, e))?;
+                }
+            }
+        }
+        if restore {
+            repo.checkout_head(Some(&mut checkout))?;
+        }
+    }
+    repo.cleanup_state().map_err(GitNotesError::from)
+}
+
+/// Work tree path for a repo-relative path.
+pub fn workdir_path(repo: &Repository, rel_path: &str) -> Result<PathBuf, GitNotesError> {
+    Ok(repo
+        .workdir()
+        .ok_or_else(|| GitNotesError::git(GitErrorKind::Other, "Repository has no working tree"))?
+        .join(rel_path))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use git2::RepositoryInitOptions;
+
+    pub(crate) fn configure(path: &Path) -> Repository {
+        let repo = Repository::open(path).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    pub(crate) fn write(repo: &Repository, rel_path: &str, content: &str) {
+        let path = workdir_path(repo, rel_path).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub(crate) fn read(repo: &Repository, rel_path: &str) -> String {
+        fs::read_to_string(workdir_path(repo, rel_path).unwrap()).unwrap()
+    }
+
+    pub(crate) fn commit(repo: &Repository, rel_path: &str, content: &str, message: &str) -> Oid {
+        write(repo, rel_path, content);
+        commit_all(repo, message).unwrap().unwrap()
+    }
+
+    /// A bare remote whose main branch has one commit with `notes/a.md`.
+    pub(crate) fn bare_remote(root: &Path) -> PathBuf {
+        let bare = root.join("remote.git");
+        Repository::init_opts(
+            &bare,
+            RepositoryInitOptions::new().bare(true).initial_head("main"),
+        )
+        .unwrap();
+
+        let seed_path = root.join("seed");
+        Repository::init_opts(
+            &seed_path,
+            RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        let seed = configure(&seed_path);
+        commit(&seed, "notes/a.md", "one\ntwo\nthree\n", "Initial");
+        seed.remote("origin", bare.to_str().unwrap())
+            .unwrap()
+            .push(&["refs/heads/main:refs/heads/main"], None)
+            .unwrap();
+        bare
+    }
+
+    pub(crate) fn clone_of(root: &Path, bare: &Path, name: &str) -> Repository {
+        let path = root.join(name);
+        clone(bare.to_str().unwrap(), &path).unwrap();
+        configure(&path)
+    }
+
+    /// `theirs` pushes a change to the first line of `notes/a.md` and a new note, `ours`
+    /// commits a different first line and fetches.
+    fn diverge(theirs: &Repository, ours: &Repository) {
+        write(theirs, "notes/new.md", "from upstream\n");
+        commit(theirs, "notes/a.md", "ONE\ntwo\nthree\n", "Shout");
+        push(theirs).unwrap();
+        commit(ours, "notes/a.md", "uno\ntwo\nthree\n", "Translate");
+        fetch(ours, false).unwrap();
+    }
+
+    #[test]
+    fn aborting_a_merge_keeps_unrelated_edits() {
+        let root = TempDir::new("git-abort-merge");
+        let bare = bare_remote(&root);
+        let theirs = clone_of(&root, &bare, "theirs");
+        let ours = clone_of(&root, &bare, "ours");
+        commit(&ours, "notes/b.md", "committed\n", "Add b");
+        diverge(&theirs, &ours);
+        let head = ours.head().unwrap().target();
+
+        // Edits that have nothing to do with the merge
+        write(&ours, "notes/b.md", "edited, not committed\n");
+        write(&ours, "notes/c.md", "brand new\n");
+
+        match integrate_upstream(&ours, true).unwrap() {
+            Integration::Conflicts(paths) => assert_eq!(paths, vec!["notes/a.md"]),
+            Integration::Done => panic!("expected a conflict"),
+        }
+        assert!(workdir_path(&ours, "notes/new.md").unwrap().exists());
+
+        abort_operation(&ours).unwrap();
+        assert_eq!(pending_operation(&ours), None);
+        assert_eq!(ours.head().unwrap().target(), head);
+        assert_eq!(read(&ours, "notes/a.md"), "uno\ntwo\nthree\n");
+        assert!(!workdir_path(&ours, "notes/new.md").unwrap().exists());
+        assert_eq!(read(&ours, "notes/b.md"), "edited, not committed\n");
+        assert_eq!(read(&ours, "notes/c.md"), "brand new\n");
+        assert!(conflicted_paths(&ours).is_empty());
+    }
+
+    #[test]
+    fn clones_fetches_and_pushes_through_a_bare_remote() {
+        let root = TempDir::new("git-remote");
+        let bare = bare_remote(&root);
+        let a = clone_of(&root, &bare, "a");
+        let b = clone_of(&root, &bare, "b");
+        assert_eq!(current_branch(&a).as_deref(), Some("main"));
+        assert_eq!(read(&a, "notes/a.md"), "one\ntwo\nthree\n");
+
+        let pushed = commit(&a, "notes/b.md", "from a\n", "Add b");
+        push(&a).unwrap();
+        let remote = Repository::open_bare(&bare).unwrap();
+        assert_eq!(remote.refname_to_id("refs/heads/main").unwrap(), pushed);
+
+        fetch(&b, false).unwrap();
+        assert_eq!(b.refname_to_id("refs/remotes/origin/main").unwrap(), pushed);
+        // Only behind, so integrating is a fast-forward
+        assert!(matches!(
+            integrate_upstream(&b, false).unwrap(),
+            Integration::Done
+        ));
+        assert_eq!(b.head().unwrap().target(), Some(pushed));
+        assert_eq!(read(&b, "notes/b.md"), "from a\n");
+    }
+
+    #[test]
+    fn tracks_sync_state_against_the_upstream() {
+        let root = TempDir::new("git-sync-status");
+        let bare = bare_remote(&root);
+        let a = clone_of(&root, &bare, "a");
+        let b = clone_of(&root, &bare, "b");
+        let status = sync_status(&a);
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!(status.sync_state, SyncState::Synced);
+
+        let pushed = commit(&a, "notes/a.md", "from a\n", "A");
+        let status = sync_status(&a);
+        assert_eq!((status.ahead, status.behind), (1, 0));
+        assert_eq!(status.sync_state, SyncState::Ahead);
+        push(&a).unwrap();
+        let remote = Repository::open_bare(&bare).unwrap();
+        assert_eq!(remote.refname_to_id("refs/heads/main").unwrap(), pushed);
+        assert_eq!(sync_status(&a).sync_state, SyncState::Synced);
+
+        // b doesn't know until it fetches
+        assert_eq!(sync_status(&b).sync_state, SyncState::Synced);
+        fetch(&b, false).unwrap();
+        let status = sync_status(&b);
+        assert_eq!((status.ahead, status.behind), (0, 1));
+        assert_eq!(status.sync_state, SyncState::Behind);
+        assert!(status.last_fetch.is_some());
+
+        commit(&b, "notes/b.md", "from b\n", "B");
+        let status = sync_status(&b);
+        assert_eq!((status.ahead, status.behind), (1, 1));
+        assert_eq!(status.sync_state, SyncState::Diverged);
+    }
+
+    #[test]
+    fn reports_no_remote_without_an_upstream() {
+        let root = TempDir::new("git-no-remote");
+        init(&root.join("local")).unwrap();
+        let repo = configure(&root.join("local"));
+        commit(&repo, "a.md", "a\n", "A");
+        let status = sync_status(&repo);
+        assert_eq!(status.upstream, None);
+        assert_eq!(status.sync_state, SyncState::NoRemote);
+    }
+
+    #[test]
+    fn names_the_branch_before_the_first_commit_and_none_when_detached() {
+        let root = TempDir::new("git-branch");
+        let path = root.join("local");
+        Repository::init_opts(&path, RepositoryInitOptions::new().initial_head("notes")).unwrap();
+        let repo = configure(&path);
+        assert_eq!(current_branch(&repo).as_deref(), Some("notes"));
+        assert_eq!(remote_url(&repo, "origin"), None);
+
+        let first = commit(&repo, "a.md", "a\n", "A");
+        assert_eq!(current_branch(&repo).as_deref(), Some("notes"));
+        repo.set_head_detached(first).unwrap();
+        assert_eq!(current_branch(&repo), None);
+
+        let bare = bare_remote(&root);
+        let clone = clone_of(&root, &bare, "clone");
+        assert_eq!(
+            remote_url(&clone, "origin"),
+            bare.to_str().map(String::from)
+        );
+    }
+
+    #[test]
+    fn push_is_rejected_when_the_remote_moved_on() {
+        let root = TempDir::new("git-rejected");
+        let bare = bare_remote(&root);
+        let a = clone_of(&root, &bare, "a");
+        let b = clone_of(&root, &bare, "b");
+        commit(&a, "notes/a.md", "from a\n", "A");
+        push(&a).unwrap();
+        commit(&b, "notes/b.md", "from b\n", "B");
+        assert!(push(&b).is_err());
+    }
+
+    #[test]
+    fn merges_upstream_changes_to_other_lines() {
+        let root = TempDir::new("git-merge-clean");
+        let bare = bare_remote(&root);
+        let theirs = clone_of(&root, &bare, "theirs");
+        let ours = clone_of(&root, &bare, "ours");
+        commit(&theirs, "notes/a.md", "ONE\ntwo\nthree\n", "Shout");
+        push(&theirs).unwrap();
+        let ours_before = commit(&ours, "notes/a.md", "one\ntwo\nTHREE\n", "Shout last");
+        fetch(&ours, false).unwrap();
+
+        assert!(matches!(
+            integrate_upstream(&ours, true).unwrap(),
+            Integration::Done
+        ));
+        assert_eq!(read(&ours, "notes/a.md"), "ONE\ntwo\nTHREE\n");
+        let merge = head_commit(&ours).unwrap();
+        assert_eq!(merge.parent_count(), 2);
+        assert_eq!(merge.parent_id(0).unwrap(), ours_before);
+        assert_eq!(
+            merge.message(),
+            Some("Merge remote-tracking branch 'origin/main'")
+        );
+        assert_eq!(pending_operation(&ours), None);
+    }
+
+    #[test]
+    fn rebases_local_commits_onto_upstream() {
+        let root = TempDir::new("git-rebase-clean");
+        let bare = bare_remote(&root);
+        let theirs = clone_of(&root, &bare, "theirs");
+        let ours = clone_of(&root, &bare, "ours");
+        let upstream = commit(&theirs, "notes/a.md", "ONE\ntwo\nthree\n", "Shout");
+        push(&theirs).unwrap();
+        commit(&ours, "notes/b.md", "local\n", "Add b");
+        fetch(&ours, false).unwrap();
+
+        assert!(matches!(
+            integrate_upstream(&ours, false).unwrap(),
+            Integration::Done
+        ));
+        let head = head_commit(&ours).unwrap();
+        assert_eq!(head.parent_count(), 1);
+        assert_eq!(head.parent_id(0).unwrap(), upstream);
+        assert_eq!(head.summary(), Some("Add b"));
+        assert_eq!(read(&ours, "notes/a.md"), "ONE\ntwo\nthree\n");
+        assert_eq!(read(&ours, "notes/b.md"), "local\n");
+        assert_eq!(pending_operation(&ours), None);
+    }
+
+    #[test]
+    fn continues_a_merge_once_conflicts_are_resolved() {
+        let root = TempDir::new("git-continue-merge");
+        let bare = bare_remote(&root);
+        let theirs = clone_of(&root, &bare, "theirs");
+        let ours = clone_of(&root, &bare, "ours");
+        diverge(&theirs, &ours);
+
+        assert!(matches!(
+            integrate_upstream(&ours, true).unwrap(),
+            Integration::Conflicts(_)
+        ));
+        assert_eq!(pending_operation(&ours), Some(PendingOperation::Merge));
+        let conflict = &conflicts(&ours).unwrap()[0];
+        assert_eq!(conflict.path, "notes/a.md");
+        assert_eq!(conflict.base.as_deref(), Some("one\ntwo\nthree\n"));
+        assert_eq!(conflict.ours.as_deref(), Some("uno\ntwo\nthree\n"));
+        assert_eq!(conflict.theirs.as_deref(), Some("ONE\ntwo\nthree\n"));
+
+        // Still conflicted
+        assert!(continue_operation(&ours).is_err());
+        write(&ours, "notes/a.md", "UNO\ntwo\nthree\n");
+        mark_resolved(&ours, "notes/a.md").unwrap();
+        assert!(matches!(
+            continue_operation(&ours).unwrap(),
+            Integration::Done
+        ));
+        assert_eq!(pending_operation(&ours), None);
+        assert_eq!(head_commit(&ours).unwrap().parent_count(), 2);
+        assert_eq!(
+            file_at(&ours, "HEAD", "notes/a.md").unwrap(),
+            "UNO\ntwo\nthree\n"
+        );
+        assert_eq!(
+            file_at(&ours, "HEAD", "notes/new.md").unwrap(),
+            "from upstream\n"
+        );
+    }
+
+    #[test]
+    fn continues_or_aborts_a_rebase() {
+        let root = TempDir::new("git-continue-rebase");
+        let bare = bare_remote(&root);
+        let theirs = clone_of(&root, &bare, "theirs");
+        let ours = clone_of(&root, &bare, "ours");
+        diverge(&theirs, &ours);
+        let local = ours.head().unwrap().target();
+
+        assert!(matches!(
+            integrate_upstream(&ours, false).unwrap(),
+            Integration::Conflicts(_)
+        ));
+        assert_eq!(pending_operation(&ours), Some(PendingOperation::Rebase));
+        // Sides are reported from the user's point of view, not git's
+        let conflict = &conflicts(&ours).unwrap()[0];
+        assert_eq!(conflict.ours.as_deref(), Some("uno\ntwo\nthree\n"));
+        assert_eq!(conflict.theirs.as_deref(), Some("ONE\ntwo\nthree\n"));
+
+        abort_operation(&ours).unwrap();
+        assert_eq!(pending_operation(&ours), None);
+        assert_eq!(ours.head().unwrap().target(), local);
+        assert_eq!(read(&ours, "notes/a.md"), "uno\ntwo\nthree\n");
+
+        assert!(matches!(
+            integrate_upstream(&ours, false).unwrap(),
+            Integration::Conflicts(_)
+        ));
+        write(&ours, "notes/a.md", "UNO\ntwo\nthree\n");
+        mark_resolved(&ours, "notes/a.md").unwrap();
+        assert!(matches!(
+            continue_operation(&ours).unwrap(),
+            Integration::Done
+        ));
+        assert_eq!(pending_operation(&ours), None);
+        let head = head_commit(&ours).unwrap();
+        assert_eq!(head.parent_count(), 1);
+        assert_eq!(head.summary(), Some("Translate"));
+        assert_eq!(read(&ours, "notes/a.md"), "UNO\ntwo\nthree\n");
+        assert_eq!(read(&ours, "notes/new.md"), "from upstream\n");
+    }
+
+    #[test]
+    fn abort_without_an_operation_is_an_error() {
+        let root = TempDir::new("git-abort-nothing");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        assert!(abort_operation(&repo).is_err());
+        assert!(continue_operation(&repo).is_err());
+    }
+
+    #[test]
+    fn merges_text_or_marks_conflicts() {
+        let base = "one\ntwo\nthree\n";
+        let (merged, clean) = merge_text(base, "ONE\ntwo\nthree\n", "one\ntwo\nTHREE\n").unwrap();
+        assert!(clean);
+        assert_eq!(merged, "ONE\ntwo\nTHREE\n");
+
+        let (merged, clean) = merge_text(base, "uno\ntwo\nthree\n", "ONE\ntwo\nthree\n").unwrap();
+        assert!(!clean);
+        assert!(merged.contains("<<<<<<< yours\nuno\n=======\nONE\n>>>>>>> on disk\n"));
+    }
+
+    #[test]
+    fn history_follows_renames() {
+        let root = TempDir::new("git-history");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        commit(&repo, "notes/a.md", "one\ntwo\nthree\nfour\n", "Add a line");
+        fs::rename(
+            workdir_path(&repo, "notes/a.md").unwrap(),
+            workdir_path(&repo, "notes/renamed.md").unwrap(),
+        )
+        .unwrap();
+        commit_all(&repo, "Rename").unwrap();
+        commit(&repo, "notes/other.md", "unrelated\n", "Other");
+
+        let history = file_history(&repo, "notes/renamed.md", None).unwrap();
+        let steps: Vec<(&str, &str, &str)> = history
+            .iter()
+            .map(|r| {
+                (
+                    r.commit.message.as_str(),
+                    r.path.as_str(),
+                    r.status.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("Rename", "notes/renamed.md", "R"),
+                ("Add a line", "notes/a.md", "M"),
+                ("Initial", "notes/a.md", "A"),
+            ]
+        );
+        assert_eq!(
+            file_history(&repo, "notes/renamed.md", Some(1))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn commits_only_the_given_paths() {
+        let root = TempDir::new("git-commit-paths");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        fs::rename(
+            workdir_path(&repo, "notes/a.md").unwrap(),
+            workdir_path(&repo, "notes/b.md").unwrap(),
+        )
+        .unwrap();
+        write(&repo, "notes/linking.md", "[[b]]\n");
+        write(&repo, "notes/draft.md", "not yet\n");
+
+        commit_paths(
+            &repo,
+            &["notes/a.md", "notes/b.md", "notes/linking.md"],
+            "Rename a to b",
+        )
+        .unwrap()
+        .unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        let committed = |path: &str| tree.get_path(Path::new(path)).is_ok();
+        assert!(!committed("notes/a.md"));
+        assert!(committed("notes/b.md") && committed("notes/linking.md"));
+        assert!(!committed("notes/draft.md"));
+        assert!(commit_paths(&repo, &["notes/b.md"], "Again")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn finds_where_a_file_was_at_any_revision() {
+        let root = TempDir::new("git-path-at");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        let rename = |from: &str, to: &str, message: &str| {
+            fs::rename(
+                workdir_path(&repo, from).unwrap(),
+                workdir_path(&repo, to).unwrap(),
+            )
+            .unwrap();
+            commit_all(&repo, message).unwrap();
+        };
+        commit(&repo, "notes/a.md", "one\ntwo\nthree\nfour\n", "Add a line");
+        rename("notes/a.md", "notes/b.md", "Rename");
+        commit(&repo, "notes/other.md", "unrelated\n", "Other");
+        rename("notes/b.md", "notes/c.md", "Rename again");
+        let first_rename = repo
+            .revparse_single("HEAD~2")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        repo.branch("before-other", &first_rename, false).unwrap();
+        let short = repo.revparse_single("HEAD~3").unwrap().id().to_string()[..7].to_string();
+
+        let path = |rev: &str| path_at(&repo, "notes/c.md", rev).unwrap();
+        assert_eq!(path("HEAD"), "notes/c.md");
+        assert_eq!(path("HEAD~1"), "notes/b.md");
+        assert_eq!(path("before-other"), "notes/b.md");
+        assert_eq!(path(&short), "notes/a.md");
+        assert_eq!(path("HEAD~4"), "notes/a.md");
+        assert_eq!(
+            file_at(&repo, "HEAD~1", &path("HEAD~1")).unwrap(),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert!(path_at(&repo, "notes/c.md", "no-such-branch").is_err());
+    }
+
+    #[test]
+    fn finds_the_last_commit_within_a_walk_limit() {
+        let root = TempDir::new("git-last-commit");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        commit(&repo, "notes/b.md", "b\n", "Add b");
+        commit(&repo, "notes/a.md", "changed\n", "Change a");
+        commit(&repo, "notes/c.md", "c\n", "Add c");
+
+        let message = |rel_path: &str, max_commits: usize| {
+            last_commit(&repo, rel_path, max_commits).map(|c| c.message)
+        };
+        assert_eq!(message("notes/a.md", 10).as_deref(), Some("Change a"));
+        assert_eq!(message("notes/b.md", 10).as_deref(), Some("Add b"));
+        assert_eq!(message("notes/b.md", 2), None);
+        assert_eq!(message("notes/draft.md", 10), None);
+    }
+
+    #[test]
+    fn finds_last_authors_within_a_walk_limit() {
+        let root = TempDir::new("git-authors");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        repo.config()
+            .unwrap()
+            .set_str("user.name", "Grace")
+            .unwrap();
+        commit(&repo, "notes/b.md", "b\n", "Add b");
+        let paths: HashSet<String> = ["notes/a.md", "notes/b.md", "notes/draft.md"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+
+        let authors = last_authors(&repo, &paths, None);
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors["notes/a.md"], "Test <test@example.com>");
+        assert_eq!(authors["notes/b.md"], "Grace <test@example.com>");
+        let recent = last_authors(&repo, &paths, Some(1));
+        assert_eq!(recent.keys().collect::<Vec<_>>(), vec!["notes/b.md"]);
+    }
+
+    #[test]
+    fn blames_committed_and_new_lines() {
+        let root = TempDir::new("git-blame");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        commit(&repo, "notes/a.md", "one\nTWO\nthree\n", "Shout two");
+
+        let lines = blame(&repo, "notes/a.md", "one\nTWO\nthree\nfour\n").unwrap();
+        let summary: Vec<(usize, Option<&str>)> = lines
+            .iter()
+            .map(|l| (l.line, l.commit.as_ref().map(|c| c.message.as_str())))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, Some("Initial")),
+                (2, Some("Shout two")),
+                (3, Some("Initial")),
+                (4, None),
+            ]
+        );
+
+        // Never committed: every line is new
+        let lines = blame(&repo, "notes/draft.md", "draft\n").unwrap();
+        assert!(lines[0].commit.is_none());
+    }
+}
@@ -1,12 +1,31 @@
+mod atomic;
+mod cli;
+mod error;
+mod frontmatter;
+mod git;
+mod links;
+mod mcp;
+mod paths;
+mod query;
 mod search;
+mod tags;
+mod watcher;
+#[cfg(test)]
+mod testing;
 
 use chrono::{DateTime, Datelike, Local, Utc};
-use search::{SearchIndex, SearchResult as TantivySearchResult};
+use error::{GitErrorKind, GitNotesError};
+use frontmatter::Frontmatter;
+use git::{Integration, RemoteError, SyncStatus};
+use links::{LinkIndex, NoteLink};
+use search::{RelatedNote, SearchIndex, SearchResult as TantivySearchResult, TagCount};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use watcher::{VaultChange, VaultWatcher};
 
 const PROTECTED_SECTIONS: &[&str] = &["1-todo", "1-weeks"];
 
@@ -120,16 +139,6 @@ pub struct GitNotesConfig {
     pub section_order: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-struct Frontmatter {
-    #[serde(default)]
-    created: Option<String>,
-    #[serde(default)]
-    modified: Option<String>,
-    #[serde(flatten)]
-    other: std::collections::HashMap<String, serde_yaml::Value>,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Vault {
     pub id: String,
@@ -139,6 +148,9 @@ pub struct Vault {
     pub is_team: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub is_team_override: Option<bool>,
+    /// How `sync_vault` integrates remote changes: "rebase" (default) or "merge"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_strategy: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -151,6 +163,8 @@ pub struct VaultStats {
     pub git_branch: Option<String>,
     pub git_provider: Option<String>,
     pub git_repo: Option<String>,
+    #[serde(flatten)]
+    pub sync: SyncStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -236,6 +250,45 @@ impl Default for EditorSettings {
     }
 }
 
+/// How search ranks results. Field boosts weigh where a word matched; the rest multiply a
+/// note's score.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchSettings {
+    #[serde(default = "default_filename_boost")]
+    pub filename_boost: f32,
+    #[serde(default = "default_headings_boost")]
+    pub headings_boost: f32,
+    #[serde(default = "default_content_boost")]
+    pub content_boost: f32,
+    /// Extra weight for a note modified just now, halving every `recency_half_life_days`
+    #[serde(default = "default_recency_boost")]
+    pub recency_boost: f32,
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f32,
+    #[serde(default = "default_pinned_boost")]
+    pub pinned_boost: f32,
+}
+
+fn default_filename_boost() -> f32 { 3.0 }
+fn default_headings_boost() -> f32 { 2.0 }
+fn default_content_boost() -> f32 { 1.0 }
+fn default_recency_boost() -> f32 { 0.5 }
+fn default_recency_half_life_days() -> f32 { 30.0 }
+fn default_pinned_boost() -> f32 { 1.5 }
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings {
+            filename_boost: default_filename_boost(),
+            headings_boost: default_headings_boost(),
+            content_boost: default_content_boost(),
+            recency_boost: default_recency_boost(),
+            recency_half_life_days: default_recency_half_life_days(),
+            pinned_boost: default_pinned_boost(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LastSession {
     pub section: String,
@@ -255,6 +308,8 @@ pub struct Settings {
     pub appearance: AppearanceSettings,
     #[serde(default)]
     pub editor: EditorSettings,
+    #[serde(default)]
+    pub search: SearchSettings,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_session: Option<LastSession>,
 }
@@ -270,22 +325,49 @@ fn get_settings_path() -> PathBuf {
         .join("settings.json")
 }
 
+// Earlier good copies of settings.json kept by `save_settings`, newest first
+const SETTINGS_BACKUPS: usize = 3;
+
+fn settings_backup_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("json.bak{}", n))
+}
+
+fn read_settings(path: &Path) -> Option<Settings> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Put back the newest backup that parses, keeping a copy of the broken file as `settings.json.corrupt`.
+fn restore_settings_backup(path: &Path) -> Option<Settings> {
+    let (backup, settings) = (1..=SETTINGS_BACKUPS)
+        .map(|n| settings_backup_path(path, n))
+        .find_map(|backup| read_settings(&backup).map(|settings| (backup, settings)))?;
+    // Copy rather than move, so a failed restore still leaves settings.json in place
+    let _ = fs::copy(path, path.with_extension("json.corrupt"));
+    match fs::read(&backup).and_then(|content| atomic::write(path, content)) {
+        Ok(()) => eprintln!("{} was unreadable; restored {}", path.display(), backup.display()),
+        Err(e) => eprintln!("Failed to restore {}: {}", path.display(), e),
+    }
+    Some(settings)
+}
+
 fn load_settings() -> Settings {
     let path = get_settings_path();
-    if path.exists() {
-        if let Ok(content) = fs::read_to_string(&path) {
-            if let Ok(mut settings) = serde_json::from_str::<Settings>(&content) {
-                // Migrate old commit_mode to auto_commit
-                if let Some(ref mode) = settings.git.commit_mode {
-                    settings.git.auto_commit = mode != "manual";
-                    settings.git.commit_mode = None;
-                    settings.git.commit_interval = None;
-                    // Save migrated settings
-                    let _ = save_settings(&settings);
-                }
-                return settings;
-            }
+    let settings = match read_settings(&path) {
+        Some(settings) => Some(settings),
+        // Don't drop every vault because the file was damaged
+        None if path.exists() => restore_settings_backup(&path),
+        None => None,
+    };
+    if let Some(mut settings) = settings {
+        // Migrate old commit_mode to auto_commit
+        if let Some(ref mode) = settings.git.commit_mode {
+            settings.git.auto_commit = mode != "manual";
+            settings.git.commit_mode = None;
+            settings.git.commit_interval = None;
+            // Save migrated settings
+            let _ = save_settings(&settings);
         }
+        return settings;
     }
     // Return empty settings for onboarding flow
     Settings {
@@ -294,17 +376,28 @@ fn load_settings() -> Settings {
         git: GitSettings::default(),
         appearance: AppearanceSettings::default(),
         editor: EditorSettings::default(),
+        search: SearchSettings::default(),
         last_session: None,
     }
 }
 
 fn save_settings(settings: &Settings) -> Result<(), String> {
-    let path = get_settings_path();
+    write_settings(&get_settings_path(), settings)
+}
+
+fn write_settings(path: &Path, settings: &Settings) -> Result<(), String> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
     let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    fs::write(&path, content).map_err(|e| e.to_string())
+    // Rotate backups, but only ever keep copies that parse
+    if read_settings(path).is_some() {
+        for n in (1..SETTINGS_BACKUPS).rev() {
+            let _ = fs::rename(settings_backup_path(path, n), settings_backup_path(path, n + 1));
+        }
+        let _ = fs::copy(path, settings_backup_path(path, 1));
+    }
+    atomic::write(path, content).map_err(|e| e.to_string())
 }
 
 fn generate_id() -> String {
@@ -372,23 +465,6 @@ fn parse_git_remote(url: &str) -> Option<(String, String)> {
     None
 }
 
-fn parse_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
-    if !content.starts_with("---\n") {
-        return (None, content);
-    }
-
-    if let Some(end) = content[4..].find("\n---") {
-        let yaml_str = &content[4..4 + end];
-        let body = &content[4 + end + 4..].trim_start_matches('\n');
-
-        if let Ok(fm) = serde_yaml::from_str::<Frontmatter>(yaml_str) {
-            return (Some(fm), body);
-        }
-    }
-
-    (None, content)
-}
-
 fn load_section_metadata(section_path: &PathBuf) -> SectionMetadata {
     let metadata_file = section_path.join(".section.md");
     let order_file = section_path.join(".order.json");
@@ -396,16 +472,14 @@ fn load_section_metadata(section_path: &PathBuf) -> SectionMetadata {
     // Try loading from .section.md first
     let mut metadata = if metadata_file.exists() {
         if let Ok(content) = fs::read_to_string(&metadata_file) {
-            if content.starts_with("---\n") {
-                if let Some(end) = content[4..].find("\n---") {
-                    let yaml_str = &content[4..4 + end];
-                    serde_yaml::from_str::<SectionMetadata>(yaml_str).unwrap_or_default()
-                } else {
-                    SectionMetadata::default()
-                }
-            } else {
-                SectionMetadata::default()
-            }
+            frontmatter::split_raw(&content)
+                .and_then(|(fm, _)| {
+                    serde_yaml::from_value::<SectionMetadata>(serde_yaml::Value::Mapping(
+                        fm.fields().clone(),
+                    ))
+                    .ok()
+                })
+                .unwrap_or_default()
         } else {
             SectionMetadata::default()
         }
@@ -429,6 +503,7 @@ fn load_section_metadata(section_path: &PathBuf) -> SectionMetadata {
                 }
                 // Save migrated data to .section.md and remove old file
                 if save_section_metadata(section_path, &metadata).is_ok() {
+                    watcher::expect_removed(&order_file);
                     let _ = fs::remove_file(&order_file);
                 }
             }
@@ -438,54 +513,72 @@ fn load_section_metadata(section_path: &PathBuf) -> SectionMetadata {
     metadata
 }
 
+/// A section's fields as they'd appear in `.section.md`; `None` for fields left out because
+/// they hold the default.
+fn section_fields(metadata: &SectionMetadata) -> Vec<(&'static str, Option<serde_yaml::Value>)> {
+    use serde_yaml::Value;
+    let string = |s: &Option<String>| s.clone().map(Value::String);
+    let list = |items: &Vec<String>| {
+        (!items.is_empty())
+            .then(|| Value::Sequence(items.iter().cloned().map(Value::String).collect()))
+    };
+    vec![
+        ("title", string(&metadata.title)),
+        ("color", string(&metadata.color)),
+        (
+            "sort",
+            (!metadata.sort.is_empty() && metadata.sort != default_sort())
+                .then(|| Value::String(metadata.sort.clone())),
+        ),
+        ("pinned", list(&metadata.pinned)),
+        ("order", list(&metadata.order)),
+        ("last_note", string(&metadata.last_note)),
+        ("agent_instructions", string(&metadata.agent_instructions)),
+        ("type", string(&metadata.section_type)),
+    ]
+}
+
+/// Update `.section.md` in place. Only fields whose value changed are rewritten; other keys
+/// (GitBrain adds its own), comments and any markdown below the frontmatter are kept as-is.
 fn save_section_metadata(section_path: &PathBuf, metadata: &SectionMetadata) -> Result<(), String> {
     let metadata_file = section_path.join(".section.md");
+    let existing = fs::read_to_string(&metadata_file).unwrap_or_default();
+
+    let (mut fm, rest) = match frontmatter::split_raw(&existing) {
+        Some((fm, rest)) => (fm, rest.to_string()),
+        // Don't guess at broken YAML someone else wrote; overwriting it would lose their data
+        None if existing.starts_with("---\n") || existing.starts_with("---\r\n") => {
+            return Err(format!(
+                "Can't update {}: its frontmatter isn't valid YAML",
+                metadata_file.display()
+            ))
+        }
+        None if existing.is_empty() => (Frontmatter::default(), String::new()),
+        None => (Frontmatter::default(), format!("\n{}", existing)),
+    };
 
-    let mut lines = vec!["---".to_string()];
+    let previous = serde_yaml::from_value::<SectionMetadata>(serde_yaml::Value::Mapping(
+        fm.fields().clone(),
+    ))
+    .unwrap_or_default();
 
-    // Write fields only if they have non-default values
-    if let Some(ref title) = metadata.title {
-        lines.push(format!("title: \"{}\"", title));
-    }
-    if let Some(ref color) = metadata.color {
-        lines.push(format!("color: \"{}\"", color));
-    }
-    if !metadata.sort.is_empty() && metadata.sort != default_sort() {
-        lines.push(format!("sort: {}", metadata.sort));
-    }
-    if !metadata.pinned.is_empty() {
-        lines.push("pinned:".to_string());
-        for item in &metadata.pinned {
-            lines.push(format!("  - {}", item));
-        }
-    }
-    if !metadata.order.is_empty() {
-        lines.push("order:".to_string());
-        for item in &metadata.order {
-            lines.push(format!("  - {}", item));
+    let mut changed = false;
+    let fields = section_fields(&previous).into_iter().zip(section_fields(metadata));
+    for ((key, before), (_, after)) in fields {
+        if before == after {
+            continue;
         }
-    }
-    if let Some(ref last_note) = metadata.last_note {
-        lines.push(format!("last_note: \"{}\"", last_note));
-    }
-    if let Some(ref instructions) = metadata.agent_instructions {
-        lines.push("agent_instructions: |".to_string());
-        for line in instructions.lines() {
-            if line.is_empty() {
-                lines.push(String::new());
-            } else {
-                lines.push(format!("  {}", line));
-            }
+        match after {
+            Some(value) => fm.set_value(key, &value),
+            None => fm.remove(key),
         }
+        changed = true;
     }
-    if let Some(ref section_type) = metadata.section_type {
-        lines.push(format!("type: {}", section_type));
+    if !changed && metadata_file.exists() {
+        return Ok(());
     }
 
-    lines.push("---".to_string());
-
-    let content = lines.join("\n");
-    fs::write(&metadata_file, content).map_err(|e| e.to_string())
+    atomic::write(&metadata_file, format!("{}{}", fm.to_block(), rest)).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -494,7 +587,7 @@ fn set_section_metadata(
     title: Option<String>,
     color: Option<String>,
 ) -> Result<(), String> {
-    let path = PathBuf::from(&section_path);
+    let path = vault_path(&section_path)?;
     // Load existing metadata to preserve sort/pinned/order fields
     let mut metadata = load_section_metadata(&path);
     metadata.title = title;
@@ -502,32 +595,6 @@ fn set_section_metadata(
     save_section_metadata(&path, &metadata)
 }
 
-fn format_frontmatter(fm: &Frontmatter) -> String {
-    let mut lines = vec!["---".to_string()];
-
-    if let Some(ref created) = fm.created {
-        lines.push(format!("created: {}", created));
-    }
-    if let Some(ref modified) = fm.modified {
-        lines.push(format!("modified: {}", modified));
-    }
-
-    // Add other fields
-    for (key, value) in &fm.other {
-        if let Ok(yaml) = serde_yaml::to_string(value) {
-            let yaml = yaml.trim();
-            if yaml.contains('\n') {
-                lines.push(format!("{}: {}", key, yaml));
-            } else {
-                lines.push(format!("{}: {}", key, yaml));
-            }
-        }
-    }
-
-    lines.push("---".to_string());
-    lines.join("\n")
-}
-
 fn iso_now() -> String {
     Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string()
 }
@@ -539,8 +606,188 @@ fn iso_to_timestamp(iso: &str) -> u64 {
         .unwrap_or(0)
 }
 
-struct AppState {
+/// Search index, link graph and file watcher for the vault currently open in the app.
+struct ActiveIndex {
+    vault_id: String,
     search_index: Arc<SearchIndex>,
+    link_index: Arc<LinkIndex>,
+    _watcher: Option<VaultWatcher>,
+}
+
+struct AppState {
+    active_index: Mutex<Option<ActiveIndex>>,
+    // Indexes opened earlier in this session, reused when switching back to a vault
+    index_cache: Mutex<HashMap<String, Arc<SearchIndex>>>,
+    // Each note as the editor last read or saved it, the base for merging conflicting writes
+    note_bases: Mutex<HashMap<PathBuf, String>>,
+    // Set once the app is running; the watcher sends vault changes to the frontend through it
+    app_handle: Arc<std::sync::OnceLock<tauri::AppHandle>>,
+}
+
+impl AppState {
+    fn new() -> Self {
+        AppState {
+            active_index: Mutex::new(None),
+            index_cache: Mutex::new(HashMap::new()),
+            note_bases: Mutex::new(HashMap::new()),
+            app_handle: Arc::new(std::sync::OnceLock::new()),
+        }
+    }
+
+    fn search_index(&self) -> Result<Arc<SearchIndex>, GitNotesError> {
+        let active = self.active_index.lock().map_err(|e| e.to_string())?;
+        active
+            .as_ref()
+            .map(|a| Arc::clone(&a.search_index))
+            .ok_or_else(|| GitNotesError::Index {
+                message: "Search index not ready".to_string(),
+            })
+    }
+
+    fn remember_base(&self, path: &Path, content: &str) {
+        if let Ok(mut bases) = self.note_bases.lock() {
+            bases.insert(path.to_path_buf(), content.to_string());
+        }
+    }
+
+    fn base_for(&self, path: &Path, version: &str) -> Option<String> {
+        let bases = self.note_bases.lock().ok()?;
+        bases
+            .get(path)
+            .filter(|base| content_version(base) == version)
+            .cloned()
+    }
+
+    fn link_index(&self) -> Result<Arc<LinkIndex>, String> {
+        let active = self.active_index.lock().map_err(|e| e.to_string())?;
+        active
+            .as_ref()
+            .map(|a| Arc::clone(&a.link_index))
+            .ok_or_else(|| "Link index not ready".to_string())
+    }
+
+    /// Point the search index and watcher at whichever vault the settings say is active.
+    /// Does nothing if that vault is already the active one.
+    fn sync_active_vault(&self) -> Result<(), String> {
+        let vault_id = get_active_vault_id();
+        let notes_path = get_notes_path();
+
+        let mut active = self.active_index.lock().map_err(|e| e.to_string())?;
+        if active.as_ref().map(|a| a.vault_id == vault_id).unwrap_or(false) {
+            return Ok(());
+        }
+
+        // Stop watching the previous vault before opening the next one
+        *active = None;
+
+        let search_index = {
+            let mut cache = self.index_cache.lock().map_err(|e| e.to_string())?;
+            match cache.get(&vault_id) {
+                Some(index) => {
+                    // Catch up on anything that changed while the vault was not watched
+                    index.sync_files(&notes_path)?;
+                    Arc::clone(index)
+                }
+                None => {
+                    let index_path = get_search_index_path(&vault_id);
+                    let index = Arc::new(open_search_index(&index_path, &notes_path)?);
+                    cache.insert(vault_id.clone(), Arc::clone(&index));
+                    index
+                }
+            }
+        };
+
+        let link_index = Arc::new(LinkIndex::new(&notes_path));
+
+        let watcher = {
+            let search_index = Arc::clone(&search_index);
+            let link_index = Arc::clone(&link_index);
+            let app_handle = Arc::clone(&self.app_handle);
+            let notes_root = notes_path.clone();
+            watcher::watch_vault(notes_path, move |change, own_write| {
+                let path = change.path().to_path_buf();
+                let is_markdown = path.extension().map(|e| e == "md").unwrap_or(false);
+                if let VaultChange::NoteRenamed { from, to } = change {
+                    let _ = search_index.remove_file(from);
+                    link_index.remove_file(from);
+                    let _ = search_index.index_file(to, &notes_root);
+                    link_index.index_file(to);
+                } else if let VaultChange::SectionChanged(_) = change {
+                    // Nothing is reported for the notes inside a moved folder
+                    let _ = search_index.sync_files(&notes_root);
+                    link_index.sync_dir(&path);
+                } else if is_markdown && path.exists() {
+                    let _ = search_index.index_file(&path, &notes_root);
+                    link_index.index_file(&path);
+                } else if is_markdown {
+                    let _ = search_index.remove_file(&path);
+                    link_index.remove_file(&path);
+                }
+
+                if !own_write {
+                    if let Some(app) = app_handle.get() {
+                        emit_vault_change(app, &notes_root, change);
+                    }
+                }
+            })
+        };
+        let watcher = match watcher {
+            Ok(w) => Some(w),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        };
+
+        *active = Some(ActiveIndex {
+            vault_id,
+            search_index,
+            link_index,
+            _watcher: watcher,
+        });
+        Ok(())
+    }
+}
+
+/// Payload of the vault change events.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultChangeEvent {
+    /// Relative to the vault, with `/` separators
+    pub path: String,
+    /// Where a renamed note used to be, the same way
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+/// Tell the frontend about a change made outside the app (git, an agent, another editor).
+fn emit_vault_change(app: &tauri::AppHandle, notes_path: &Path, change: &VaultChange) {
+    use tauri::Emitter;
+
+    let event = match change {
+        VaultChange::NoteCreated(_) => "note-created",
+        VaultChange::NoteChanged(_) => "note-changed",
+        VaultChange::NoteDeleted(_) => "note-deleted",
+        VaultChange::NoteRenamed { .. } => "note-renamed",
+        VaultChange::SectionChanged(_) => "section-changed",
+        VaultChange::MetadataChanged(_) => "metadata-changed",
+    };
+    let relative = |path: &Path| {
+        let rel = path.strip_prefix(notes_path).ok()?;
+        Some(
+            rel.components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    };
+    let Some(path) = relative(change.path()) else {
+        return;
+    };
+    let from = match change {
+        VaultChange::NoteRenamed { from, .. } => relative(from),
+        _ => None,
+    };
+    let _ = app.emit(event, VaultChangeEvent { path, from });
 }
 
 fn get_notes_path() -> PathBuf {
@@ -559,7 +806,69 @@ fn get_notes_path() -> PathBuf {
     }
 }
 
-fn load_gitnotes_config(vault_path: &PathBuf) -> GitNotesConfig {
+/// Resolve a path from the frontend, refusing anything outside the active vault.
+fn vault_path(path: &str) -> Result<PathBuf, GitNotesError> {
+    vault_path_in(&get_notes_path(), path)
+}
+
+fn vault_path_in(notes_path: &Path, path: &str) -> Result<PathBuf, GitNotesError> {
+    Ok(paths::resolve_in_vault(notes_path, path)?)
+}
+
+/// Id of the vault `get_notes_path` resolves to, or "default" when no vault is configured.
+fn get_active_vault_id() -> String {
+    let settings = load_settings();
+    settings
+        .active_vault
+        .and_then(|id| settings.vaults.iter().find(|v| v.id == id).map(|v| v.id.clone()))
+        .or_else(|| settings.vaults.first().map(|v| v.id.clone()))
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Times to try opening a search index held by another process, and the pause in between
+const INDEX_LOCK_RETRIES: u32 = 5;
+const INDEX_LOCK_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Open a vault's search index, waiting a moment for another process to let go of it.
+fn open_search_index(index_path: &Path, notes_path: &Path) -> Result<SearchIndex, GitNotesError> {
+    let mut attempt = 1;
+    loop {
+        match SearchIndex::new(&index_path.to_path_buf(), &notes_path.to_path_buf()) {
+            Err(GitNotesError::IndexLocked { .. }) if attempt < INDEX_LOCK_RETRIES => {
+                attempt += 1;
+                std::thread::sleep(INDEX_LOCK_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn get_search_index_path(vault_id: &str) -> PathBuf {
+    search_index_root().join(vault_id)
+}
+
+/// Where the per-vault search indexes live.
+fn search_index_root() -> PathBuf {
+    let index_root = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(get_config_dir_name())
+        .join("search-index");
+
+    // Clean up the old shared index, which lived directly in search-index/
+    if index_root.join("meta.json").exists() {
+        if let Ok(entries) = fs::read_dir(&index_root) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                if entry.path().is_file() {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    index_root
+}
+
+fn load_gitnotes_config(vault_path: &Path) -> GitNotesConfig {
     let config_file = vault_path.join(".gitnotes");
     if config_file.exists() {
         if let Ok(content) = fs::read_to_string(&config_file) {
@@ -581,19 +890,19 @@ fn save_section_order(order: Vec<String>) -> Result<(), String> {
     config.section_order = order;
 
     let content = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
-    fs::write(&config_file, content).map_err(|e| e.to_string())
+    atomic::write(&config_file, content).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn get_sort_preference(section_path: String) -> Result<String, String> {
-    let path = PathBuf::from(&section_path);
+    let path = vault_path(&section_path)?;
     let metadata = load_section_metadata(&path);
     Ok(metadata.sort)
 }
 
 #[tauri::command]
 fn set_sort_preference(section_path: String, sort: String) -> Result<(), String> {
-    let path = PathBuf::from(&section_path);
+    let path = vault_path(&section_path)?;
     let mut metadata = load_section_metadata(&path);
     metadata.sort = sort;
     save_section_metadata(&path, &metadata)
@@ -601,14 +910,14 @@ fn set_sort_preference(section_path: String, sort: String) -> Result<(), String>
 
 #[tauri::command]
 fn get_last_note(section_path: String) -> Result<Option<String>, String> {
-    let path = PathBuf::from(&section_path);
+    let path = vault_path(&section_path)?;
     let metadata = load_section_metadata(&path);
     Ok(metadata.last_note)
 }
 
 #[tauri::command]
 fn set_last_note(section_path: String, note_path: String) -> Result<(), String> {
-    let path = PathBuf::from(&section_path);
+    let path = vault_path(&section_path)?;
     let mut metadata = load_section_metadata(&path);
     // Only save if changed to avoid unnecessary YAML reformatting
     if metadata.last_note.as_ref() != Some(&note_path) {
@@ -621,15 +930,17 @@ fn set_last_note(section_path: String, note_path: String) -> Result<(), String>
 
 #[tauri::command]
 fn list_sections() -> Result<Vec<Section>, String> {
-    let notes_path = get_notes_path();
+    list_sections_in(&get_notes_path())
+}
 
+fn list_sections_in(notes_path: &Path) -> Result<Vec<Section>, String> {
     if !notes_path.exists() {
         return Err(format!("Notes directory not found: {:?}", notes_path));
     }
 
-    let gitnotes_config = load_gitnotes_config(&notes_path);
+    let gitnotes_config = load_gitnotes_config(notes_path);
 
-    let mut sections: Vec<Section> = fs::read_dir(&notes_path)
+    let mut sections: Vec<Section> = fs::read_dir(notes_path)
         .map_err(|e| e.to_string())?
         .filter_map(|entry| {
             let entry = entry.ok()?;
@@ -690,15 +1001,15 @@ fn collect_notes_from_dir(dir: &PathBuf, subfolder: Option<String>, section_type
                 // Try frontmatter first, fall back to filesystem
                 let (created, modified, contact_title, contact_company, contact_role, contact_email, imported, last_call) =
                     if let Ok(content) = fs::read_to_string(&path) {
-                        let (fm, _) = parse_frontmatter(&content);
+                        let (fm, _) = frontmatter::split(&content);
                         let fm_created = fm
                             .as_ref()
-                            .and_then(|f| f.created.as_ref())
-                            .map(|s| iso_to_timestamp(s));
+                            .and_then(|f| f.get_str("created"))
+                            .map(iso_to_timestamp);
                         let fm_modified = fm
                             .as_ref()
-                            .and_then(|f| f.modified.as_ref())
-                            .map(|s| iso_to_timestamp(s));
+                            .and_then(|f| f.get_str("modified"))
+                            .map(iso_to_timestamp);
 
                         // Fall back to filesystem if frontmatter missing
                         let metadata = fs::metadata(&path).ok();
@@ -718,7 +1029,7 @@ fn collect_notes_from_dir(dir: &PathBuf, subfolder: Option<String>, section_type
                         // Extract rolodex contact fields if applicable
                         let (ct, cc, cr, ce, imp, lc) = if is_rolodex {
                             if let Some(ref f) = fm {
-                                let other = &f.other;
+                                let other = f.fields();
                                 let title = other.get("title").and_then(|v| v.as_str()).map(|s| s.to_string());
                                 let company = other.get("company").and_then(|v| v.as_str()).map(|s| s.to_string());
                                 let role = other.get("role").and_then(|v| v.as_str()).map(|s| s.to_string());
@@ -771,7 +1082,11 @@ fn collect_notes_from_dir(dir: &PathBuf, subfolder: Option<String>, section_type
 
 #[tauri::command]
 fn list_notes(section_path: String) -> Result<Vec<Note>, String> {
-    let path = PathBuf::from(&section_path);
+    list_notes_in(&get_notes_path(), &section_path)
+}
+
+fn list_notes_in(notes_path: &Path, section_path: &str) -> Result<Vec<Note>, String> {
+    let path = vault_path_in(notes_path, section_path)?;
 
     if !path.exists() {
         return Err(format!("Section not found: {}", section_path));
@@ -876,29 +1191,132 @@ fn list_notes(section_path: String) -> Result<Vec<Note>, String> {
     Ok(notes)
 }
 
+/// Version token for a note's content, handed back on save to detect changes made elsewhere.
+fn content_version(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))[..16].to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteContent {
+    pub content: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub enum WriteOutcome {
+    Saved {
+        version: String,
+    },
+    /// The note changed on disk since `expected_version` was read; nothing was written.
+    Conflict {
+        ours: String,
+        theirs: String,
+        theirs_version: String,
+        /// Three-way merge of the bodies against the last-read base, keeping the frontmatter
+        /// on disk. Has conflict markers unless `clean`; `None` if that base is gone.
+        merged: Option<String>,
+        clean: bool,
+    },
+}
+
 #[tauri::command]
-fn read_note(path: String) -> Result<String, String> {
-    fs::read_to_string(&path).map_err(|e| e.to_string())
+fn read_note(path: String, state: tauri::State<'_, AppState>) -> Result<NoteContent, String> {
+    open_note(&state, &vault_path(&path)?)
 }
 
+fn open_note(state: &AppState, file_path: &Path) -> Result<NoteContent, String> {
+    let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
+    state.remember_base(file_path, &content);
+    Ok(NoteContent {
+        version: content_version(&content),
+        content,
+    })
+}
+
+/// Save a note. With `expected_version`, the write only happens if the file still has that
+/// version; otherwise the conflicting content comes back for the editor to resolve.
 #[tauri::command]
-fn write_note(path: String, content: String) -> Result<(), String> {
-    let file_path = PathBuf::from(&path);
+fn write_note(
+    path: String,
+    content: String,
+    expected_version: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<WriteOutcome, GitNotesError> {
+    save_note(&state, &vault_path(&path)?, content, expected_version)
+}
+
+fn save_note(
+    state: &AppState,
+    file_path: &Path,
+    content: String,
+    expected_version: Option<String>,
+) -> Result<WriteOutcome, GitNotesError> {
+    if let Some(expected) = &expected_version {
+        // Gone since it was read (deleted or moved elsewhere); saving would bring it back
+        let theirs = fs::read_to_string(file_path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => GitNotesError::NotFound {
+                path: file_path.to_string_lossy().to_string(),
+            },
+            _ => GitNotesError::io(file_path, e),
+        })?;
+        let theirs_version = content_version(&theirs);
+        if &theirs_version != expected {
+            let (merged, clean) = match state.base_for(file_path, expected) {
+                Some(base) => {
+                    let (merged, clean) = merge_note(&base, &content, &theirs)?;
+                    (Some(merged), clean)
+                }
+                None => (None, false),
+            };
+            return Ok(WriteOutcome::Conflict {
+                ours: content,
+                theirs,
+                theirs_version,
+                merged,
+                clean,
+            });
+        }
+    }
+
+    write_note_content(file_path, &content)?;
+    let saved = fs::read_to_string(file_path).map_err(|e| GitNotesError::io(file_path, e))?;
+    state.remember_base(file_path, &saved);
+    Ok(WriteOutcome::Saved {
+        version: content_version(&saved),
+    })
+}
+
+/// Merge the bodies of two edits of `base`. Frontmatter is taken from `theirs` as-is, since
+/// `write_note_content` rewrites `modified` on every save and would always conflict.
+fn merge_note(base: &str, ours: &str, theirs: &str) -> Result<(String, bool), GitNotesError> {
+    let (_, base_body) = frontmatter::split(base);
+    let (_, our_body) = frontmatter::split(ours);
+    let (their_fm, their_body) = frontmatter::split(theirs);
+    let (body, clean) = git::merge_text(base_body, our_body, their_body)?;
+    let merged = match their_fm {
+        Some(fm) => format!("{}\n\n{}", fm.to_block(), body),
+        None => body,
+    };
+    Ok((merged, clean))
+}
+
+/// Write a note, keeping its frontmatter and bumping `modified` only if the body changed.
+fn write_note_content(file_path: &Path, content: &str) -> Result<(), String> {
     let now = iso_now();
 
     // Parse frontmatter from incoming content
-    let (existing_fm, body) = parse_frontmatter(&content);
+    let (existing_fm, body) = frontmatter::split(content);
 
     // Read existing file to check for changes and get old frontmatter
     let old_content = if file_path.exists() {
-        fs::read_to_string(&file_path).ok()
+        fs::read_to_string(file_path).ok()
     } else {
         None
     };
 
     let (old_fm, old_body) = old_content
         .as_ref()
-        .map(|c| parse_frontmatter(c))
+        .map(|c| frontmatter::split(c))
         .map(|(fm, b)| (fm, b.to_string()))
         .unwrap_or((None, String::new()));
 
@@ -912,37 +1330,78 @@ fn write_note(path: String, content: String) -> Result<(), String> {
 
     // Merge frontmatter: prefer existing from content, fall back to file
     let mut fm = existing_fm.or(old_fm).unwrap_or_default();
-    if fm.created.is_none() {
-        fm.created = Some(now.clone());
+    if !fm.fields().contains_key("created") {
+        fm.set("created", &now);
+    }
+    // Update modified timestamp since content changed; every other line is kept as written
+    fm.set("modified", &now);
+
+    let final_content = format!("{}\n\n{}", fm.to_block(), body);
+    atomic::write(file_path, final_content).map_err(|e| e.to_string())
+}
+
+/// Note returned by rename/move, along with the notes whose links were rewritten.
+#[derive(Debug, Serialize)]
+pub struct MovedNote {
+    #[serde(flatten)]
+    pub note: Note,
+    pub updated_links: Vec<String>,
+}
+
+/// When `update_links` is set, work out the link rewrites needed to follow a
+/// move from `old` to `new`. Must run before the file is moved.
+fn plan_link_updates(
+    state: &AppState,
+    old: &Path,
+    new: &Path,
+    update_links: Option<bool>,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    if update_links.unwrap_or(false) {
+        Ok(state.link_index()?.plan_move(old, new))
+    } else {
+        Ok(vec![])
     }
-    // Update modified timestamp since content changed
-    fm.modified = Some(now);
+}
 
-    // Write with updated frontmatter
-    let final_content = format!("{}\n\n{}", format_frontmatter(&fm), body);
-    fs::write(&path, final_content).map_err(|e| e.to_string())
+fn apply_link_updates(rewrites: Vec<(PathBuf, String)>) -> Result<Vec<String>, String> {
+    let mut touched = Vec::new();
+    for (path, content) in rewrites {
+        write_note_content(&path, &content)?;
+        touched.push(path.to_string_lossy().to_string());
+    }
+    Ok(touched)
 }
 
 #[tauri::command]
-fn create_note(section_path: String, name: String) -> Result<Note, String> {
+fn create_note(section_path: String, name: String) -> Result<Note, GitNotesError> {
+    create_note_in(&get_notes_path(), &section_path, name)
+}
+
+fn create_note_in(
+    notes_path: &Path,
+    section_path: &str,
+    name: String,
+) -> Result<Note, GitNotesError> {
     let filename = format!("{}.md", name);
-    let path = PathBuf::from(&section_path).join(&filename);
+    let path = vault_path_in(notes_path, section_path)?.join(&filename);
+    // The name comes from the user too
+    let path = vault_path_in(notes_path, &path.to_string_lossy())?;
 
     if path.exists() {
-        return Err("Note already exists".to_string());
+        return Err(GitNotesError::AlreadyExists {
+            path: path.to_string_lossy().to_string(),
+        });
     }
 
     let now_iso = iso_now();
     let now_ts = iso_to_timestamp(&now_iso);
 
-    let fm = Frontmatter {
-        created: Some(now_iso.clone()),
-        modified: Some(now_iso),
-        other: std::collections::HashMap::new(),
-    };
+    let mut fm = Frontmatter::default();
+    fm.set("created", &now_iso);
+    fm.set("modified", &now_iso);
 
-    let content = format!("{}\n\n", format_frontmatter(&fm));
-    fs::write(&path, content).map_err(|e| e.to_string())?;
+    let content = format!("{}\n\n", fm.to_block());
+    atomic::write(&path, content).map_err(|e| GitNotesError::io(&path, e))?;
 
     Ok(Note {
         name,
@@ -967,7 +1426,11 @@ fn get_week_name() -> String {
 
 #[tauri::command]
 fn create_note_smart(section_path: String) -> Result<Note, String> {
-    let path = PathBuf::from(&section_path);
+    create_note_smart_in(&get_notes_path(), &section_path)
+}
+
+fn create_note_smart_in(notes_path: &Path, section_path: &str) -> Result<Note, String> {
+    let path = vault_path_in(notes_path, section_path)?;
     let section_name = path
         .file_name()
         .ok_or("Invalid path")?
@@ -999,14 +1462,12 @@ fn create_note_smart(section_path: String) -> Result<Note, String> {
     let now_iso = iso_now();
     let now_ts = iso_to_timestamp(&now_iso);
 
-    let fm = Frontmatter {
-        created: Some(now_iso.clone()),
-        modified: Some(now_iso),
-        other: std::collections::HashMap::new(),
-    };
+    let mut fm = Frontmatter::default();
+    fm.set("created", &now_iso);
+    fm.set("modified", &now_iso);
 
-    let content = format!("{}\n\n", format_frontmatter(&fm));
-    fs::write(&file_path, content).map_err(|e| e.to_string())?;
+    let content = format!("{}\n\n", fm.to_block());
+    atomic::write(&file_path, content).map_err(|e| e.to_string())?;
 
     // Position new note based on sort order (manual sort needs explicit ordering)
     let mut section_meta = load_section_metadata(&path);
@@ -1034,27 +1495,34 @@ fn create_note_smart(section_path: String) -> Result<Note, String> {
 
 #[tauri::command]
 fn delete_note(path: String) -> Result<(), String> {
-    let file_path = PathBuf::from(&path);
+    let file_path = vault_path(&path)?;
 
-    if !file_path.exists() {
+    // Sections go through delete_section, which protects the built-in ones
+    if !file_path.is_file() {
         return Err("Note not found".to_string());
     }
 
+    watcher::expect_removed(&file_path);
     trash::delete(&file_path).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn rename_note(old_path: String, new_name: String) -> Result<Note, String> {
-    let old_file = PathBuf::from(&old_path);
+fn rename_note(
+    old_path: String,
+    new_name: String,
+    update_links: Option<bool>,
+    state: tauri::State<AppState>,
+) -> Result<MovedNote, GitNotesError> {
+    let old_file = vault_path(&old_path)?;
 
     if !old_file.exists() {
-        return Err("Note not found".to_string());
+        return Err(GitNotesError::NotFound { path: old_path });
     }
 
     // Validate name - no filesystem-invalid chars
     let invalid_chars = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
     if new_name.chars().any(|c| invalid_chars.contains(&c)) {
-        return Err("Invalid characters in name".to_string());
+        return Err(GitNotesError::InvalidName { name: new_name });
     }
 
     let parent = old_file.parent().ok_or("Invalid path")?;
@@ -1062,7 +1530,9 @@ fn rename_note(old_path: String, new_name: String) -> Result<Note, String> {
     let new_path = parent.join(&new_filename);
 
     if new_path.exists() {
-        return Err("A note with that name already exists".to_string());
+        return Err(GitNotesError::AlreadyExists {
+            path: new_path.to_string_lossy().to_string(),
+        });
     }
 
     // Get timestamps before rename
@@ -1074,7 +1544,11 @@ fn rename_note(old_path: String, new_name: String) -> Result<Note, String> {
         .map(|d| d.as_secs())
         .unwrap_or(0);
 
-    fs::rename(&old_file, &new_path).map_err(|e| e.to_string())?;
+    let rewrites = plan_link_updates(&state, &old_file, &new_path, update_links)?;
+    watcher::expect_removed(&old_file);
+    watcher::expect_created(&new_path);
+    fs::rename(&old_file, &new_path).map_err(|e| GitNotesError::io(&old_file, e))?;
+    let updated_links = apply_link_updates(rewrites)?;
 
     let modified = fs::metadata(&new_path)
         .ok()
@@ -1083,7 +1557,7 @@ fn rename_note(old_path: String, new_name: String) -> Result<Note, String> {
         .map(|d| d.as_secs())
         .unwrap_or(0);
 
-    Ok(Note {
+    let note = Note {
         name: new_name,
         path: new_path.to_string_lossy().to_string(),
         filename: new_filename,
@@ -1096,15 +1570,22 @@ fn rename_note(old_path: String, new_name: String) -> Result<Note, String> {
         contact_email: None,
         imported: None,
         last_call: None,
-    })
+    };
+
+    Ok(MovedNote { note, updated_links })
 }
 
 #[tauri::command]
-fn move_note(path: String, new_section_path: String) -> Result<Note, String> {
-    let old_file = PathBuf::from(&path);
+fn move_note(
+    path: String,
+    new_section_path: String,
+    update_links: Option<bool>,
+    state: tauri::State<AppState>,
+) -> Result<MovedNote, GitNotesError> {
+    let old_file = vault_path(&path)?;
 
     if !old_file.exists() {
-        return Err("Note not found".to_string());
+        return Err(GitNotesError::NotFound { path });
     }
 
     let filename = old_file
@@ -1113,10 +1594,18 @@ fn move_note(path: String, new_section_path: String) -> Result<Note, String> {
         .to_string_lossy()
         .to_string();
 
-    let new_path = PathBuf::from(&new_section_path).join(&filename);
+    let section_dir = vault_path(&new_section_path)?;
+    if !section_dir.is_dir() {
+        return Err(GitNotesError::NotFound {
+            path: new_section_path,
+        });
+    }
+    let new_path = section_dir.join(&filename);
 
     if new_path.exists() {
-        return Err("A note with that name already exists in the target section".to_string());
+        return Err(GitNotesError::AlreadyExists {
+            path: new_path.to_string_lossy().to_string(),
+        });
     }
 
     // Get timestamps before move
@@ -1128,7 +1617,11 @@ fn move_note(path: String, new_section_path: String) -> Result<Note, String> {
         .map(|d| d.as_secs())
         .unwrap_or(0);
 
-    fs::rename(&old_file, &new_path).map_err(|e| e.to_string())?;
+    let rewrites = plan_link_updates(&state, &old_file, &new_path, update_links)?;
+    watcher::expect_removed(&old_file);
+    watcher::expect_created(&new_path);
+    fs::rename(&old_file, &new_path).map_err(|e| GitNotesError::io(&old_file, e))?;
+    let updated_links = apply_link_updates(rewrites)?;
 
     let modified = fs::metadata(&new_path)
         .ok()
@@ -1137,7 +1630,7 @@ fn move_note(path: String, new_section_path: String) -> Result<Note, String> {
         .map(|d| d.as_secs())
         .unwrap_or(0);
 
-    Ok(Note {
+    let note = Note {
         name: filename.trim_end_matches(".md").to_string(),
         path: new_path.to_string_lossy().to_string(),
         filename,
@@ -1150,7 +1643,9 @@ fn move_note(path: String, new_section_path: String) -> Result<Note, String> {
         contact_email: None,
         imported: None,
         last_call: None,
-    })
+    };
+
+    Ok(MovedNote { note, updated_links })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1162,15 +1657,17 @@ pub struct SearchResult {
 
 #[tauri::command]
 fn list_all_notes() -> Result<Vec<SearchResult>, String> {
-    let notes_path = get_notes_path();
+    list_all_notes_in(&get_notes_path())
+}
 
+fn list_all_notes_in(notes_path: &Path) -> Result<Vec<SearchResult>, String> {
     if !notes_path.exists() {
         return Err(format!("Notes directory not found: {:?}", notes_path));
     }
 
     let mut results: Vec<SearchResult> = Vec::new();
 
-    let sections = fs::read_dir(&notes_path).map_err(|e| e.to_string())?;
+    let sections = fs::read_dir(notes_path).map_err(|e| e.to_string())?;
 
     for section_entry in sections.filter_map(|e| e.ok()) {
         let section_path = section_entry.path();
@@ -1220,20 +1717,22 @@ fn list_all_notes() -> Result<Vec<SearchResult>, String> {
 }
 
 #[tauri::command]
-fn create_section(name: String) -> Result<Section, String> {
+fn create_section(name: String) -> Result<Section, GitNotesError> {
     let invalid_chars = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
     if name.chars().any(|c| invalid_chars.contains(&c)) {
-        return Err("Invalid characters in name".to_string());
+        return Err(GitNotesError::InvalidName { name });
     }
 
-    let notes_path = get_notes_path();
-    let section_path = notes_path.join(&name);
+    let section_path = vault_path(&name)?;
 
     if section_path.exists() {
-        return Err("Section already exists".to_string());
+        return Err(GitNotesError::AlreadyExists {
+            path: section_path.to_string_lossy().to_string(),
+        });
     }
 
-    fs::create_dir(&section_path).map_err(|e| e.to_string())?;
+    watcher::expect_created(&section_path);
+    fs::create_dir(&section_path).map_err(|e| GitNotesError::io(&section_path, e))?;
 
     Ok(Section {
         name,
@@ -1245,11 +1744,11 @@ fn create_section(name: String) -> Result<Section, String> {
 }
 
 #[tauri::command]
-fn rename_section(path: String, new_name: String) -> Result<Section, String> {
-    let section_path = PathBuf::from(&path);
+fn rename_section(path: String, new_name: String) -> Result<Section, GitNotesError> {
+    let section_path = vault_path(&path)?;
 
     if !section_path.exists() {
-        return Err("Section not found".to_string());
+        return Err(GitNotesError::NotFound { path });
     }
 
     let folder_name = section_path
@@ -1260,7 +1759,7 @@ fn rename_section(path: String, new_name: String) -> Result<Section, String> {
 
     let old_name_lower = folder_name.to_lowercase();
     if PROTECTED_SECTIONS.contains(&old_name_lower.as_str()) {
-        return Err("This section cannot be renamed".to_string());
+        return Err(GitNotesError::Protected { name: folder_name });
     }
 
     // Load existing metadata to preserve color
@@ -1280,11 +1779,11 @@ fn rename_section(path: String, new_name: String) -> Result<Section, String> {
 }
 
 #[tauri::command]
-fn delete_section(path: String) -> Result<(), String> {
-    let dir_path = PathBuf::from(&path);
+fn delete_section(path: String) -> Result<(), GitNotesError> {
+    let dir_path = vault_path(&path)?;
 
-    if !dir_path.exists() {
-        return Err("Section not found".to_string());
+    if !dir_path.exists() || dir_path == get_notes_path() {
+        return Err(GitNotesError::NotFound { path });
     }
 
     let name = dir_path
@@ -1294,10 +1793,14 @@ fn delete_section(path: String) -> Result<(), String> {
         .to_lowercase();
 
     if PROTECTED_SECTIONS.contains(&name.as_str()) {
-        return Err("This section cannot be deleted".to_string());
+        return Err(GitNotesError::Protected { name });
     }
 
-    trash::delete(&dir_path).map_err(|e| e.to_string())
+    watcher::expect_removed(&dir_path);
+    trash::delete(&dir_path).map_err(|e| GitNotesError::Io {
+        path: Some(path),
+        message: e.to_string(),
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1307,7 +1810,7 @@ pub struct FileMetadata {
 
 #[tauri::command]
 fn get_file_metadata(path: String) -> Result<FileMetadata, String> {
-    let file_path = PathBuf::from(&path);
+    let file_path = vault_path(&path)?;
 
     if !file_path.exists() {
         return Ok(FileMetadata { created: None });
@@ -1325,7 +1828,7 @@ fn get_file_metadata(path: String) -> Result<FileMetadata, String> {
 
 #[tauri::command]
 fn list_whispers(note_path: String) -> Result<Vec<Whisper>, String> {
-    let note = PathBuf::from(&note_path);
+    let note = vault_path(&note_path)?;
     let parent = note.parent().ok_or("Invalid note path")?;
     let stem = note
         .file_stem()
@@ -1411,81 +1914,45 @@ pub struct RepoStatus {
     pub last_commit_date: Option<String>,
     pub last_commit_author: Option<String>,
     pub is_team: bool,
+    #[serde(flatten)]
+    pub sync: SyncStatus,
 }
 
-#[tauri::command]
-fn get_git_info(path: String) -> Result<GitInfo, String> {
-    let file_path = PathBuf::from(&path);
-    let parent = file_path.parent().unwrap_or(&file_path);
-
-    // Check if in a git repo
-    let repo_check = Command::new("git")
-        .args(["rev-parse", "--git-dir"])
-        .current_dir(parent)
-        .output();
-
-    let is_git_repo = repo_check.map(|o| o.status.success()).unwrap_or(false);
-
-    if !is_git_repo {
-        return Ok(GitInfo {
-            last_commit_date: None,
-            last_commit_author: None,
-            is_dirty: false,
-            is_tracked: false,
-            is_git_repo: false,
-        });
-    }
+/// Commits `get_git_info` looks back through for a note's last change.
+const GIT_INFO_WALK_LIMIT: usize = 500;
 
-    // Check if file is tracked
-    let tracked_check = Command::new("git")
-        .args(["ls-files", &path])
-        .current_dir(parent)
-        .output();
-
-    let is_tracked = tracked_check
-        .map(|o| o.status.success() && !o.stdout.is_empty())
-        .unwrap_or(false);
-
-    // Check if file has uncommitted changes
-    let dirty_check = Command::new("git")
-        .args(["diff", "--name-only", &path])
-        .current_dir(parent)
-        .output();
-
-    let staged_check = Command::new("git")
-        .args(["diff", "--cached", "--name-only", &path])
-        .current_dir(parent)
-        .output();
-
-    let is_dirty = dirty_check.map(|o| !o.stdout.is_empty()).unwrap_or(false)
-        || staged_check.map(|o| !o.stdout.is_empty()).unwrap_or(false)
-        || !is_tracked;
-
-    // Get last commit info
-    let log_output = Command::new("git")
-        .args(["log", "-1", "--format=%aI|%an", "--", &path])
-        .current_dir(parent)
-        .output();
-
-    let (last_commit_date, last_commit_author) = match log_output {
-        Ok(output) if output.status.success() => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let parts: Vec<&str> = stdout.trim().split('|').collect();
-            if parts.len() == 2 {
-                (Some(parts[0].to_string()), Some(parts[1].to_string()))
-            } else {
-                (None, None)
-            }
+#[tauri::command]
+fn get_git_info(path: String) -> Result<GitInfo, GitNotesError> {
+    let file_path = vault_path(&path)?;
+
+    let repo = match git::open(&file_path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            return Ok(GitInfo {
+                last_commit_date: None,
+                last_commit_author: None,
+                is_dirty: false,
+                is_tracked: false,
+                is_git_repo: false,
+            })
         }
-        _ => (None, None),
     };
 
+    let rel_path = git::relative_path(&repo, &file_path)?;
+    let status = git::path_status(&repo, &rel_path);
+
+    // Shown whenever a note is opened, so only look through recent history
+    let last_commit = status
+        .is_tracked
+        .then(|| git::last_commit(&repo, &rel_path, GIT_INFO_WALK_LIMIT))
+        .flatten();
+
     Ok(GitInfo {
-        last_commit_date,
-        last_commit_author,
-        is_dirty,
-        is_tracked,
-        is_git_repo,
+        last_commit_date: last_commit.as_ref().map(|c| c.date.clone()),
+        last_commit_author: last_commit.map(|c| c.author),
+        is_dirty: status.is_dirty,
+        is_tracked: status.is_tracked,
+        is_git_repo: true,
     })
 }
 
@@ -1493,98 +1960,87 @@ fn get_git_info(path: String) -> Result<GitInfo, String> {
 fn search_notes(
     query: String,
     state: tauri::State<AppState>,
-) -> Result<Vec<TantivySearchResult>, String> {
-    if query.len() < 3 {
-        // For short queries, return empty - frontend handles filename matching
-        return Ok(vec![]);
-    }
-    state.search_index.search(&query, 20)
+) -> Result<Vec<TantivySearchResult>, GitNotesError> {
+    state.search_index()?.search(&query, 20, &load_settings().search)
 }
 
 #[tauri::command]
-fn git_commit(path: String, message: String) -> Result<(), String> {
-    let file_path = PathBuf::from(&path);
-    let parent = file_path.parent().unwrap_or(&file_path);
-
-    // Add the file
-    let add_result = Command::new("git")
-        .args(["add", &path])
-        .current_dir(parent)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if !add_result.status.success() {
-        return Err(String::from_utf8_lossy(&add_result.stderr).to_string());
-    }
-
-    // Commit
-    let commit_result = Command::new("git")
-        .args(["commit", "-m", &message, "--", &path])
-        .current_dir(parent)
-        .output()
-        .map_err(|e| e.to_string())?;
+fn list_tags(state: tauri::State<AppState>) -> Result<Vec<TagCount>, GitNotesError> {
+    state.search_index()?.list_tags()
+}
 
-    if !commit_result.status.success() {
-        let stderr = String::from_utf8_lossy(&commit_result.stderr);
-        // "nothing to commit" is not an error for us
-        if !stderr.contains("nothing to commit") {
-            return Err(stderr.to_string());
-        }
-    }
+#[tauri::command]
+fn notes_with_tag(
+    tag: String,
+    state: tauri::State<AppState>,
+) -> Result<Vec<TantivySearchResult>, GitNotesError> {
+    state.search_index()?.notes_with_tag(&tag)
+}
 
-    Ok(())
+#[tauri::command]
+fn related_notes(
+    path: String,
+    limit: Option<usize>,
+    state: tauri::State<AppState>,
+) -> Result<Vec<RelatedNote>, GitNotesError> {
+    let file_path = vault_path(&path)?;
+    let link_index = state.link_index()?;
+    state
+        .search_index()?
+        .related_notes(&file_path, limit.unwrap_or(10), &link_index)
 }
 
 #[tauri::command]
-fn git_commit_and_push(message: String) -> Result<(), String> {
-    let notes_path = get_notes_path();
+fn get_backlinks(path: String, state: tauri::State<AppState>) -> Result<Vec<NoteLink>, String> {
+    Ok(state.link_index()?.backlinks(&vault_path(&path)?))
+}
 
-    // Stage all changes
-    let add_result = Command::new("git")
-        .args(["add", "-A"])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn get_outgoing_links(path: String, state: tauri::State<AppState>) -> Result<Vec<NoteLink>, String> {
+    Ok(state.link_index()?.outgoing_links(&vault_path(&path)?))
+}
 
-    if !add_result.status.success() {
-        return Err(String::from_utf8_lossy(&add_result.stderr).to_string());
-    }
+#[tauri::command]
+fn get_unresolved_links(state: tauri::State<AppState>) -> Result<Vec<NoteLink>, String> {
+    Ok(state.link_index()?.unresolved_links())
+}
 
-    // Commit
-    let commit_result = Command::new("git")
-        .args(["commit", "-m", &message])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn git_commit(path: String, message: String) -> Result<(), GitNotesError> {
+    let file_path = vault_path(&path)?;
+    let repo = git::open(&file_path)?;
+    let rel_path = git::relative_path(&repo, &file_path)?;
 
-    if !commit_result.status.success() {
-        let stderr = String::from_utf8_lossy(&commit_result.stderr);
-        if !stderr.contains("nothing to commit") {
-            return Err(stderr.to_string());
-        }
-        // Nothing to commit - still try to push in case there are unpushed commits
-    }
+    // Nothing to commit is not an error for us
+    git::commit_path(&repo, &rel_path, &message)?;
+    Ok(())
+}
 
-    // Push
-    let push_result = Command::new("git")
-        .args(["push"])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
+/// Commit several notes together, e.g. a renamed note and the notes whose links followed it.
+#[tauri::command]
+fn git_commit_files(paths: Vec<String>, message: String) -> Result<(), GitNotesError> {
+    let notes_path = get_notes_path();
+    let repo = git::open(&notes_path)?;
+    let rel_paths = paths
+        .iter()
+        .map(|path| git::relative_path(&repo, &vault_path(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let rel_paths: Vec<&str> = rel_paths.iter().map(String::as_str).collect();
+    git::commit_paths(&repo, &rel_paths, &message)?;
+    Ok(())
+}
 
-    if !push_result.status.success() {
-        let stderr = String::from_utf8_lossy(&push_result.stderr);
-        // "Everything up-to-date" goes to stderr but isn't an error
-        if !stderr.contains("Everything up-to-date") && !stderr.is_empty() {
-            return Err(stderr.to_string());
-        }
-    }
+#[tauri::command]
+fn git_commit_and_push(message: String) -> Result<(), GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
 
-    Ok(())
+    // Nothing to commit - still push in case there are unpushed commits
+    git::commit_all(&repo, &message)?;
+    Ok(git::push(&repo)?)
 }
 
 #[tauri::command]
-fn get_repo_status() -> Result<RepoStatus, String> {
+fn get_repo_status() -> Result<RepoStatus, GitNotesError> {
     let notes_path = get_notes_path();
 
     // Get repo name from active vault config, falling back to folder name
@@ -1601,80 +2057,23 @@ fn get_repo_status() -> Result<RepoStatus, String> {
                 .unwrap_or_else(|| "notes".to_string())
         });
 
-    // Check dirty status (count of uncommitted files)
-    let status_output = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    let dirty_count = if status_output.status.success() {
-        String::from_utf8_lossy(&status_output.stdout)
-            .lines()
-            .count() as u32
-    } else {
-        0
-    };
+    // Not being a git repo isn't an error; everything just reads as empty
+    let repo = git::open(&notes_path).ok();
 
+    // Check dirty status (count of uncommitted files)
+    let dirty_files = repo
+        .as_ref()
+        .and_then(|r| git::status(r).ok())
+        .unwrap_or_default();
+    let dirty_count = dirty_files.len() as u32;
     let is_dirty = dirty_count > 0;
 
-    // Get diff stats (insertions/deletions) for uncommitted changes
-    let (insertions, deletions) = if is_dirty {
-        // Get stats for both staged and unstaged changes
-        let diff_output = Command::new("git")
-            .args(["diff", "--numstat", "HEAD"])
-            .current_dir(&notes_path)
-            .output();
-
-        match diff_output {
-            Ok(output) if output.status.success() => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let mut ins: u32 = 0;
-                let mut del: u32 = 0;
-                for line in stdout.lines() {
-                    let parts: Vec<&str> = line.split('\t').collect();
-                    if parts.len() >= 2 {
-                        // Binary files show "-" instead of numbers
-                        if let Ok(i) = parts[0].parse::<u32>() {
-                            ins += i;
-                        }
-                        if let Ok(d) = parts[1].parse::<u32>() {
-                            del += d;
-                        }
-                    }
-                }
-                (ins, del)
-            }
-            _ => (0, 0),
-        }
-    } else {
-        (0, 0)
-    };
+    // Diff stats (insertions/deletions) for staged and unstaged changes
+    let insertions = dirty_files.iter().map(|f| f.insertions).sum();
+    let deletions = dirty_files.iter().map(|f| f.deletions).sum();
 
     // Get last commit info
-    let log_output = Command::new("git")
-        .args(["log", "-1", "--format=%H|%s|%aI|%an"])
-        .current_dir(&notes_path)
-        .output();
-
-    let (last_commit_hash, last_commit_message, last_commit_date, last_commit_author) =
-        match log_output {
-            Ok(output) if output.status.success() => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let parts: Vec<&str> = stdout.trim().split('|').collect();
-                if parts.len() == 4 {
-                    (
-                        Some(parts[0].chars().take(7).collect()), // Short hash (safe)
-                        Some(parts[1].to_string()),
-                        Some(parts[2].to_string()),
-                        Some(parts[3].to_string()),
-                    )
-                } else {
-                    (None, None, None, None)
-                }
-            }
-            _ => (None, None, None, None),
-        };
+    let last_commit = repo.as_ref().and_then(git::head);
 
     // Detect team brain: use cached value or count unique committer emails
     let mut settings = load_settings();
@@ -1687,22 +2086,10 @@ fn get_repo_status() -> Result<RepoStatus, String> {
     let is_team = if let Some(cached) = cached_is_team {
         cached
     } else {
-        let unique_authors = Command::new("sh")
-            .args(["-c", "git log --format='%ae' | sort -u | head -3"])
-            .current_dir(&notes_path)
-            .output();
-
-        let author_count = unique_authors
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| {
-                String::from_utf8_lossy(&o.stdout)
-                    .lines()
-                    .filter(|l| !l.trim().is_empty())
-                    .count()
-            })
+        let author_count = repo
+            .as_ref()
+            .map(|r| git::author_emails(r, 3).len())
             .unwrap_or(0);
-
         let detected = author_count >= 2;
 
         // Cache result on the active vault
@@ -1730,84 +2117,239 @@ fn get_repo_status() -> Result<RepoStatus, String> {
         dirty_count,
         insertions,
         deletions,
-        last_commit_hash,
-        last_commit_message,
-        last_commit_date,
-        last_commit_author,
+        last_commit_hash: last_commit.as_ref().map(|c| c.hash.clone()),
+        last_commit_message: last_commit.as_ref().map(|c| c.message.clone()),
+        last_commit_date: last_commit.as_ref().map(|c| c.date.clone()),
+        last_commit_author: last_commit.map(|c| c.author),
         is_team: effective_is_team,
+        sync: repo.as_ref().map(git::sync_status).unwrap_or_default(),
     })
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DirtyFile {
-    pub path: String,
-    pub filename: String,
-    pub status: String, // M, A, D, R, etc.
-    pub insertions: u32,
-    pub deletions: u32,
+/// Result of `sync_vault`. Failures that aren't auth or network related are returned as errors.
+#[derive(Debug, Serialize)]
+pub enum SyncOutcome {
+    UpToDate,
+    Pushed,
+    /// Remote changes were integrated (local commits, if any, were pushed too)
+    Pulled,
+    /// Integration stopped on conflicts; the repo is left mid-rebase/merge
+    Conflicts(Vec<String>),
+    AuthFailed,
+    Offline,
 }
 
+/// Turn a fetch/push failure into a sync outcome, or an error if it isn't auth or network related.
+fn remote_outcome(e: RemoteError) -> Result<SyncOutcome, GitNotesError> {
+    match e {
+        RemoteError::AuthFailed => Ok(SyncOutcome::AuthFailed),
+        RemoteError::Offline => Ok(SyncOutcome::Offline),
+        e @ RemoteError::Other(_) => Err(e.into()),
+    }
+}
+
+/// Commit everything, pull (rebase or merge, per vault), then push.
 #[tauri::command]
-fn get_dirty_files() -> Result<Vec<DirtyFile>, String> {
+fn sync_vault(message: Option<String>) -> Result<SyncOutcome, GitNotesError> {
     let notes_path = get_notes_path();
+    sync_repo(&notes_path, pull_uses_merge(&load_settings(), &notes_path), message)
+}
+
+/// The configured vault whose folder is `path`, if any.
+fn find_vault_by_path(settings: &Settings, path: &Path) -> Option<Vault> {
+    let canonical = path.canonicalize().ok();
+    settings
+        .vaults
+        .iter()
+        .find(|v| {
+            let vault_path = Path::new(&v.path);
+            vault_path == path || (canonical.is_some() && vault_path.canonicalize().ok() == canonical)
+        })
+        .cloned()
+}
+
+fn pull_uses_merge(settings: &Settings, notes_path: &Path) -> bool {
+    find_vault_by_path(settings, notes_path)
+        .and_then(|v| v.pull_strategy)
+        .as_deref()
+        == Some("merge")
+}
 
-    // Get file statuses
-    let status_output = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
+fn sync_repo(notes_path: &Path, use_merge: bool, message: Option<String>) -> Result<SyncOutcome, GitNotesError> {
+    let repo = git::open(notes_path)?;
 
-    if !status_output.status.success() {
-        return Ok(vec![]);
+    if git::pending_operation(&repo).is_some() {
+        return Ok(SyncOutcome::Conflicts(git::conflicted_paths(&repo)));
     }
 
-    // Get diff stats for tracked files
-    let numstat_output = Command::new("git")
-        .args(["diff", "--numstat", "HEAD"])
-        .current_dir(&notes_path)
-        .output()
-        .ok();
-
-    // Build a map of path -> (insertions, deletions)
-    let mut stats_map: std::collections::HashMap<String, (u32, u32)> =
-        std::collections::HashMap::new();
-    if let Some(output) = numstat_output {
-        if output.status.success() {
-            for line in String::from_utf8_lossy(&output.stdout).lines() {
-                let parts: Vec<&str> = line.split('\t').collect();
-                if parts.len() >= 3 {
-                    let ins = parts[0].parse().unwrap_or(0);
-                    let del = parts[1].parse().unwrap_or(0);
-                    stats_map.insert(parts[2].to_string(), (ins, del));
-                }
-            }
+    // Commit local changes
+    let message = message.unwrap_or_else(|| format!("Sync {}", iso_now()));
+    git::commit_all(&repo, &message)?;
+
+    if git::sync_status(&repo).upstream.is_none() {
+        return Err(GitNotesError::git(
+            GitErrorKind::NoUpstream,
+            "No upstream branch configured",
+        ));
+    }
+
+    // Pull, split into fetch + rebase/merge so network and conflict failures can be told apart
+    if let Err(e) = git::fetch(&repo, false) {
+        return remote_outcome(e);
+    }
+
+    let pulled = git::sync_status(&repo).behind > 0;
+    if pulled {
+        if let Integration::Conflicts(conflicts) = git::integrate_upstream(&repo, use_merge)? {
+            return Ok(SyncOutcome::Conflicts(conflicts));
         }
     }
 
-    let files: Vec<DirtyFile> = String::from_utf8_lossy(&status_output.stdout)
-        .lines()
-        .filter_map(|line| {
-            if line.len() < 4 {
-                return None;
+    if git::sync_status(&repo).ahead > 0 {
+        if let Err(e) = git::push(&repo) {
+            return remote_outcome(e);
+        }
+    } else if !pulled {
+        return Ok(SyncOutcome::UpToDate);
+    }
+
+    Ok(if pulled {
+        SyncOutcome::Pulled
+    } else {
+        SyncOutcome::Pushed
+    })
+}
+
+/// Fetch from the upstream remote and return the refreshed sync status.
+#[tauri::command]
+fn git_fetch() -> Result<SyncStatus, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+    git::fetch(&repo, true)?;
+    Ok(git::sync_status(&repo))
+}
+
+/// One conflicted file with each side's content. A side is `None` when the file doesn't exist
+/// there (e.g. deleted on one side, or added on both with no common base).
+#[derive(Debug, Serialize)]
+pub struct ConflictFile {
+    pub path: String,
+    pub base: Option<String>,
+    /// This vault's version
+    pub ours: Option<String>,
+    /// The incoming (remote) version
+    pub theirs: Option<String>,
+    /// Working tree content, including conflict markers
+    pub working: Option<String>,
+}
+
+#[tauri::command]
+fn list_conflicts() -> Result<Vec<ConflictFile>, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+
+    Ok(git::conflicts(&repo)?
+        .into_iter()
+        .map(|conflict| ConflictFile {
+            working: git::workdir_path(&repo, &conflict.path)
+                .ok()
+                .and_then(|p| fs::read_to_string(p).ok()),
+            path: conflict.path,
+            base: conflict.base,
+            ours: conflict.ours,
+            theirs: conflict.theirs,
+        })
+        .collect())
+}
+
+/// Resolve a conflicted file and stage it. Pass either `strategy` ("ours" or "theirs")
+/// or the hand-merged `merged_content`.
+#[tauri::command]
+fn resolve_conflict(
+    path: String,
+    strategy: Option<String>,
+    merged_content: Option<String>,
+) -> Result<(), GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+
+    let conflict = git::conflicts(&repo)?
+        .into_iter()
+        .find(|c| c.path == path)
+        .ok_or_else(|| GitNotesError::NotFound { path: path.clone() })?;
+
+    let resolved = match (strategy.as_deref(), merged_content) {
+        (None, Some(content)) => Some(content),
+        (Some("ours"), None) => conflict.ours,
+        (Some("theirs"), None) => conflict.theirs,
+        (Some(other), None) => {
+            return Err(format!("Unknown resolution strategy: {}", other).into())
+        }
+        _ => return Err("Pass either a strategy or merged content".into()),
+    };
+
+    let file_path = git::workdir_path(&repo, &path)?;
+    match resolved {
+        Some(content) => atomic::write(&file_path, content)?,
+        // The chosen side deleted the file
+        None => {
+            if file_path.exists() {
+                watcher::expect_removed(&file_path);
+                fs::remove_file(&file_path)?;
             }
-            let status = line[0..2].trim().to_string();
-            let path = line[3..].to_string();
+        }
+    }
+
+    git::mark_resolved(&repo, &path)
+}
+
+/// Abandon an in-progress merge or rebase, restoring the pre-sync state.
+#[tauri::command]
+fn abort_merge() -> Result<(), GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+    git::abort_operation(&repo)
+}
+
+/// Continue a rebase (or conclude a merge) once all conflicts are resolved. Returns the files
+/// in conflict at the next stop; an empty list means the integration finished.
+#[tauri::command]
+fn continue_rebase() -> Result<Vec<String>, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+    match git::continue_operation(&repo)? {
+        Integration::Done => Ok(vec![]),
+        Integration::Conflicts(conflicts) => Ok(conflicts),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirtyFile {
+    pub path: String,
+    pub filename: String,
+    pub status: String, // M, A, D, R, etc.
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+#[tauri::command]
+fn get_dirty_files() -> Result<Vec<DirtyFile>, GitNotesError> {
+    let Ok(repo) = git::open(&get_notes_path()) else {
+        return Ok(vec![]);
+    };
+
+    let files: Vec<DirtyFile> = git::status(&repo)?
+        .into_iter()
+        .map(|file| {
             // Handle paths ending with / (directories) - trim and get last non-empty segment
-            let trimmed_path = path.trim_end_matches('/');
+            let trimmed_path = file.path.trim_end_matches('/');
             let filename = trimmed_path
-                .split('/')
-                .last()
+                .rsplit('/')
+                .next()
                 .unwrap_or(trimmed_path)
                 .to_string();
-            let (insertions, deletions) = stats_map.get(trimmed_path).copied().unwrap_or((0, 0));
-            Some(DirtyFile {
-                path,
+            DirtyFile {
                 filename,
-                status,
-                insertions,
-                deletions,
-            })
+                path: file.path,
+                status: file.status,
+                insertions: file.insertions,
+                deletions: file.deletions,
+            }
         })
         .collect();
 
@@ -1820,35 +2362,25 @@ fn get_vault_path() -> String {
 }
 
 #[tauri::command]
-fn get_file_diff(path: String, vault_path: Option<String>) -> Result<String, String> {
-    let notes_path = vault_path.map(PathBuf::from).unwrap_or_else(get_notes_path);
-
-    // Try diff against HEAD (modified files)
-    let output = Command::new("git")
-        .args(["diff", "HEAD", "--", &path])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
-    if !diff.is_empty() {
-        return Ok(diff);
-    }
-
-    // Try staged diff (new staged files)
-    let output = Command::new("git")
-        .args(["diff", "--cached", "--", &path])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
+fn get_file_diff(path: String, vault_path: Option<String>) -> Result<String, GitNotesError> {
+    // Only configured vaults, so this can't be used to read arbitrary folders
+    let notes_path = match vault_path {
+        Some(path) => find_vault_by_path(&load_settings(), Path::new(&path))
+            .map(|v| PathBuf::from(v.path))
+            .ok_or_else(|| format!("Not a configured vault: {}", path))?,
+        None => get_notes_path(),
+    };
+    let file_path = paths::resolve_in_vault(&notes_path, &path)?;
 
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
-    if !diff.is_empty() {
-        return Ok(diff);
+    // Diff against HEAD covers modified files and new staged files
+    if let Ok(repo) = git::open(&notes_path) {
+        let diff = git::diff_path(&repo, &path)?;
+        if !diff.is_empty() {
+            return Ok(diff);
+        }
     }
 
     // For untracked files, show content as addition
-    let file_path = notes_path.join(&path);
     if file_path.exists() {
         if file_path.is_file() {
             if let Ok(content) = std::fs::read_to_string(&file_path) {
@@ -1891,20 +2423,9 @@ fn get_file_diff(path: String, vault_path: Option<String>) -> Result<String, Str
 }
 
 #[tauri::command]
-fn get_commit_diff(hash: String) -> Result<String, String> {
-    let notes_path = get_notes_path();
-
-    let output = Command::new("git")
-        .args(["show", "--format=", &hash])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
-        Err("Failed to get commit diff".to_string())
-    }
+fn get_commit_diff(hash: String) -> Result<String, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
+    git::commit_diff(&repo, &hash)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1919,118 +2440,69 @@ pub struct GitLogEntry {
 }
 
 #[tauri::command]
-fn get_git_log(limit: Option<u32>) -> Result<Vec<GitLogEntry>, String> {
-    let notes_path = get_notes_path();
+fn get_git_log(limit: Option<u32>) -> Result<Vec<GitLogEntry>, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
     let limit = limit.unwrap_or(50);
 
-    // Get current HEAD hash
-    let head_output = Command::new("git")
-        .args(["rev-parse", "HEAD"])
-        .current_dir(&notes_path)
-        .output();
+    let head_hash = git::head(&repo).map(|c| c.full_hash);
 
-    let head_hash = head_output
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
-
-    // Get log entries with shortstat
-    let log_output = Command::new("git")
-        .args([
-            "log",
-            &format!("-{}", limit),
-            "--format=COMMIT|%H|%s|%aI|%an",
-            "--shortstat",
-        ])
-        .current_dir(&notes_path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if !log_output.status.success() {
-        return Err("Failed to get git log".to_string());
-    }
-
-    let output = String::from_utf8_lossy(&log_output.stdout);
-    let mut entries: Vec<GitLogEntry> = Vec::new();
-    let mut current_entry: Option<(String, String, String, String, String)> = None;
-
-    for line in output.lines() {
-        if line.starts_with("COMMIT|") {
-            // Save previous entry if exists
-            if let Some((hash, message, date, author, full_hash)) = current_entry.take() {
-                let is_head = head_hash.as_ref().map(|h| h == &full_hash).unwrap_or(false);
-                entries.push(GitLogEntry {
-                    hash,
-                    message,
-                    date,
-                    author,
-                    is_head,
-                    insertions: 0,
-                    deletions: 0,
-                });
-            }
-            // Parse new entry
-            let parts: Vec<&str> = line.split('|').collect();
-            if parts.len() == 5 {
-                let full_hash = parts[1].to_string();
-                current_entry = Some((
-                    full_hash.chars().take(7).collect(),
-                    parts[2].to_string(),
-                    parts[3].to_string(),
-                    parts[4].to_string(),
-                    full_hash,
-                ));
-            }
-        } else if !line.trim().is_empty() {
-            // Parse shortstat line: " 3 files changed, 10 insertions(+), 5 deletions(-)"
-            if current_entry.is_some() {
-                let mut insertions = 0u32;
-                let mut deletions = 0u32;
-                for part in line.split(',') {
-                    let part = part.trim();
-                    if part.contains("insertion") {
-                        if let Some(num) = part.split_whitespace().next() {
-                            insertions = num.parse::<u32>().unwrap_or(0);
-                        }
-                    } else if part.contains("deletion") {
-                        if let Some(num) = part.split_whitespace().next() {
-                            deletions = num.parse::<u32>().unwrap_or(0);
-                        }
-                    }
-                }
-                if let Some((hash, message, date, author, full_hash)) = current_entry.take() {
-                    let is_head = head_hash.as_ref().map(|h| h == &full_hash).unwrap_or(false);
-                    entries.push(GitLogEntry {
-                        hash,
-                        message,
-                        date,
-                        author,
-                        is_head,
-                        insertions,
-                        deletions,
-                    });
-                }
+    let entries = git::log(&repo, limit as usize)?
+        .into_iter()
+        .map(|commit| {
+            let (insertions, deletions) = git::commit_stats(&repo, &commit.full_hash);
+            GitLogEntry {
+                is_head: head_hash.as_ref() == Some(&commit.full_hash),
+                hash: commit.hash,
+                message: commit.message,
+                date: commit.date,
+                author: commit.author,
+                insertions,
+                deletions,
             }
-        }
-    }
-
-    // Don't forget the last entry if it had no stat line
-    if let Some((hash, message, date, author, full_hash)) = current_entry {
-        let is_head = head_hash.as_ref().map(|h| h == &full_hash).unwrap_or(false);
-        entries.push(GitLogEntry {
-            hash,
-            message,
-            date,
-            author,
-            is_head,
-            insertions: 0,
-            deletions: 0,
-        });
-    }
+        })
+        .collect();
 
     Ok(entries)
 }
 
+#[tauri::command]
+fn get_note_history(path: String, limit: Option<u32>) -> Result<Vec<git::FileRevision>, GitNotesError> {
+    let file_path = vault_path(&path)?;
+    let repo = git::open(&file_path)?;
+    let rel_path = git::relative_path(&repo, &file_path)?;
+    git::file_history(&repo, &rel_path, Some(limit.unwrap_or(100) as usize))
+}
+
+/// Content of a note as of `rev`, following renames back to the path it had then.
+fn note_content_at(path: &Path, rev: &str) -> Result<String, GitNotesError> {
+    let repo = git::open(path)?;
+    let rel_path = git::relative_path(&repo, path)?;
+    let path_at_rev = git::path_at(&repo, &rel_path, rev)?;
+    git::file_at(&repo, rev, &path_at_rev)
+}
+
+/// Who last changed each line of a note, as it is on disk now.
+#[tauri::command]
+fn get_note_blame(path: String) -> Result<Vec<git::BlameLine>, GitNotesError> {
+    let file_path = vault_path(&path)?;
+    let repo = git::open(&file_path)?;
+    let rel_path = git::relative_path(&repo, &file_path)?;
+    let content = fs::read_to_string(&file_path).map_err(|e| GitNotesError::io(&file_path, e))?;
+    git::blame(&repo, &rel_path, &content)
+}
+
+#[tauri::command]
+fn read_note_at(path: String, rev: String) -> Result<String, GitNotesError> {
+    note_content_at(&vault_path(&path)?, &rev)
+}
+
+#[tauri::command]
+fn restore_note(path: String, rev: String) -> Result<(), GitNotesError> {
+    let file_path = vault_path(&path)?;
+    let content = note_content_at(&file_path, &rev)?;
+    Ok(write_note_content(&file_path, &content)?)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RepoStats {
     pub total_commits: u32,
@@ -2114,8 +2586,9 @@ fn validate_active_vault() -> VaultValidation {
 }
 
 #[tauri::command]
-fn update_settings(settings: Settings) -> Result<(), String> {
-    save_settings(&settings)
+fn update_settings(settings: Settings, state: tauri::State<AppState>) -> Result<(), String> {
+    save_settings(&settings)?;
+    state.sync_active_vault()
 }
 
 #[tauri::command]
@@ -2192,59 +2665,16 @@ fn get_vault_stats(vault_id: String) -> Result<VaultStats, String> {
         }
     });
 
-    // Check git status - walk up to find .git directory
-    let git_path = {
-        let mut current: Option<&Path> = Some(&vault_path);
-        loop {
-            match current {
-                Some(dir) => {
-                    let candidate = dir.join(".git");
-                    if candidate.exists() {
-                        break Some(candidate);
-                    }
-                    current = dir.parent();
-                }
-                None => break None,
-            }
-        }
-    };
-    let is_git_repo = git_path.is_some();
-
-    let (git_branch, git_provider, git_repo) = if let Some(git_path) = git_path {
-        // Get branch
-        let head_path = git_path.join("HEAD");
-        let branch = fs::read_to_string(&head_path).ok().and_then(|content| {
-            content
-                .strip_prefix("ref: refs/heads/")
-                .map(|s| s.trim().to_string())
-        });
-
-        // Get remote URL from config
-        let config_path = git_path.join("config");
-        let (provider, repo) = fs::read_to_string(&config_path)
-            .ok()
-            .and_then(|content| {
-                // Find [remote "origin"] section and extract url
-                let mut in_origin = false;
-                for line in content.lines() {
-                    if line.trim() == "[remote \"origin\"]" {
-                        in_origin = true;
-                    } else if line.trim().starts_with('[') {
-                        in_origin = false;
-                    } else if in_origin && line.trim().starts_with("url = ") {
-                        let url = line.trim().strip_prefix("url = ")?;
-                        return parse_git_remote(url);
-                    }
-                }
-                None
-            })
-            .map(|(p, r)| (Some(p), Some(r)))
-            .unwrap_or((None, None));
-
-        (branch, provider, repo)
-    } else {
-        (None, None, None)
-    };
+    // Branch, remote and sync state of the repository the vault is in, if any
+    let repo = git::open(vault_path).ok();
+    let is_git_repo = repo.is_some();
+    let sync = repo.as_ref().map(git::sync_status).unwrap_or_default();
+    let git_branch = repo.as_ref().and_then(git::current_branch);
+    let (git_provider, git_repo) = repo
+        .and_then(|repo| git::remote_url(&repo, "origin"))
+        .and_then(|url| parse_git_remote(&url))
+        .map(|(p, r)| (Some(p), Some(r)))
+        .unwrap_or((None, None));
 
     Ok(VaultStats {
         vault_id,
@@ -2255,11 +2685,15 @@ fn get_vault_stats(vault_id: String) -> Result<VaultStats, String> {
         git_branch,
         git_provider,
         git_repo,
+        sync,
     })
 }
 
 #[tauri::command]
-async fn add_vault(app: tauri::AppHandle) -> Result<Option<Vault>, String> {
+async fn add_vault(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<Vault>, String> {
     use tauri_plugin_dialog::DialogExt;
 
     let folder = app.dialog().file().blocking_pick_folder();
@@ -2288,11 +2722,13 @@ async fn add_vault(app: tauri::AppHandle) -> Result<Option<Vault>, String> {
                 path: path_str,
                 is_team: None,
                 is_team_override: None,
+                pull_strategy: None,
             };
 
             let mut settings = load_settings();
             settings.vaults.push(vault.clone());
             save_settings(&settings)?;
+            state.sync_active_vault()?;
 
             Ok(Some(vault))
         }
@@ -2301,7 +2737,7 @@ async fn add_vault(app: tauri::AppHandle) -> Result<Option<Vault>, String> {
 }
 
 #[tauri::command]
-fn remove_vault(vault_id: String) -> Result<(), String> {
+fn remove_vault(vault_id: String, state: tauri::State<AppState>) -> Result<(), String> {
     let mut settings = load_settings();
     settings.vaults.retain(|v| v.id != vault_id);
 
@@ -2310,22 +2746,24 @@ fn remove_vault(vault_id: String) -> Result<(), String> {
         settings.active_vault = settings.vaults.first().map(|v| v.id.clone());
     }
 
-    save_settings(&settings)
+    save_settings(&settings)?;
+    state.sync_active_vault()
 }
 
 #[tauri::command]
-fn set_active_vault(vault_id: String) -> Result<(), String> {
+fn set_active_vault(vault_id: String, state: tauri::State<AppState>) -> Result<(), String> {
     let mut settings = load_settings();
     if settings.vaults.iter().any(|v| v.id == vault_id) {
         settings.active_vault = Some(vault_id);
-        save_settings(&settings)
+        save_settings(&settings)?;
+        state.sync_active_vault()
     } else {
         Err("Vault not found".to_string())
     }
 }
 
 #[tauri::command]
-fn add_existing_vault(path: String) -> Result<Vault, String> {
+fn add_existing_vault(path: String, state: tauri::State<AppState>) -> Result<Vault, String> {
     if !is_git_repository(&path) {
         return Err("Not a git repository".to_string());
     }
@@ -2342,11 +2780,14 @@ fn add_existing_vault(path: String) -> Result<Vault, String> {
         path,
         is_team: None,
         is_team_override: None,
+        pull_strategy: None,
     };
 
     let mut settings = load_settings();
     settings.vaults.push(vault.clone());
     save_settings(&settings)?;
+    // The first vault added becomes the active one
+    state.sync_active_vault()?;
 
     Ok(vault)
 }
@@ -2390,87 +2831,15 @@ fn set_editor_settings(settings: EditorSettings) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn get_repo_stats() -> Result<RepoStats, String> {
-    let notes_path = get_notes_path();
-
-    // Get total commit count
-    let count_output = Command::new("git")
-        .args(["rev-list", "--count", "HEAD"])
-        .current_dir(&notes_path)
-        .output();
-
-    let total_commits = count_output
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| {
-            String::from_utf8_lossy(&o.stdout)
-                .trim()
-                .parse::<u32>()
-                .unwrap_or(0)
-        })
-        .unwrap_or(0);
-
-    // Get first commit date (repo age) - find root commit first
-    let root_commit = Command::new("git")
-        .args(["rev-list", "--max-parents=0", "HEAD"])
-        .current_dir(&notes_path)
-        .output()
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| {
-            String::from_utf8_lossy(&o.stdout)
-                .lines()
-                .next()
-                .unwrap_or("")
-                .to_string()
-        })
-        .filter(|s| !s.is_empty());
-
-    let first_commit_date = root_commit.and_then(|hash| {
-        Command::new("git")
-            .args(["log", "-1", "--format=%aI", &hash])
-            .current_dir(&notes_path)
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .filter(|s| !s.is_empty())
-    });
-
-    // Get current branch
-    let branch_output = Command::new("git")
-        .args(["branch", "--show-current"])
-        .current_dir(&notes_path)
-        .output();
-
-    let current_branch = branch_output
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-        .filter(|s| !s.is_empty());
-
-    // Get branch count
-    let branches_output = Command::new("git")
-        .args(["branch", "--list"])
-        .current_dir(&notes_path)
-        .output();
-
-    let branch_count = branches_output
-        .ok()
-        .filter(|o| o.status.success())
-        .map(|o| {
-            String::from_utf8_lossy(&o.stdout)
-                .lines()
-                .filter(|l| !l.trim().is_empty())
-                .count() as u32
-        })
-        .unwrap_or(0);
+fn get_repo_stats() -> Result<RepoStats, GitNotesError> {
+    let repo = git::open(&get_notes_path())?;
 
     Ok(RepoStats {
-        total_commits,
-        first_commit_date,
-        current_branch,
-        branch_count,
+        total_commits: git::commit_count(&repo),
+        // Repo age
+        first_commit_date: git::first_commit(&repo).map(|c| c.date),
+        current_branch: git::current_branch(&repo),
+        branch_count: git::branch_count(&repo),
     })
 }
 
@@ -2506,17 +2875,10 @@ fn check_clone_path(url: String, path: String) -> Result<ClonePathStatus, String
     }
 
     // It's a git repo - check if same remote
-    let output = std::process::Command::new("git")
-        .args(["remote", "get-url", "origin"])
-        .current_dir(path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if !output.status.success() {
-        return Ok(ClonePathStatus::NotGit);
-    }
-
-    let existing_remote = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let existing_remote = match git::open(path).ok().and_then(|r| git::remote_url(&r, "origin")) {
+        Some(url) => url,
+        None => return Ok(ClonePathStatus::NotGit),
+    };
 
     // Normalize URLs for comparison (remove .git suffix, trailing slashes)
     let normalize = |u: &str| {
@@ -2534,23 +2896,19 @@ fn check_clone_path(url: String, path: String) -> Result<ClonePathStatus, String
 }
 
 #[tauri::command]
-async fn clone_vault(_app: tauri::AppHandle, url: String, path: String) -> Result<Vault, String> {
+async fn clone_vault(
+    _app: tauri::AppHandle,
+    url: String,
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vault, String> {
     // Create parent directory if needed
     let parent = std::path::Path::new(&path).parent();
     if let Some(parent) = parent {
         std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
 
-    // Run git clone
-    let output = std::process::Command::new("git")
-        .args(["clone", &url, &path])
-        .output()
-        .map_err(|e| format!("Failed to run git: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Clone failed: {}", stderr.trim()));
-    }
+    git::clone(&url, Path::new(&path)).map_err(|e| format!("Clone failed: {}", e))?;
 
     // Create vault
     let name = parse_repo_name(&url).unwrap_or_else(|| "notes".to_string());
@@ -2560,12 +2918,14 @@ async fn clone_vault(_app: tauri::AppHandle, url: String, path: String) -> Resul
         path: path.clone(),
         is_team: None,
         is_team_override: None,
+        pull_strategy: None,
     };
 
     // Save to settings
     let mut settings = load_settings();
     settings.vaults.push(vault.clone());
     save_settings(&settings)?;
+    state.sync_active_vault()?;
 
     Ok(vault)
 }
@@ -2579,7 +2939,11 @@ fn get_default_clone_path(url: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn create_vault(path: String, name: String) -> Result<Vault, String> {
+async fn create_vault(
+    path: String,
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vault, String> {
     let vault_path = PathBuf::from(&path);
 
     // Create directory if not exists
@@ -2591,17 +2955,7 @@ async fn create_vault(path: String, name: String) -> Result<Vault, String> {
     // Check if already a git repo
     let git_dir = vault_path.join(".git");
     if !git_dir.exists() {
-        // git init
-        let init_result = Command::new("git")
-            .args(["init"])
-            .current_dir(&vault_path)
-            .output()
-            .map_err(|e| format!("Failed to run git: {}", e))?;
-
-        if !init_result.status.success() {
-            let stderr = String::from_utf8_lossy(&init_result.stderr);
-            return Err(format!("git init failed: {}", stderr.trim()));
-        }
+        git::init(&vault_path).map_err(|e| format!("git init failed: {}", e))?;
 
         // Create initial .gitignore
         let gitignore_path = vault_path.join(".gitignore");
@@ -2617,6 +2971,7 @@ async fn create_vault(path: String, name: String) -> Result<Vault, String> {
         path,
         is_team: None,
         is_team_override: None,
+        pull_strategy: None,
     };
 
     // Add to settings
@@ -2624,6 +2979,7 @@ async fn create_vault(path: String, name: String) -> Result<Vault, String> {
     settings.vaults.push(vault.clone());
     settings.active_vault = Some(vault.id.clone());
     save_settings(&settings)?;
+    state.sync_active_vault()?;
 
     Ok(vault)
 }
@@ -2637,7 +2993,11 @@ fn get_default_vault_path(name: String) -> Result<String, String> {
 
 #[tauri::command]
 fn get_contact_data(note_path: String) -> Result<Option<ContactData>, String> {
-    let path = PathBuf::from(&note_path);
+    get_contact_data_in(&get_notes_path(), &note_path)
+}
+
+fn get_contact_data_in(notes_path: &Path, note_path: &str) -> Result<Option<ContactData>, String> {
+    let path = vault_path_in(notes_path, note_path)?;
 
     // Check if note is in a rolodex section by checking parent dir's .section.md
     let parent = path.parent().ok_or("No parent directory")?;
@@ -2649,16 +3009,16 @@ fn get_contact_data(note_path: String) -> Result<Option<ContactData>, String> {
     }
 
     let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let (fm, _) = parse_frontmatter(&content);
+    let (fm, _) = frontmatter::split(&content);
     let fm = match fm {
         Some(f) => f,
         None => return Ok(None),
     };
 
-    let other = &fm.other;
+    let other = fm.fields();
 
     // Helper to extract Vec<LabeledValue> from a YAML sequence of mappings
-    fn extract_labeled_values(other: &std::collections::HashMap<String, serde_yaml::Value>, key: &str) -> Vec<LabeledValue> {
+    fn extract_labeled_values(other: &serde_yaml::Mapping, key: &str) -> Vec<LabeledValue> {
         other.get(key)
             .and_then(|v| v.as_sequence())
             .map(|seq| {
@@ -2689,24 +3049,19 @@ fn get_contact_data(note_path: String) -> Result<Option<ContactData>, String> {
     Ok(Some(contact))
 }
 
+/// Entry point for the headless `gitnotes-cli` binary; returns the process exit code.
+pub fn run_cli() -> i32 {
+    cli::run(std::env::args().skip(1).collect())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let notes_path = get_notes_path();
-
-    // Initialize search index
-    let index_path = dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(get_config_dir_name())
-        .join("search-index");
-
-    let search_index = Arc::new(
-        SearchIndex::new(&index_path, &notes_path).expect("Failed to create search index"),
-    );
-
-    SearchIndex::start_watcher(Arc::clone(&search_index), notes_path.clone())
-        .expect("Failed to start file watcher");
-
-    let app_state = AppState { search_index };
+    // Initialize search index and watcher for the active vault (one persistent index per vault)
+    // If another instance holds the index, search stays unavailable until the vault is reopened
+    let app_state = AppState::new();
+    if let Err(e) = app_state.sync_active_vault() {
+        eprintln!("Failed to open search index: {}", e);
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -2714,7 +3069,9 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             use tauri::menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder};
-            use tauri::Emitter;
+            use tauri::{Emitter, Manager};
+
+            let _ = app.state::<AppState>().app_handle.set(app.handle().clone());
 
             let settings_item = MenuItemBuilder::new("Settings...")
                 .id("settings")
@@ -2780,14 +3137,31 @@ pub fn run() {
             list_whispers,
             get_git_info,
             git_commit,
+            git_commit_files,
             git_commit_and_push,
+            git_fetch,
+            sync_vault,
+            list_conflicts,
+            resolve_conflict,
+            abort_merge,
+            continue_rebase,
             search_notes,
+            list_tags,
+            notes_with_tag,
+            related_notes,
+            get_backlinks,
+            get_outgoing_links,
+            get_unresolved_links,
             get_repo_status,
             get_dirty_files,
             get_vault_path,
             get_file_diff,
             get_commit_diff,
             get_git_log,
+            get_note_history,
+            read_note_at,
+            get_note_blame,
+            restore_note,
             get_repo_stats,
             get_sort_preference,
             set_sort_preference,
@@ -2820,3 +3194,297 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use git::tests::{bare_remote, clone_of, commit, read};
+
+    fn sync(repo: &git2::Repository, use_merge: bool) -> SyncOutcome {
+        sync_repo(repo.workdir().unwrap(), use_merge, Some("Sync".to_string())).unwrap()
+    }
+
+    #[test]
+    fn sync_pushes_local_commits() {
+        let root = TempDir::new("lib-sync-push");
+        let bare = bare_remote(&root);
+        let repo = clone_of(&root, &bare, "repo");
+        assert!(matches!(sync(&repo, false), SyncOutcome::UpToDate));
+
+        fs::write(repo.workdir().unwrap().join("notes/b.md"), "new\n").unwrap();
+        assert!(matches!(sync(&repo, false), SyncOutcome::Pushed));
+        let remote = git2::Repository::open_bare(&bare).unwrap();
+        assert_eq!(
+            remote.refname_to_id("refs/heads/main").ok(),
+            repo.head().unwrap().target()
+        );
+        assert!(matches!(sync(&repo, false), SyncOutcome::UpToDate));
+    }
+
+    #[test]
+    fn sync_merges_or_rebases_a_diverged_upstream() {
+        for use_merge in [true, false] {
+            let root = TempDir::new(&format!("lib-sync-clean-{}", use_merge));
+            let bare = bare_remote(&root);
+            let theirs = clone_of(&root, &bare, "theirs");
+            let ours = clone_of(&root, &bare, "ours");
+            commit(&theirs, "notes/a.md", "ONE\ntwo\nthree\n", "Shout");
+            assert!(matches!(sync(&theirs, use_merge), SyncOutcome::Pushed));
+            commit(&ours, "notes/a.md", "one\ntwo\nTHREE\n", "Shout last");
+
+            assert!(matches!(sync(&ours, use_merge), SyncOutcome::Pulled));
+            assert_eq!(read(&ours, "notes/a.md"), "ONE\ntwo\nTHREE\n");
+            let head = ours.head().unwrap().peel_to_commit().unwrap();
+            assert_eq!(head.parent_count(), if use_merge { 2 } else { 1 });
+            let remote = git2::Repository::open_bare(&bare).unwrap();
+            assert_eq!(remote.refname_to_id("refs/heads/main").unwrap(), head.id());
+            assert!(git::pending_operation(&ours).is_none());
+        }
+    }
+
+    #[test]
+    fn sync_stops_on_conflicts_until_they_are_resolved() {
+        for use_merge in [true, false] {
+            let root = TempDir::new(&format!("lib-sync-conflict-{}", use_merge));
+            let bare = bare_remote(&root);
+            let theirs = clone_of(&root, &bare, "theirs");
+            let ours = clone_of(&root, &bare, "ours");
+            commit(&theirs, "notes/a.md", "ONE\ntwo\nthree\n", "Shout");
+            assert!(matches!(sync(&theirs, use_merge), SyncOutcome::Pushed));
+            let pushed = theirs.head().unwrap().target().unwrap();
+            commit(&ours, "notes/a.md", "uno\ntwo\nthree\n", "Translate");
+
+            for _ in 0..2 {
+                match sync(&ours, use_merge) {
+                    SyncOutcome::Conflicts(paths) => assert_eq!(paths, vec!["notes/a.md"]),
+                    other => panic!("expected conflicts, got {:?}", other),
+                }
+            }
+            let remote = git2::Repository::open_bare(&bare).unwrap();
+            assert_eq!(remote.refname_to_id("refs/heads/main").unwrap(), pushed);
+
+            fs::write(
+                ours.workdir().unwrap().join("notes/a.md"),
+                "UNO\ntwo\nthree\n",
+            )
+            .unwrap();
+            git::mark_resolved(&ours, "notes/a.md").unwrap();
+            assert!(matches!(
+                git::continue_operation(&ours).unwrap(),
+                Integration::Done
+            ));
+            assert!(matches!(sync(&ours, use_merge), SyncOutcome::Pushed));
+            assert_eq!(
+                remote.refname_to_id("refs/heads/main").ok(),
+                ours.head().unwrap().target()
+            );
+        }
+    }
+
+    #[test]
+    fn waits_for_another_process_to_let_go_of_the_index() {
+        let root = TempDir::new("lib-index-lock");
+        let (vault, index_path) = (root.join("vault"), root.join("index"));
+        fs::create_dir_all(&vault).unwrap();
+        let held = SearchIndex::new(&index_path, &vault).unwrap();
+
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            open_search_index(&index_path, &vault),
+            Err(GitNotesError::IndexLocked { .. })
+        ));
+        assert!(started.elapsed() >= INDEX_LOCK_RETRY_DELAY * (INDEX_LOCK_RETRIES - 1));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(INDEX_LOCK_RETRY_DELAY);
+            drop(held);
+        });
+        assert!(open_search_index(&index_path, &vault).is_ok());
+        release.join().unwrap();
+    }
+
+    const NOTE: &str = "---\ncreated: 2024-01-01T00:00:00\nmodified: 2024-01-01T00:00:00\n---\n\none\ntwo\nthree\n";
+
+    /// A note on disk as the editor last read it, and what was read.
+    fn opened_note(name: &str) -> (TempDir, AppState, PathBuf, NoteContent) {
+        let dir = TempDir::new(&format!("lib-{}", name));
+        let path = dir.join("note.md");
+        fs::write(&path, NOTE).unwrap();
+        let state = AppState::new();
+        let read = open_note(&state, &path).unwrap();
+        (dir, state, path, read)
+    }
+
+    /// Someone else's edit to the note's body, with a new `modified`.
+    fn edit_elsewhere(path: &Path, body: &str) -> String {
+        let theirs = NOTE
+            .replace(
+                "modified: 2024-01-01T00:00:00",
+                "modified: 2024-02-01T00:00:00",
+            )
+            .replace("one\ntwo\nthree\n", body);
+        fs::write(path, &theirs).unwrap();
+        theirs
+    }
+
+    fn conflict(outcome: WriteOutcome) -> (String, String, Option<String>, bool) {
+        match outcome {
+            WriteOutcome::Conflict {
+                ours,
+                theirs,
+                merged,
+                clean,
+                ..
+            } => (ours, theirs, merged, clean),
+            WriteOutcome::Saved { .. } => panic!("expected a conflict"),
+        }
+    }
+
+    #[test]
+    fn saves_only_over_the_version_that_was_read() {
+        let (_dir, state, path, read) = opened_note("note-version");
+        assert_eq!(read.content, NOTE);
+        assert_eq!(read.version, content_version(NOTE));
+
+        let ours = NOTE.replace("one", "ONE");
+        let version = match save_note(&state, &path, ours, Some(read.version.clone())).unwrap() {
+            WriteOutcome::Saved { version } => version,
+            other => panic!("expected a save, got {:?}", other),
+        };
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.ends_with("\n\nONE\ntwo\nthree\n"));
+        assert_eq!(version, content_version(&saved));
+
+        // The first read's version is stale now
+        let stale = NOTE.replace("two", "TWO");
+        let (ours, theirs, _, _) =
+            conflict(save_note(&state, &path, stale.clone(), Some(read.version)).unwrap());
+        assert_eq!((ours, theirs), (stale, saved.clone()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+    }
+
+    #[test]
+    fn does_not_recreate_a_note_deleted_since_it_was_read() {
+        let (_dir, state, path, read) = opened_note("note-deleted");
+        fs::remove_file(&path).unwrap();
+
+        let ours = NOTE.replace("one", "ONE");
+        assert!(matches!(
+            save_note(&state, &path, ours.clone(), Some(read.version)),
+            Err(GitNotesError::NotFound { .. })
+        ));
+        assert!(!path.exists());
+        // Without a version to check against, saving writes it again
+        save_note(&state, &path, ours, None).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("\n\nONE\ntwo\nthree\n"));
+    }
+
+    #[test]
+    fn merges_edits_to_different_lines() {
+        let (_dir, state, path, read) = opened_note("note-merge-clean");
+        let theirs = edit_elsewhere(&path, "one\ntwo\nTHREE\n");
+
+        let ours = NOTE.replace("one", "ONE");
+        let (_, on_disk, merged, clean) =
+            conflict(save_note(&state, &path, ours, Some(read.version)).unwrap());
+        assert!(clean);
+        assert_eq!(on_disk, theirs);
+        // Their frontmatter, both bodies
+        assert_eq!(
+            merged.as_deref(),
+            Some(theirs.replace("one", "ONE").as_str())
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), theirs);
+    }
+
+    #[test]
+    fn marks_edits_to_the_same_line() {
+        let (_dir, state, path, read) = opened_note("note-merge-conflict");
+        edit_elsewhere(&path, "uno\ntwo\nthree\n");
+
+        let ours = NOTE.replace("one", "ONE");
+        let (_, _, merged, clean) =
+            conflict(save_note(&state, &path, ours.clone(), Some(read.version)).unwrap());
+        assert!(!clean);
+        let merged = merged.unwrap();
+        assert!(merged.starts_with(
+            "---\ncreated: 2024-01-01T00:00:00\nmodified: 2024-02-01T00:00:00\n---\n\n"
+        ));
+        assert!(merged.contains("<<<<<<< yours\nONE\n=======\nuno\n>>>>>>> on disk\n"));
+
+        // Without the base that was read, there's nothing to merge against
+        let (_, _, merged, clean) = conflict(
+            save_note(&AppState::new(), &path, ours, Some(content_version(NOTE))).unwrap(),
+        );
+        assert_eq!((merged, clean), (None, false));
+    }
+
+    fn settings_named(name: &str) -> Settings {
+        serde_json::from_value(serde_json::json!({
+            "vaults": [{ "id": name, "name": name, "path": "/notes" }],
+        }))
+        .unwrap()
+    }
+
+    fn vault_name(settings: Option<Settings>) -> Option<String> {
+        settings.map(|s| s.vaults[0].name.clone())
+    }
+
+    #[test]
+    fn keeps_the_last_good_settings_as_backups() {
+        let dir = TempDir::new("lib-settings-backups");
+        let path = dir.join("settings.json");
+        for name in ["a", "b", "c", "d", "e"] {
+            write_settings(&path, &settings_named(name)).unwrap();
+        }
+        assert_eq!(vault_name(read_settings(&path)).as_deref(), Some("e"));
+        let backups: Vec<_> = (1..=SETTINGS_BACKUPS + 1)
+            .map(|n| vault_name(read_settings(&settings_backup_path(&path, n))))
+            .collect();
+        assert_eq!(
+            backups,
+            [Some("d".into()), Some("c".into()), Some("b".into()), None]
+        );
+
+        // A damaged file is replaced, but never rotated into the backups
+        fs::write(&path, "{\"vaults\": [").unwrap();
+        write_settings(&path, &settings_named("f")).unwrap();
+        assert_eq!(
+            vault_name(read_settings(&settings_backup_path(&path, 1))).as_deref(),
+            Some("d")
+        );
+    }
+
+    #[test]
+    fn restores_the_newest_backup_that_parses() {
+        let dir = TempDir::new("lib-settings-restore");
+        let path = dir.join("settings.json");
+        for name in ["a", "b", "c"] {
+            write_settings(&path, &settings_named(name)).unwrap();
+        }
+        // settings.json is "c", bak1 "b" and bak2 "a"; break the file and the newest backup
+        fs::write(&path, "{\"vaults\": [").unwrap();
+        fs::write(settings_backup_path(&path, 1), "").unwrap();
+
+        assert!(read_settings(&path).is_none());
+        assert_eq!(
+            vault_name(restore_settings_backup(&path)).as_deref(),
+            Some("a")
+        );
+        assert_eq!(vault_name(read_settings(&path)).as_deref(), Some("a"));
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.corrupt")).unwrap(),
+            "{\"vaults\": ["
+        );
+
+        // Nothing to fall back on
+        let dir = TempDir::new("lib-settings-no-backup");
+        let path = dir.join("settings.json");
+        fs::write(&path, "not json").unwrap();
+        assert!(restore_settings_backup(&path).is_none());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not json");
+    }
+}
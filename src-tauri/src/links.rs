@@ -0,0 +1,675 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `[[Note Name]]`, optionally with `|alias` or `#heading`
+    Wiki,
+    /// `[text](relative/path.md)`
+    Markdown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NoteLink {
+    /// Note containing the link
+    pub source: String,
+    /// Note the link points to, if it exists in the vault
+    pub target: Option<String>,
+    /// Link target as written in the source note
+    pub text: String,
+    pub kind: LinkKind,
+    /// Zero-based line of the link in the source note
+    pub line: usize,
+}
+
+/// A link as parsed from a note, before resolving it against the vault.
+#[derive(Debug, Clone)]
+struct RawLink {
+    text: String,
+    kind: LinkKind,
+    line: usize,
+    // Byte range of the link target in the note (without alias, heading or <>)
+    span: Range<usize>,
+}
+
+#[derive(Default)]
+struct LinkGraph {
+    outgoing: HashMap<PathBuf, Vec<RawLink>>,
+    // Lowercased note name -> notes with that name, for resolving [[Name]]
+    by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+/// In-memory graph of wiki and markdown links between the notes of one vault.
+/// Links are stored as written and resolved at query time, so adding or
+/// removing a note immediately changes which links resolve.
+pub struct LinkIndex {
+    notes_root: PathBuf,
+    graph: RwLock<LinkGraph>,
+}
+
+impl LinkIndex {
+    pub fn new(notes_path: &Path) -> Self {
+        let index = LinkIndex {
+            notes_root: notes_path.to_path_buf(),
+            graph: RwLock::new(LinkGraph::default()),
+        };
+
+        let mut files = Vec::new();
+        collect_markdown_files(notes_path, &mut files);
+        for path in files {
+            index.index_file(&path);
+        }
+
+        index
+    }
+
+    pub fn index_file(&self, path: &Path) {
+        // Whispers and other dot-folders are not part of the note graph
+        let hidden = path
+            .strip_prefix(&self.notes_root)
+            .map(|rel| {
+                rel.components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            })
+            .unwrap_or(true);
+        if hidden {
+            return;
+        }
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+        let links = extract_links(&content);
+
+        if let Ok(mut graph) = self.graph.write() {
+            graph.remove(path);
+            graph
+                .by_name
+                .entry(note_name(path))
+                .or_default()
+                .push(path.to_path_buf());
+            graph.outgoing.insert(path.to_path_buf(), links);
+        }
+    }
+
+    pub fn remove_file(&self, path: &Path) {
+        if let Ok(mut graph) = self.graph.write() {
+            graph.remove(path);
+        }
+    }
+
+    /// Re-read every note under `dir` after the folder was added, removed or renamed.
+    pub fn sync_dir(&self, dir: &Path) {
+        if let Ok(mut graph) = self.graph.write() {
+            let stale: Vec<PathBuf> = graph
+                .outgoing
+                .keys()
+                .filter(|p| p.starts_with(dir))
+                .cloned()
+                .collect();
+            for path in stale {
+                graph.remove(&path);
+            }
+        }
+        let mut files = Vec::new();
+        collect_markdown_files(dir, &mut files);
+        for path in files {
+            self.index_file(&path);
+        }
+    }
+
+    pub fn outgoing_links(&self, path: &Path) -> Vec<NoteLink> {
+        let graph = match self.graph.read() {
+            Ok(g) => g,
+            Err(_) => return vec![],
+        };
+        graph
+            .outgoing
+            .get(path)
+            .map(|links| {
+                links
+                    .iter()
+                    .map(|link| self.resolve(&graph, path, link))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn backlinks(&self, path: &Path) -> Vec<NoteLink> {
+        let target = path.to_string_lossy();
+        self.all_links()
+            .into_iter()
+            .filter(|link| link.target.as_deref() == Some(target.as_ref()))
+            .collect()
+    }
+
+    pub fn unresolved_links(&self) -> Vec<NoteLink> {
+        self.all_links()
+            .into_iter()
+            .filter(|link| link.target.is_none())
+            .collect()
+    }
+
+    fn all_links(&self) -> Vec<NoteLink> {
+        let graph = match self.graph.read() {
+            Ok(g) => g,
+            Err(_) => return vec![],
+        };
+        let mut sources: Vec<&PathBuf> = graph.outgoing.keys().collect();
+        sources.sort();
+
+        sources
+            .into_iter()
+            .flat_map(|source| {
+                graph.outgoing[source]
+                    .iter()
+                    .map(|link| self.resolve(&graph, source, link))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn resolve(&self, graph: &LinkGraph, source: &Path, link: &RawLink) -> NoteLink {
+        let target = match link.kind {
+            LinkKind::Wiki => self.resolve_wiki(graph, source, &link.text),
+            LinkKind::Markdown => resolve_relative(source, &link.text)
+                .filter(|p| graph.outgoing.contains_key(p)),
+        };
+
+        NoteLink {
+            source: source.to_string_lossy().to_string(),
+            target: target.map(|p| p.to_string_lossy().to_string()),
+            text: link.text.clone(),
+            kind: link.kind,
+            line: link.line,
+        }
+    }
+
+    /// Work out which notes need their links rewritten when `old` is renamed or
+    /// moved to `new`. Returns the new content of each affected note, keyed by
+    /// the path the note will have once the move is done.
+    pub fn plan_move(&self, old: &Path, new: &Path) -> Vec<(PathBuf, String)> {
+        let graph = match self.graph.read() {
+            Ok(g) => g,
+            Err(_) => return vec![],
+        };
+
+        let mut rewrites = Vec::new();
+        for source in graph.outgoing.keys() {
+            let moving_self = source.as_path() == old;
+            // Only re-read notes that actually point at the moved note (or are the moved note)
+            let affected = moving_self
+                || graph.outgoing[source]
+                    .iter()
+                    .any(|link| self.resolve(&graph, source, link).target.as_deref()
+                        == Some(old.to_string_lossy().as_ref()));
+            if !affected {
+                continue;
+            }
+
+            let content = match std::fs::read_to_string(source) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let source_after = if moving_self { new } else { source.as_path() };
+
+            let mut replacements = Vec::new();
+            for link in extract_links(&content) {
+                let target = self.resolve(&graph, source, &link).target.map(PathBuf::from);
+                let target_after = match target {
+                    Some(t) if t == old => new.to_path_buf(),
+                    // The moved note's own relative links need rebasing to its new folder,
+                    // including links to notes that don't exist (yet)
+                    _ if moving_self && link.kind == LinkKind::Markdown => {
+                        match resolve_relative(source, &link.text) {
+                            Some(t) => t,
+                            None => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+
+                let replacement = match link.kind {
+                    LinkKind::Wiki => self.wiki_text(&link.text, &target_after),
+                    LinkKind::Markdown => {
+                        markdown_text(&content, &link, source_after, &target_after)
+                    }
+                };
+                if let Some(text) = replacement.filter(|t| *t != content[link.span.clone()]) {
+                    replacements.push((link.span, text));
+                }
+            }
+
+            if replacements.is_empty() {
+                continue;
+            }
+
+            // Apply back to front so earlier spans stay valid
+            let mut updated = content.clone();
+            for (span, text) in replacements.into_iter().rev() {
+                updated.replace_range(span, &text);
+            }
+            rewrites.push((source_after.to_path_buf(), updated));
+        }
+
+        rewrites
+    }
+
+    /// New text for a wiki link pointing at `target`, in the same style as `text`.
+    fn wiki_text(&self, text: &str, target: &Path) -> Option<String> {
+        if text.contains('/') {
+            // [[section/Note]] stays a vault-relative path
+            let rel = target.strip_prefix(&self.notes_root).ok()?.with_extension("");
+            let parts: Vec<String> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            Some(parts.join("/"))
+        } else {
+            Some(target.file_stem()?.to_string_lossy().to_string())
+        }
+    }
+
+    fn resolve_wiki(&self, graph: &LinkGraph, source: &Path, text: &str) -> Option<PathBuf> {
+        // [[section/Note]] is a path from the vault root
+        if text.contains('/') {
+            let path = normalize(&self.notes_root.join(format!("{}.md", text)));
+            return graph.outgoing.contains_key(&path).then_some(path);
+        }
+
+        let candidates = graph.by_name.get(&text.to_lowercase())?;
+        // Prefer a note next to the source, then the first one by path
+        let source_dir = source.parent();
+        candidates
+            .iter()
+            .find(|p| p.parent() == source_dir)
+            .or_else(|| candidates.iter().min())
+            .cloned()
+    }
+}
+
+impl LinkGraph {
+    fn remove(&mut self, path: &Path) {
+        self.outgoing.remove(path);
+        let name = note_name(path);
+        if let Some(paths) = self.by_name.get_mut(&name) {
+            paths.retain(|p| p != path);
+            if paths.is_empty() {
+                self.by_name.remove(&name);
+            }
+        }
+    }
+}
+
+fn note_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() {
+            if !hidden {
+                collect_markdown_files(&path, files);
+            }
+        } else if !hidden && path.extension().map(|e| e == "md").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+}
+
+/// Resolve a markdown link target relative to the note containing it.
+/// Returns None for external URLs and non-markdown targets.
+fn resolve_relative(source: &Path, target: &str) -> Option<PathBuf> {
+    if target.contains("://") || target.starts_with("mailto:") {
+        return None;
+    }
+    let target = target.split('#').next().unwrap_or(target);
+    if !target.ends_with(".md") {
+        return None;
+    }
+    let target = percent_decode(target);
+    Some(normalize(&source.parent()?.join(target)))
+}
+
+/// New text for a markdown link from `source` to `target`, keeping any `#anchor`.
+fn markdown_text(content: &str, link: &RawLink, source: &Path, target: &Path) -> Option<String> {
+    let rel = relative_path(source.parent()?, target);
+    let mut text = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    // <path with spaces.md> can keep its spaces, bare links need them escaped
+    let angled = link.span.start > 0 && content.as_bytes()[link.span.start - 1] == b'<';
+    if !angled {
+        text = text.replace(' ', "%20");
+    }
+    if let Some(anchor) = link.text.find('#').map(|i| &link.text[i..]) {
+        text.push_str(anchor);
+    }
+    Some(text)
+}
+
+/// Path to `target` relative to the directory `from`, using `..` where needed.
+fn relative_path(from: &Path, target: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = target.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut result = PathBuf::new();
+    for _ in common..from.len() {
+        result.push("..");
+    }
+    for component in &to[common..] {
+        result.push(component.as_os_str());
+    }
+    result
+}
+
+/// Lexically resolve `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Byte range of `part`, which must be a slice of `content`.
+fn span_of(content: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - content.as_ptr() as usize;
+    start..start + part.len()
+}
+
+/// Find wiki links and relative markdown links, skipping fenced and inline code.
+fn extract_links(content: &str) -> Vec<RawLink> {
+    let mut links = Vec::new();
+    let mut in_fence = false;
+
+    for (line_num, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            // Skip inline code spans
+            if let Some(after) = rest.strip_prefix('`') {
+                rest = match after.find('`') {
+                    Some(end) => &after[end + 1..],
+                    None => "",
+                };
+                continue;
+            }
+
+            if let Some(after) = rest.strip_prefix("[[") {
+                if let Some(end) = after.find("]]") {
+                    let inner = &after[..end];
+                    // Drop |alias and #heading
+                    let name = inner.split('|').next().unwrap_or(inner);
+                    let name = name.split('#').next().unwrap_or(name).trim();
+                    if !name.is_empty() {
+                        links.push(RawLink {
+                            text: name.to_string(),
+                            kind: LinkKind::Wiki,
+                            line: line_num,
+                            span: span_of(content, name),
+                        });
+                    }
+                    rest = &after[end + 2..];
+                    continue;
+                }
+            }
+
+            if let Some(after) = rest.strip_prefix("](") {
+                let (target, consumed) = match after.strip_prefix('<') {
+                    Some(angled) => match angled.find('>') {
+                        Some(end) => (&angled[..end], end + 2),
+                        None => ("", 0),
+                    },
+                    None => {
+                        let end = after
+                            .find(|c: char| c == ')' || c.is_whitespace())
+                            .unwrap_or(after.len());
+                        (&after[..end], end)
+                    }
+                };
+                if target.split('#').next().unwrap_or("").ends_with(".md")
+                    && !target.contains("://")
+                {
+                    links.push(RawLink {
+                        text: target.to_string(),
+                        kind: LinkKind::Markdown,
+                        line: line_num,
+                        span: span_of(content, target),
+                    });
+                }
+                rest = &after[consumed..];
+                continue;
+            }
+
+            let next = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+            rest = &rest[next..];
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    /// A vault with the given notes, and a link index over it.
+    fn fixture(name: &str, notes: &[(&str, &str)]) -> (LinkIndex, TempDir) {
+        let vault = TempDir::new(&format!("links-{}", name));
+        for (file, content) in notes {
+            let path = vault.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        (LinkIndex::new(&vault), vault)
+    }
+
+    fn texts(links: &[NoteLink]) -> Vec<&str> {
+        links.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn extracts_wiki_and_markdown_links_outside_code() {
+        let content = "\
+See [[Ada Lovelace|Ada]] and [[ Plans#Q3 ]].
+[notes](../work/Meeting%20notes.md#agenda) and [spaced](<my notes/x.md>)
+[site](https://example.com/a.md) [pic](img.png) `[[not a link]]`
+```
+[[fenced]] [x](fenced.md)
+```
+[[]] [[Last]]";
+        let links = extract_links(content);
+        let found: Vec<(&str, LinkKind, usize)> = links
+            .iter()
+            .map(|l| (l.text.as_str(), l.kind, l.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Ada Lovelace", LinkKind::Wiki, 0),
+                ("Plans", LinkKind::Wiki, 0),
+                ("../work/Meeting%20notes.md#agenda", LinkKind::Markdown, 1),
+                ("my notes/x.md", LinkKind::Markdown, 1),
+                ("Last", LinkKind::Wiki, 6),
+            ]
+        );
+        for link in &links {
+            assert_eq!(&content[link.span.clone()], link.text);
+        }
+    }
+
+    #[test]
+    fn decodes_percent_escapes_up_to_the_last_byte() {
+        assert_eq!(percent_decode("Meeting%20notes.md"), "Meeting notes.md");
+        assert_eq!(percent_decode("x%41"), "xA");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4"), "%4");
+        assert_eq!(percent_decode("%zz.md"), "%zz.md");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+    }
+
+    #[test]
+    fn resolves_links_by_name_path_and_relative_path() {
+        let (index, vault) = fixture(
+            "resolve",
+            &[
+                ("work/Index.md", "[[Plans]] [[home/Plans]] [[Nowhere]]\n[m](Meeting%20notes.md) [x](../home/Plans.md#top)\n"),
+                ("work/Plans.md", "work plans\n"),
+                ("work/Meeting notes.md", "[[index]]\n"),
+                ("home/Plans.md", "home plans\n"),
+                ("home/Other.md", "[[Plans]]\n"),
+                (".whispers/Index.md", "[[Plans]]\n"),
+            ],
+        );
+        let path = |rel: &str| vault.join(rel).to_string_lossy().to_string();
+
+        let links = index.outgoing_links(&vault.join("work/Index.md"));
+        let targets: Vec<Option<String>> = links.iter().map(|l| l.target.clone()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                // A note in the same folder wins over one elsewhere
+                Some(path("work/Plans.md")),
+                Some(path("home/Plans.md")),
+                None,
+                Some(path("work/Meeting notes.md")),
+                Some(path("home/Plans.md")),
+            ]
+        );
+        assert_eq!(links[3].line, 1);
+
+        let backlinks = index.backlinks(&vault.join("home/Plans.md"));
+        let sources: Vec<&str> = backlinks.iter().map(|l| l.source.as_str()).collect();
+        assert_eq!(
+            sources,
+            vec![
+                path("home/Other.md"),
+                path("work/Index.md"),
+                path("work/Index.md")
+            ]
+        );
+        assert_eq!(texts(&index.unresolved_links()), vec!["Nowhere"]);
+
+        // Removing a note unresolves links to it
+        index.remove_file(&vault.join("work/Plans.md"));
+        let links = index.outgoing_links(&vault.join("work/Index.md"));
+        assert_eq!(links[0].target, Some(path("home/Plans.md")));
+    }
+
+    #[test]
+    fn plans_rewrites_of_only_the_link_targets() {
+        let (index, vault) = fixture(
+            "move",
+            &[
+                ("work/Plans.md", "# Plans\n"),
+                (
+                    "work/Index.md",
+                    "[[Plans|the plans]] and [[Plans#Q3]], [[work/Plans]]\n[p](Plans.md#q3) [[Other]]\n",
+                ),
+                ("home/Note.md", "[up](<../work/Plans.md>)\n`[[Plans]]`\n"),
+                ("home/Other.md", "nothing\n"),
+            ],
+        );
+        let old = vault.join("work/Plans.md");
+        let new = vault.join("archive/Old plans.md");
+
+        let mut rewrites = index.plan_move(&old, &new);
+        rewrites.sort();
+        assert_eq!(
+            rewrites,
+            vec![
+                (
+                    vault.join("home/Note.md"),
+                    "[up](<../archive/Old plans.md>)\n`[[Plans]]`\n".to_string()
+                ),
+                (
+                    vault.join("work/Index.md"),
+                    "[[Old plans|the plans]] and [[Old plans#Q3]], [[archive/Old plans]]\n\
+                     [p](../archive/Old%20plans.md#q3) [[Other]]\n"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn moving_a_note_rebases_its_own_relative_links() {
+        let (index, vault) = fixture(
+            "move-self",
+            &[
+                (
+                    "work/Plans.md",
+                    "[[Index]] [idx](Index.md) [todo](drafts/To%20do.md#next) \
+                     [self](Plans.md) [web](https://example.com/x.md)\n",
+                ),
+                ("work/Index.md", "index\n"),
+            ],
+        );
+        let old = vault.join("work/Plans.md");
+        let new = vault.join("home/later/Plans.md");
+
+        let rewrites = index.plan_move(&old, &new);
+        assert_eq!(
+            rewrites,
+            vec![(
+                new.clone(),
+                "[[Index]] [idx](../../work/Index.md) [todo](../../work/drafts/To%20do.md#next) \
+                 [self](Plans.md) [web](https://example.com/x.md)\n"
+                    .to_string()
+            )]
+        );
+    }
+}
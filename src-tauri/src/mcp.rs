@@ -0,0 +1,715 @@
+// Model Context Protocol server, so local agents can read and write a brain with the same
+// rules as the app. Messages are newline-delimited JSON-RPC 2.0, over stdio or a Unix socket.
+// Besides the MCP methods, each tool can also be called directly as a plain JSON-RPC method.
+
+use crate::paths;
+use crate::search::SearchIndex;
+use crate::{
+    get_contact_data_in, list_notes_in, list_sections_in, load_section_metadata,
+    write_note_content, SearchSettings,
+};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const INSTRUCTIONS_URI_PREFIX: &str = "gitnotes://sections/";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub struct McpServer {
+    notes_path: PathBuf,
+    search_index: SearchIndex,
+    search_settings: SearchSettings,
+}
+
+impl McpServer {
+    pub fn new(
+        notes_path: PathBuf,
+        search_index: SearchIndex,
+        search_settings: SearchSettings,
+    ) -> Self {
+        McpServer {
+            notes_path,
+            search_index,
+            search_settings,
+        }
+    }
+
+    /// Serve requests from stdin until it closes.
+    pub fn serve_stdio(&self) -> Result<(), String> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.serve(stdin.lock(), stdout.lock())
+    }
+
+    /// Listen on a Unix socket, serving each connection on its own thread.
+    #[cfg(unix)]
+    pub fn serve_socket(self, socket_path: &Path) -> Result<(), String> {
+        use std::os::unix::net::UnixListener;
+        use std::sync::Arc;
+
+        // A socket file left behind by a previous run would make bind fail, but anything else
+        // at that path is not ours to delete
+        if let Ok(metadata) = socket_path.symlink_metadata() {
+            use std::os::unix::fs::FileTypeExt;
+            if !metadata.file_type().is_socket() {
+                return Err(format!(
+                    "{} already exists and is not a socket",
+                    socket_path.display()
+                ));
+            }
+            std::fs::remove_file(socket_path).map_err(|e| e.to_string())?;
+        }
+        let listener = UnixListener::bind(socket_path).map_err(|e| e.to_string())?;
+        let server = Arc::new(self);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("MCP connection failed: {}", e);
+                    continue;
+                }
+            };
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                let reader = match stream.try_clone() {
+                    Ok(r) => r,
+                    Err(e) => return eprintln!("MCP connection failed: {}", e),
+                };
+                if let Err(e) = server.serve(reader, stream) {
+                    eprintln!("MCP connection closed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn serve<R: std::io::Read, W: Write>(&self, reader: R, mut writer: W) -> Result<(), String> {
+        for line in BufReader::new(reader).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                // One write per message, so socket clients never see half a line
+                let line = format!("{}\n", response);
+                writer
+                    .write_all(line.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                writer.flush().map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message. Notifications get no response.
+    pub fn handle_message(&self, message: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(v) => v,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+
+        let id = request.get("id").cloned();
+        let method = match request.get("method").and_then(|m| m.as_str()) {
+            Some(m) => m,
+            None => {
+                return Some(error_response(
+                    id.unwrap_or(Value::Null),
+                    INVALID_REQUEST,
+                    "Missing method",
+                ))
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(json!({}));
+
+        let result = self.dispatch(method, &params);
+        // No id means a notification, e.g. notifications/initialized
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e.code, &e.message),
+        })
+    }
+
+    fn dispatch(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {}, "resources": {} },
+                "serverInfo": { "name": "gitnotes", "version": env!("CARGO_PKG_VERSION") },
+                "instructions": self.instructions(),
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                // Tool failures are results the agent can read, not protocol errors
+                Ok(match self.call_tool(name, &arguments) {
+                    Ok(value) => json!({
+                        "content": [{ "type": "text", "text": tool_text(&value) }],
+                        "isError": false,
+                    }),
+                    Err(e) => json!({
+                        "content": [{ "type": "text", "text": e.message }],
+                        "isError": true,
+                    }),
+                })
+            }
+            "resources/list" => Ok(json!({ "resources": self.instruction_resources() })),
+            "resources/read" => {
+                let uri = params
+                    .get("uri")
+                    .and_then(|u| u.as_str())
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing uri"))?;
+                self.read_resource(uri)
+            }
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            // Plain JSON-RPC: call a tool by name with the params as its arguments
+            method => self.call_tool(method, params),
+        }
+    }
+
+    fn call_tool(&self, name: &str, args: &Value) -> Result<Value, RpcError> {
+        let arg = |key: &str| -> Result<&str, RpcError> {
+            args.get(key)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Missing argument: {}", key)))
+        };
+        // The request was fine but reading or writing the vault wasn't
+        let failed = |e: String| RpcError::new(INTERNAL_ERROR, e);
+
+        match name {
+            "list_sections" => {
+                let sections = list_sections_in(&self.notes_path).map_err(failed)?;
+                Ok(Value::Array(
+                    sections
+                        .into_iter()
+                        .map(|section| {
+                            let metadata = load_section_metadata(&PathBuf::from(&section.path));
+                            json!({
+                                "name": section.name,
+                                "title": section.title,
+                                "type": section.section_type,
+                                "agent_instructions": metadata.agent_instructions,
+                            })
+                        })
+                        .collect(),
+                ))
+            }
+            "list_notes" => {
+                let section = arg("section")?;
+                let metadata = load_section_metadata(&self.resolve(section)?);
+                let notes = list_notes_in(&self.notes_path, section).map_err(failed)?;
+                Ok(json!({
+                    "agent_instructions": metadata.agent_instructions,
+                    "notes": notes
+                        .into_iter()
+                        .map(|note| json!({
+                            "name": note.name,
+                            "path": self.relative(Path::new(&note.path)),
+                            "created": note.created,
+                            "modified": note.modified,
+                            "subfolder": note.subfolder,
+                        }))
+                        .collect::<Vec<_>>(),
+                }))
+            }
+            "read_note" => {
+                let path = self.resolve(arg("path")?)?;
+                std::fs::read_to_string(&path)
+                    .map(Value::String)
+                    .map_err(|e| failed(e.to_string()))
+            }
+            "write_note" => {
+                let path = self.resolve(arg("path")?)?;
+                let content = arg("content")?;
+                if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                    return Err(RpcError::new(INVALID_PARAMS, "Notes must be .md files"));
+                }
+                if !path.parent().is_some_and(|p| p.is_dir()) {
+                    return Err(RpcError::new(INVALID_PARAMS, "Section not found"));
+                }
+                write_note_content(&path, content).map_err(failed)?;
+                Ok(json!({ "path": self.relative(&path) }))
+            }
+            "search_notes" => {
+                let query = arg("query")?;
+                let limit = args.get("limit").and_then(|l| l.as_u64()).unwrap_or(20) as usize;
+                // The vault may have changed since the last call; this only re-reads what did
+                self.search_index
+                    .sync_files(&self.notes_path)
+                    .map_err(|e| failed(e.into()))?;
+                let results = self
+                    .search_index
+                    .search(query, limit, &self.search_settings)
+                    .map_err(|e| failed(e.into()))?;
+                Ok(Value::Array(
+                    results
+                        .into_iter()
+                        .map(|r| {
+                            json!({
+                                "path": self.relative(Path::new(&r.path)),
+                                "section": r.section,
+                                "snippet": r.snippet,
+                                "line": r.match_line,
+                            })
+                        })
+                        .collect(),
+                ))
+            }
+            "get_contact_data" => {
+                let path = arg("path")?;
+                // A path outside the vault is a bad request, not a failed lookup
+                self.resolve(path)?;
+                let contact = get_contact_data_in(&self.notes_path, path).map_err(failed)?;
+                serde_json::to_value(contact).map_err(|e| failed(e.to_string()))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", name),
+            )),
+        }
+    }
+
+    /// A vault-relative path (or an absolute one inside the vault) as an absolute path.
+    fn resolve(&self, path: &str) -> Result<PathBuf, RpcError> {
+        paths::resolve_in_vault(&self.notes_path, path)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.notes_path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Every section's agent instructions, for the client to use as context.
+    fn instructions(&self) -> String {
+        let mut text = String::from(
+            "This is a GitNotes brain: markdown notes grouped into sections (top-level folders). \
+             Paths are relative to the vault root.",
+        );
+        for section in list_sections_in(&self.notes_path).unwrap_or_default() {
+            let metadata = load_section_metadata(&PathBuf::from(&section.path));
+            if let Some(instructions) = metadata.agent_instructions {
+                text.push_str(&format!(
+                    "\n\n## Section {}\n{}",
+                    section.name,
+                    instructions.trim()
+                ));
+            }
+        }
+        text
+    }
+
+    fn instruction_resources(&self) -> Vec<Value> {
+        list_sections_in(&self.notes_path)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|section| {
+                load_section_metadata(&PathBuf::from(&section.path))
+                    .agent_instructions
+                    .is_some()
+            })
+            .map(|section| {
+                json!({
+                    "uri": format!("{}{}/instructions", INSTRUCTIONS_URI_PREFIX, section.name),
+                    "name": format!("{} instructions", section.title.as_deref().unwrap_or(&section.name)),
+                    "description": format!("How agents should work with notes in {}", section.name),
+                    "mimeType": "text/markdown",
+                })
+            })
+            .collect()
+    }
+
+    fn read_resource(&self, uri: &str) -> Result<Value, RpcError> {
+        let section = uri
+            .strip_prefix(INSTRUCTIONS_URI_PREFIX)
+            .and_then(|rest| rest.strip_suffix("/instructions"))
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown resource: {}", uri)))?;
+        let section_path = self.resolve(section)?;
+        let instructions = load_section_metadata(&section_path)
+            .agent_instructions
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown resource: {}", uri)))?;
+        Ok(json!({
+            "contents": [{ "uri": uri, "mimeType": "text/markdown", "text": instructions }]
+        }))
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+// Note content goes back as-is; structured results as pretty JSON
+fn tool_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+fn tool_definitions() -> Value {
+    let path = json!({ "type": "string", "description": "Note path relative to the vault root" });
+    json!([
+        {
+            "name": "list_sections",
+            "description": "List the vault's sections with their agent instructions",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+        {
+            "name": "list_notes",
+            "description": "List the notes in a section, in the section's sort order",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "section": { "type": "string", "description": "Section folder name" } },
+                "required": ["section"],
+            },
+        },
+        {
+            "name": "read_note",
+            "description": "Read a note's markdown, including frontmatter",
+            "inputSchema": { "type": "object", "properties": { "path": path }, "required": ["path"] },
+        },
+        {
+            "name": "write_note",
+            "description": "Create or overwrite a note. Frontmatter is preserved and timestamps are kept current.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "path": path, "content": { "type": "string" } },
+                "required": ["path", "content"],
+            },
+        },
+        {
+            "name": "search_notes",
+            "description": "Full-text search across the vault",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "default": 20 },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "get_contact_data",
+            "description": "Structured contact fields of a note in a rolodex section",
+            "inputSchema": { "type": "object", "properties": { "path": path }, "required": ["path"] },
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    /// A server over a vault with one note, `notes/a.md`.
+    fn fixture(name: &str) -> (McpServer, TempDir) {
+        let root = TempDir::new(&format!("mcp-{}", name));
+        let vault = root.join("vault");
+        let index_path = root.join("index");
+        fs::create_dir_all(vault.join("notes")).unwrap();
+        fs::create_dir_all(&index_path).unwrap();
+        fs::write(vault.join("notes/a.md"), "# A\n\nhello world\n").unwrap();
+        let index = SearchIndex::new(&index_path, &vault).unwrap();
+        (
+            McpServer::new(vault, index, SearchSettings::default()),
+            root,
+        )
+    }
+
+    fn request(server: &McpServer, method: &str, params: Value) -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params });
+        server.handle_message(&message.to_string()).unwrap()
+    }
+
+    fn error_code(response: &Value) -> Option<i64> {
+        response["error"]["code"].as_i64()
+    }
+
+    fn write_section(vault: &Path, section: &str, frontmatter: &str) {
+        fs::create_dir_all(vault.join(section)).unwrap();
+        let metadata = format!("---\n{}---\n", frontmatter);
+        fs::write(vault.join(section).join(".section.md"), metadata).unwrap();
+    }
+
+    #[test]
+    fn initializes_and_lists_tools() {
+        let (server, _root) = fixture("initialize");
+        let response = request(&server, "initialize", json!({}));
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(response["result"]["serverInfo"]["name"], "gitnotes");
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(server.handle_message(&notification.to_string()), None);
+
+        let response = request(&server, "tools/list", json!({}));
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "list_sections",
+                "list_notes",
+                "read_note",
+                "write_note",
+                "search_notes",
+                "get_contact_data"
+            ]
+        );
+    }
+
+    #[test]
+    fn tool_calls_report_failures_as_results() {
+        let (server, _root) = fixture("tools-call");
+        let call = |name: &str, arguments: Value| {
+            request(
+                &server,
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+        };
+
+        let response = call("read_note", json!({ "path": "notes/a.md" }));
+        assert_eq!(response["result"]["isError"], false);
+        assert_eq!(
+            response["result"]["content"][0]["text"],
+            "# A\n\nhello world\n"
+        );
+
+        for (name, arguments) in [
+            ("read_note", json!({ "path": "notes/missing.md" })),
+            ("read_note", json!({ "path": "../outside.md" })),
+            ("read_note", json!({})),
+            ("no_such_tool", json!({})),
+        ] {
+            let response = call(name, arguments);
+            assert_eq!(error_code(&response), None);
+            assert_eq!(response["result"]["isError"], true);
+        }
+    }
+
+    #[test]
+    fn direct_calls_tell_bad_params_from_failures() {
+        let (server, _root) = fixture("direct");
+        let response = request(&server, "list_sections", json!({}));
+        assert_eq!(response["result"][0]["name"], "notes");
+
+        let bad_params = [
+            ("read_note", json!({})),
+            ("read_note", json!({ "path": "../outside.md" })),
+            (
+                "write_note",
+                json!({ "path": "notes/a.txt", "content": "x" }),
+            ),
+            (
+                "write_note",
+                json!({ "path": "missing/a.md", "content": "x" }),
+            ),
+        ];
+        for (method, params) in bad_params {
+            assert_eq!(
+                error_code(&request(&server, method, params)),
+                Some(INVALID_PARAMS)
+            );
+        }
+        let response = request(&server, "read_note", json!({ "path": "notes/missing.md" }));
+        assert_eq!(error_code(&response), Some(INTERNAL_ERROR));
+    }
+
+    #[test]
+    fn lists_a_sections_notes_with_its_instructions() {
+        let (server, root) = fixture("list-notes");
+        let vault = root.join("vault");
+        write_section(&vault, "notes", "agent_instructions: Keep it short\n");
+        fs::write(vault.join("notes/b.md"), "# B\n").unwrap();
+
+        let response = request(&server, "list_notes", json!({ "section": "notes" }));
+        let result = &response["result"];
+        assert_eq!(result["agent_instructions"], "Keep it short");
+        let mut paths: Vec<&str> = result["notes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|note| note["path"].as_str().unwrap())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["notes/a.md", "notes/b.md"]);
+
+        let response = request(&server, "list_notes", json!({ "section": "missing" }));
+        assert_eq!(error_code(&response), Some(INTERNAL_ERROR));
+        let response = request(&server, "list_notes", json!({ "section": "../.." }));
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn writes_notes_and_finds_them_in_search() {
+        let (server, root) = fixture("write-search");
+        let response = request(&server, "search_notes", json!({ "query": "hello" }));
+        assert_eq!(response["result"][0]["path"], "notes/a.md");
+        assert_eq!(response["result"][0]["section"], "notes");
+
+        let response = request(
+            &server,
+            "write_note",
+            json!({ "path": "notes/tent.md", "content": "# Tent\n\nPitched by the river\n" }),
+        );
+        assert_eq!(response["result"]["path"], "notes/tent.md");
+        let written = fs::read_to_string(root.join("vault/notes/tent.md")).unwrap();
+        assert!(written.starts_with("---\ncreated: "));
+        assert!(written.ends_with("# Tent\n\nPitched by the river\n"));
+
+        let response = request(
+            &server,
+            "search_notes",
+            json!({ "query": "river", "limit": 1 }),
+        );
+        let results = response["result"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["path"], "notes/tent.md");
+    }
+
+    #[test]
+    fn reads_contacts_only_in_rolodex_sections() {
+        let (server, root) = fixture("contacts");
+        let vault = root.join("vault");
+        write_section(&vault, "people", "type: rolodex\n");
+        fs::write(
+            vault.join("people/ada.md"),
+            "---\ntitle: Ada Lovelace\ncompany: Analytical Engines\nemails:\n  - label: work\n    value: ada@example.com\n---\n\nMet at the salon\n",
+        )
+        .unwrap();
+
+        let response = request(
+            &server,
+            "get_contact_data",
+            json!({ "path": "people/ada.md" }),
+        );
+        let contact = &response["result"];
+        assert_eq!(contact["title"], "Ada Lovelace");
+        assert_eq!(contact["company"], "Analytical Engines");
+        assert_eq!(
+            contact["emails"],
+            json!([{ "label": "work", "value": "ada@example.com" }])
+        );
+
+        let response = request(&server, "get_contact_data", json!({ "path": "notes/a.md" }));
+        assert_eq!(response["result"], Value::Null);
+        let response = request(
+            &server,
+            "get_contact_data",
+            json!({ "path": "people/bob.md" }),
+        );
+        assert_eq!(error_code(&response), Some(INTERNAL_ERROR));
+        let response = request(&server, "get_contact_data", json!({ "path": "../ada.md" }));
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn serves_section_instructions_as_resources() {
+        let (server, root) = fixture("resources");
+        let vault = root.join("vault");
+        write_section(
+            &vault,
+            "notes",
+            "title: Notes\nagent_instructions: Keep it short\n",
+        );
+        write_section(&vault, "people", "type: rolodex\n");
+
+        let response = request(&server, "resources/list", json!({}));
+        let uri = "gitnotes://sections/notes/instructions";
+        assert_eq!(
+            response["result"]["resources"],
+            json!([{
+                "uri": uri,
+                "name": "Notes instructions",
+                "description": "How agents should work with notes in notes",
+                "mimeType": "text/markdown",
+            }])
+        );
+        let response = request(&server, "initialize", json!({}));
+        assert!(response["result"]["instructions"]
+            .as_str()
+            .unwrap()
+            .ends_with("## Section notes\nKeep it short"));
+
+        let response = request(&server, "resources/read", json!({ "uri": uri }));
+        assert_eq!(
+            response["result"]["contents"],
+            json!([{ "uri": uri, "mimeType": "text/markdown", "text": "Keep it short" }])
+        );
+        for uri in [
+            "gitnotes://sections/people/instructions",
+            "gitnotes://sections/notes",
+            "https://example.com",
+        ] {
+            let response = request(&server, "resources/read", json!({ "uri": uri }));
+            assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+        }
+        let response = request(&server, "resources/read", json!({}));
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        let (server, _root) = fixture("malformed");
+        let response = server.handle_message("{not json").unwrap();
+        assert_eq!(error_code(&response), Some(PARSE_ERROR));
+        assert_eq!(response["id"], Value::Null);
+
+        let response = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 1 }).to_string())
+            .unwrap();
+        assert_eq!(error_code(&response), Some(INVALID_REQUEST));
+
+        let response = request(&server, "no/such/method", json!({}));
+        assert_eq!(error_code(&response), Some(METHOD_NOT_FOUND));
+
+        let response = request(&server, "tools/call", json!({}));
+        assert_eq!(error_code(&response), Some(INVALID_PARAMS));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_never_replaces_a_regular_file() {
+        let (server, root) = fixture("socket");
+        let path = root.join("not-a-socket");
+        fs::write(&path, "keep me").unwrap();
+        assert!(server.serve_socket(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,143 @@
+// Every path the webview (or an agent) hands us is resolved here before we touch the
+// filesystem, so a buggy or compromised caller can't reach outside the active vault.
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// A path that resolves outside the vault, through `..` or a symlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathOutsideVault(pub PathBuf);
+
+impl fmt::Display for PathOutsideVault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Path is outside the vault: {}", self.0.display())
+    }
+}
+
+impl std::error::Error for PathOutsideVault {}
+
+impl From<PathOutsideVault> for String {
+    fn from(e: PathOutsideVault) -> String {
+        e.to_string()
+    }
+}
+
+/// Resolve `path` (absolute, or relative to the vault) to a path inside `vault`.
+///
+/// The target doesn't need to exist yet, but every part of it that does is followed through
+/// symlinks before the check. The result is spelled under `vault` as given, not canonicalized.
+pub fn resolve_in_vault(vault: &Path, path: &str) -> Result<PathBuf, PathOutsideVault> {
+    let outside = || PathOutsideVault(PathBuf::from(path));
+    let vault_root = vault.canonicalize().map_err(|_| outside())?;
+
+    let mut resolved = PathBuf::new();
+    for component in vault.join(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                // Existing entries may be symlinks; anything missing is created by us later
+                if resolved.symlink_metadata().is_ok() {
+                    // A dangling symlink would let a write land wherever it points
+                    resolved = resolved.canonicalize().map_err(|_| outside())?;
+                }
+            }
+        }
+    }
+
+    let relative = resolved.strip_prefix(&vault_root).map_err(|_| outside())?;
+    Ok(vault.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    /// A fresh vault with one section and note, next to a folder outside it.
+    fn fixture(name: &str) -> (TempDir, PathBuf, PathBuf) {
+        let root = TempDir::new(&format!("paths-{}", name));
+        let vault = root.join("vault");
+        fs::create_dir_all(vault.join("notes")).unwrap();
+        fs::write(vault.join("notes/a.md"), "a").unwrap();
+        let outside = root.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret.md"), "secret").unwrap();
+        (root, vault, outside)
+    }
+
+    #[test]
+    fn resolves_paths_inside_the_vault() {
+        let (_root, vault, _) = fixture("inside");
+        assert_eq!(
+            resolve_in_vault(&vault, "notes/a.md"),
+            Ok(vault.join("notes/a.md"))
+        );
+        let absolute = vault.join("notes/a.md");
+        assert_eq!(
+            resolve_in_vault(&vault, absolute.to_str().unwrap()),
+            Ok(vault.join("notes/a.md"))
+        );
+        assert_eq!(resolve_in_vault(&vault, ""), Ok(vault.clone()));
+        // Notes that don't exist yet are fine
+        assert_eq!(
+            resolve_in_vault(&vault, "notes/new.md"),
+            Ok(vault.join("notes/new.md"))
+        );
+        assert_eq!(
+            resolve_in_vault(&vault, "notes/../notes/./a.md"),
+            Ok(vault.join("notes/a.md"))
+        );
+    }
+
+    #[test]
+    fn rejects_parent_dir_traversal() {
+        let (_root, vault, outside) = fixture("traversal");
+        for path in [
+            "..",
+            "../outside/secret.md",
+            "notes/../../outside/secret.md",
+            "notes/missing/../../../outside",
+            "missing/../../outside/new.md",
+        ] {
+            assert!(resolve_in_vault(&vault, path).is_err(), "{} escaped", path);
+        }
+        let absolute = outside.join("secret.md");
+        assert!(resolve_in_vault(&vault, absolute.to_str().unwrap()).is_err());
+        assert!(resolve_in_vault(&vault, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn rejects_sibling_with_shared_prefix() {
+        let (_root, vault, _) = fixture("sibling");
+        let sibling = vault.with_file_name("vault-other");
+        fs::create_dir_all(&sibling).unwrap();
+        assert!(resolve_in_vault(&vault, sibling.to_str().unwrap()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_before_checking() {
+        use std::os::unix::fs::symlink;
+
+        let (_root, vault, outside) = fixture("symlinks");
+        symlink(&outside, vault.join("escape")).unwrap();
+        symlink(outside.join("secret.md"), vault.join("notes/link.md")).unwrap();
+        symlink(vault.join("notes"), vault.join("alias")).unwrap();
+        symlink(vault.join("nowhere"), vault.join("notes/dangling.md")).unwrap();
+
+        assert!(resolve_in_vault(&vault, "escape/secret.md").is_err());
+        assert!(resolve_in_vault(&vault, "escape/new.md").is_err());
+        assert!(resolve_in_vault(&vault, "notes/link.md").is_err());
+        assert!(resolve_in_vault(&vault, "notes/dangling.md").is_err());
+        assert_eq!(
+            resolve_in_vault(&vault, "alias/a.md"),
+            Ok(vault.join("notes/a.md"))
+        );
+    }
+}
@@ -0,0 +1,335 @@
+// Search syntax. Words and "quoted phrases" must all match, `-` in front of anything excludes
+// it, and `key:value` narrows the results:
+//
+//   section:work  tag:project/alpha  type:rolodex  author:ada  author:"Ada Lovelace"
+//   company:acme  role:cto  title:"Dr"  email:ada@example.com
+//   created:>2026-01-01  created:2026-03-14  modified:<7d  modified:>=2w
+//
+// Dates are `YYYY-MM-DD` (UTC) or an age: a number with h, d, w, m (30 days) or y. With an
+// age, `<7d` means less than seven days ago.
+
+use crate::error::GitNotesError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClauseKind {
+    /// A word, matched against note names and content
+    Text(String),
+    Phrase(String),
+    Section(String),
+    Tag(String),
+    Type(String),
+    Author(String),
+    /// Frontmatter fields, as for rolodex contacts
+    Title(String),
+    Company(String),
+    Role(String),
+    Email(String),
+    Created(DateRange),
+    Modified(DateRange),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub negated: bool,
+    pub kind: ClauseKind,
+}
+
+/// Unix timestamps (seconds): `after` is inclusive, `before` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DateRange {
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    pub clauses: Vec<Clause>,
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn parse_error(message: String) -> GitNotesError {
+    GitNotesError::Parse { message }
+}
+
+/// Parse a query; `now` (Unix seconds) anchors ages like `7d`.
+pub fn parse(query: &str, now: u64) -> Result<SearchQuery, GitNotesError> {
+    let mut clauses = Vec::new();
+    for token in tokenize(query)? {
+        let (negated, token) = match token.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, token.as_str()),
+        };
+        let kind = if let Some(phrase) = token.strip_prefix('"') {
+            ClauseKind::Phrase(phrase.trim_end_matches('"').to_string())
+        } else {
+            match token.split_once(':') {
+                Some((key, value)) => match filter(key, &unquote(value), now)? {
+                    Some(kind) => kind,
+                    // Not a filter, e.g. a time like 10:30
+                    None => ClauseKind::Text(token.replace('"', "")),
+                },
+                None => ClauseKind::Text(token.replace('"', "")),
+            }
+        };
+        clauses.push(Clause { negated, kind });
+    }
+    Ok(SearchQuery { clauses })
+}
+
+/// Split on whitespace, keeping quoted stretches (`"a b"`, `author:"a b"`) in one token.
+fn tokenize(query: &str) -> Result<Vec<String>, GitNotesError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in query.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            current.push(c);
+        } else if c.is_whitespace() && !in_quotes {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if in_quotes {
+        return Err(parse_error("Unclosed quote in search".to_string()));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+fn filter(key: &str, value: &str, now: u64) -> Result<Option<ClauseKind>, GitNotesError> {
+    let key = key.to_lowercase();
+    let known = [
+        "section", "tag", "type", "author", "title", "company", "role", "email", "created",
+        "modified",
+    ];
+    if !known.contains(&key.as_str()) {
+        return Ok(None);
+    }
+    if value.is_empty() {
+        return Err(parse_error(format!("Missing value after {}:", key)));
+    }
+    Ok(Some(match key.as_str() {
+        "section" => ClauseKind::Section(value.to_lowercase()),
+        "tag" => ClauseKind::Tag(
+            crate::tags::normalize(value)
+                .ok_or_else(|| parse_error(format!("Invalid tag: {}", value)))?,
+        ),
+        "type" => ClauseKind::Type(value.to_lowercase()),
+        "author" => ClauseKind::Author(value.to_string()),
+        "title" => ClauseKind::Title(value.to_string()),
+        "company" => ClauseKind::Company(value.to_string()),
+        "role" => ClauseKind::Role(value.to_string()),
+        "email" => ClauseKind::Email(value.to_string()),
+        "created" => ClauseKind::Created(date_range(&key, value, now)?),
+        _ => ClauseKind::Modified(date_range(&key, value, now)?),
+    }))
+}
+
+fn date_range(key: &str, value: &str, now: u64) -> Result<DateRange, GitNotesError> {
+    let (op, operand) = [">=", "<=", ">", "<"]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("", value));
+    let invalid = || {
+        parse_error(format!(
+            "Invalid date in {}:{} (use YYYY-MM-DD or an age like 7d)",
+            key, value
+        ))
+    };
+
+    if let Some(age) = parse_age(operand) {
+        // Comparisons are on age, so they flip: `<7d` is after seven days ago
+        let point = now.saturating_sub(age);
+        return Ok(match op {
+            ">" | ">=" => DateRange {
+                before: Some(point),
+                ..Default::default()
+            },
+            _ => DateRange {
+                after: Some(point),
+                ..Default::default()
+            },
+        });
+    }
+
+    let day = chrono::NaiveDate::parse_from_str(operand, "%Y-%m-%d").map_err(|_| invalid())?;
+    let start = day
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(invalid)?
+        .and_utc()
+        .timestamp()
+        .max(0) as u64;
+    let end = start + DAY;
+    Ok(match op {
+        ">" => DateRange {
+            after: Some(end),
+            before: None,
+        },
+        ">=" => DateRange {
+            after: Some(start),
+            before: None,
+        },
+        "<" => DateRange {
+            after: None,
+            before: Some(start),
+        },
+        "<=" => DateRange {
+            after: None,
+            before: Some(end),
+        },
+        _ => DateRange {
+            after: Some(start),
+            before: Some(end),
+        },
+    })
+}
+
+/// `7d`, `12h`, `2w`, `3m`, `1y` in seconds.
+fn parse_age(value: &str) -> Option<u64> {
+    let unit = value.chars().last()?;
+    let count: u64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        'h' => 60 * 60,
+        'd' => DAY,
+        'w' => 7 * DAY,
+        'm' => 30 * DAY,
+        'y' => 365 * DAY,
+        _ => return None,
+    };
+    count.checked_mul(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2026-03-14T00:00:00Z
+    const MARCH_14: u64 = 1_773_446_400;
+    const NOW: u64 = MARCH_14 + 12 * 60 * 60;
+
+    fn kinds(query: &str) -> Vec<(bool, ClauseKind)> {
+        parse(query, NOW)
+            .unwrap()
+            .clauses
+            .into_iter()
+            .map(|c| (c.negated, c.kind))
+            .collect()
+    }
+
+    fn range(query: &str) -> DateRange {
+        match kinds(query).pop() {
+            Some((_, ClauseKind::Created(range) | ClauseKind::Modified(range))) => range,
+            other => panic!("not a date filter: {:?}", other),
+        }
+    }
+
+    fn text(word: &str) -> ClauseKind {
+        ClauseKind::Text(word.to_string())
+    }
+
+    #[test]
+    fn keeps_quoted_stretches_together() {
+        assert_eq!(
+            kinds(r#"  tent  "river bank" author:"Ada Lovelace" "#),
+            vec![
+                (false, text("tent")),
+                (false, ClauseKind::Phrase("river bank".into())),
+                (false, ClauseKind::Author("Ada Lovelace".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn negates_words_phrases_and_filters() {
+        assert_eq!(
+            kinds(r#"-tent -"river bank" -section:Work - "#),
+            vec![
+                (true, text("tent")),
+                (true, ClauseKind::Phrase("river bank".into())),
+                (true, ClauseKind::Section("work".into())),
+                (false, text("-")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_plain_text() {
+        assert_eq!(
+            kinds("10:30 foo:bar TYPE:Rolodex"),
+            vec![
+                (false, text("10:30")),
+                (false, text("foo:bar")),
+                (false, ClauseKind::Type("rolodex".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn dates_cover_whole_days() {
+        let end = MARCH_14 + DAY;
+        let cases = [
+            ("created:2026-03-14", Some(MARCH_14), Some(end)),
+            ("created:>2026-03-14", Some(end), None),
+            ("created:>=2026-03-14", Some(MARCH_14), None),
+            ("created:<2026-03-14", None, Some(MARCH_14)),
+            ("created:<=2026-03-14", None, Some(end)),
+        ];
+        for (query, after, before) in cases {
+            assert_eq!(range(query), DateRange { after, before }, "{}", query);
+        }
+    }
+
+    #[test]
+    fn ages_compare_the_other_way_round() {
+        // Less than seven days old: modified after seven days ago
+        assert_eq!(
+            range("modified:<7d"),
+            DateRange {
+                after: Some(NOW - 7 * DAY),
+                before: None
+            }
+        );
+        // More than two weeks old: modified before two weeks ago
+        assert_eq!(
+            range("modified:>2w"),
+            DateRange {
+                after: None,
+                before: Some(NOW - 14 * DAY)
+            }
+        );
+        assert_eq!(range("created:12h").after, Some(NOW - 12 * 60 * 60));
+        assert_eq!(parse_age("3m"), Some(90 * DAY));
+        assert_eq!(parse_age("1y"), Some(365 * DAY));
+        assert_eq!(parse_age("7"), None);
+        assert_eq!(parse_age("d"), None);
+    }
+
+    #[test]
+    fn reports_malformed_queries() {
+        for query in [
+            r#"tent "river"#,
+            r#"author:"Ada"#,
+            "author:",
+            r#"company:"""#,
+            "created:yesterday",
+            "modified:<2026-13-01",
+        ] {
+            assert!(
+                matches!(parse(query, NOW), Err(GitNotesError::Parse { .. })),
+                "{}",
+                query
+            );
+        }
+    }
+}
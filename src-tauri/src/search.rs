@@ -1,20 +1,107 @@
-use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
+use crate::error::GitNotesError;
+use crate::links::LinkIndex;
+use crate::query::{self, ClauseKind, DateRange, SearchQuery};
+use crate::SearchSettings;
+use crate::{frontmatter, git, tags};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::{Bound, Range};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tantivy::collector::{Collector, DocSetCollector, FacetCollector, TopDocs};
+use tantivy::directory::{Directory, MmapDirectory, INDEX_WRITER_LOCK};
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, MoreLikeThisQuery, Occur,
+    PhrasePrefixQuery, Query, QueryParser, RangeQuery, TermQuery,
+};
 use tantivy::schema::*;
-use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::tokenizer::{TextAnalyzer, TokenStream};
+use tantivy::{
+    DocAddress, DocId, Index, IndexReader, IndexWriter, ReloadPolicy, Score, Searcher,
+    SegmentReader, TantivyDocument, Term,
+};
+
+/// Bump whenever the schema or the way documents are built changes.
+/// An index written with a different version is wiped and rebuilt on open.
+const SCHEMA_VERSION: u32 = 9;
+const SCHEMA_VERSION_FILE: &str = "schema_version";
+
+/// Commits to look back through for the authors of notes that aren't cached. A note last
+/// committed before that is indexed without an author.
+const AUTHOR_WALK_LIMIT: usize = 200;
+
+/// Most fragments returned per search result
+const MAX_FRAGMENTS: usize = 5;
+/// Longest fragment, in characters; CSS truncates to fit the available width
+const FRAGMENT_CHARS: usize = 160;
+/// With fewer exact and prefix hits than this, misspellings are tried too
+const FEW_HITS: usize = 5;
+
+// How much each thing two notes share raises a related note's score, on top of their text
+// being alike
+const RELATED_TAG_BOOST: f32 = 0.25;
+const RELATED_LINK_BOOST: f32 = 0.5;
+const RELATED_SECTION_BOOST: f32 = 0.2;
+/// Notes alike in text that are considered before reranking, per related note asked for
+const RELATED_CANDIDATES: usize = 4;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResult {
     pub path: String,
     pub filename: String,
     pub section: String,
+    /// The first fragment's text and line, for callers that only show one
     pub snippet: Option<String>,
     pub match_line: Option<usize>,
+    pub fragments: Vec<SnippetFragment>,
+    pub match_kind: MatchKind,
+}
+
+/// How a result matched the query's words. Results come in this order.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Exact,
+    /// A word starts a word of the note's name or a heading
+    Prefix,
+    /// A word is a typo or two away from one in the note
+    Fuzzy,
+}
+
+/// One line of a note around matched terms.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SnippetFragment {
+    pub text: String,
+    /// Zero-based line in the note the fragment comes from
+    pub line: usize,
+    pub highlights: Vec<Highlight>,
+}
+
+/// A matched term, as a range of characters (not bytes) within its fragment's text.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelatedNote {
+    pub path: String,
+    pub filename: String,
+    pub section: String,
+    pub score: Score,
+    /// Tags both notes have
+    pub shared_tags: Vec<String>,
+    /// Links between the two notes, plus notes they both link to
+    pub shared_links: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TagCount {
+    pub tag: String,
+    /// Notes with this tag or one nested under it
+    pub count: u64,
 }
 
 pub struct SearchIndex {
@@ -22,80 +109,259 @@ pub struct SearchIndex {
     reader: IndexReader,
     writer: Mutex<IndexWriter>,
     _schema: Schema,
+    notes_path: PathBuf,
+    authors: Mutex<AuthorCache>,
     // Field handles
     path_field: Field,
     filename_field: Field,
     section_field: Field,
     content_field: Field,
+    headings_field: Field,
+    mtime_field: Field,
+    size_field: Field,
+    tags_field: Field,
+    // Filters for the query language
+    created_field: Field,
+    modified_field: Field,
+    section_key_field: Field,
+    type_field: Field,
+    author_field: Field,
+    // Frontmatter, which is left out of `content`
+    title_field: Field,
+    company_field: Field,
+    role_field: Field,
+    email_field: Field,
+}
+
+/// Last commit author of each note (`None` if it has none), valid for one HEAD. Saving a
+/// note doesn't change who last committed it, so only a new HEAD means looking again.
+#[derive(Default)]
+struct AuthorCache {
+    head: Option<String>,
+    authors: HashMap<PathBuf, Option<String>>,
+}
+
+/// What the index knows about a file the last time it was indexed.
+struct IndexedFile {
+    mtime: u64,
+    size: u64,
+}
+
+/// Modification time (ms since epoch) and size of a file, as stored in the index.
+fn file_stamp(path: &PathBuf) -> (u64, u64) {
+    let metadata = std::fs::metadata(path).ok();
+    let mtime = metadata
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let size = metadata.map(|m| m.len()).unwrap_or(0);
+    (mtime, size)
+}
+
+/// Wipe the index directory if it was written by a different schema version. That only
+/// happens while holding the index's writer lock, so an index in use elsewhere is left alone.
+fn check_schema_version(index_path: &PathBuf) -> Result<(), GitNotesError> {
+    std::fs::create_dir_all(index_path)?;
+    let version_file = index_path.join(SCHEMA_VERSION_FILE);
+    let current = std::fs::read_to_string(&version_file)
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok());
+    if current == Some(SCHEMA_VERSION) {
+        return Ok(());
+    }
+
+    let directory = MmapDirectory::open(index_path).map_err(|e| GitNotesError::Index {
+        message: e.to_string(),
+    })?;
+    let _lock = directory
+        .acquire_lock(&INDEX_WRITER_LOCK)
+        .map_err(tantivy::TantivyError::from)?;
+    for entry in std::fs::read_dir(index_path)?.flatten() {
+        let path = entry.path();
+        if path.file_name() == INDEX_WRITER_LOCK.filepath.file_name() {
+            continue;
+        }
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    std::fs::write(&version_file, SCHEMA_VERSION.to_string())?;
+    Ok(())
 }
 
 impl SearchIndex {
-    pub fn new(index_path: &PathBuf, notes_path: &PathBuf) -> Result<Self, String> {
+    pub fn new(index_path: &PathBuf, notes_path: &PathBuf) -> Result<Self, GitNotesError> {
         // Build schema
         let mut schema_builder = Schema::builder();
-        let path_field = schema_builder.add_text_field("path", STRING | STORED);
+        // Fast, to look up pinned notes while scoring
+        let path_field = schema_builder.add_text_field("path", STRING | STORED | FAST);
         let filename_field = schema_builder.add_text_field("filename", TEXT | STORED);
         let section_field = schema_builder.add_text_field("section", TEXT | STORED);
         let content_field = schema_builder.add_text_field("content", TEXT | STORED);
+        let headings_field = schema_builder.add_text_field("headings", TEXT);
+        let mtime_field = schema_builder.add_u64_field("mtime", STORED);
+        let size_field = schema_builder.add_u64_field("size", STORED);
+        // Stored, to compare notes' tags for related notes
+        let tags_field =
+            schema_builder.add_facet_field("tags", FacetOptions::default().set_stored());
+        // Unix seconds, from the frontmatter or else the file
+        let created_field = schema_builder.add_u64_field("created", INDEXED | FAST);
+        let modified_field = schema_builder.add_u64_field("modified", INDEXED | FAST);
+        // Lowercased, for exact matches
+        let section_key_field = schema_builder.add_text_field("section_key", STRING | FAST);
+        let type_field = schema_builder.add_text_field("type", STRING | FAST);
+        let author_field = schema_builder.add_text_field("author", TEXT);
+        let title_field = schema_builder.add_text_field("title", TEXT);
+        let company_field = schema_builder.add_text_field("company", TEXT);
+        let role_field = schema_builder.add_text_field("role", TEXT);
+        let email_field = schema_builder.add_text_field("email", TEXT);
         let schema = schema_builder.build();
 
-        // Create or open index
-        std::fs::create_dir_all(index_path).map_err(|e| e.to_string())?;
+        // Create or open index, rebuilding from scratch on schema changes
+        check_schema_version(index_path)?;
 
-        let index = Index::create_in_dir(index_path, schema.clone())
-            .or_else(|_| Index::open_in_dir(index_path))
-            .map_err(|e| e.to_string())?;
+        let index = Index::open_in_dir(index_path)
+            .or_else(|_| Index::create_in_dir(index_path, schema.clone()))?;
 
         let reader = index
             .reader_builder()
             .reload_policy(ReloadPolicy::OnCommitWithDelay)
             .try_into()
-            .map_err(|e: tantivy::TantivyError| e.to_string())?;
+            .map_err(GitNotesError::from)?;
 
-        let writer = index.writer(50_000_000).map_err(|e| e.to_string())?;
+        let writer = index.writer(50_000_000)?;
 
         let search_index = Self {
             index,
             reader,
             writer: Mutex::new(writer),
             _schema: schema,
+            notes_path: notes_path.clone(),
+            authors: Mutex::new(AuthorCache::default()),
             path_field,
             filename_field,
             section_field,
             content_field,
+            headings_field,
+            mtime_field,
+            size_field,
+            tags_field,
+            created_field,
+            modified_field,
+            section_key_field,
+            type_field,
+            author_field,
+            title_field,
+            company_field,
+            role_field,
+            email_field,
         };
 
-        // Initial indexing of all files
-        search_index.index_all_files(notes_path)?;
+        // Bring the persisted index up to date with what's on disk
+        search_index.sync_files(notes_path)?;
 
         Ok(search_index)
     }
 
-    fn index_all_files(&self, notes_path: &PathBuf) -> Result<(), String> {
+    /// Re-index only the files that changed since the index was last written,
+    /// and drop entries for files that no longer exist.
+    pub fn sync_files(&self, notes_path: &PathBuf) -> Result<(), GitNotesError> {
+        let mut indexed = self.indexed_files()?;
+
+        let mut on_disk = Vec::new();
+        if notes_path.exists() {
+            Self::collect_markdown_files(notes_path, &mut on_disk)?;
+        }
+
         let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        let mut to_index = Vec::new();
 
-        // Clear existing index
-        writer.delete_all_documents().map_err(|e| e.to_string())?;
+        for path in on_disk {
+            let path_str = path.to_string_lossy().to_string();
+            let (mtime, size) = file_stamp(&path);
 
-        // Walk directory and index all .md files
-        if notes_path.exists() {
-            self.index_directory_recursive(&mut writer, notes_path, notes_path)?;
+            if let Some(known) = indexed.remove(&path_str) {
+                if known.mtime == mtime && known.size == size {
+                    continue;
+                }
+                // Even if only touched (e.g. git checkout), re-add it so the new stamp is stored
+                // and the file isn't read again on the next launch
+                writer.delete_term(tantivy::Term::from_field_text(self.path_field, &path_str));
+            }
+            to_index.push(path);
+        }
+
+        let authors = self.git_authors(notes_path, &to_index);
+        for path in &to_index {
+            match self.add_file_to_index(&mut writer, path, notes_path, authors.get(path)) {
+                // One unreadable note (not UTF-8, or gone since the scan) shouldn't stop the rest
+                Err(e @ GitNotesError::Io { .. }) => eprintln!("Skipping in search index: {}", e),
+                result => result?,
+            }
+        }
+        let mut changed = !to_index.is_empty();
+
+        // Whatever is left in the index no longer exists on disk
+        for path_str in indexed.keys() {
+            writer.delete_term(tantivy::Term::from_field_text(self.path_field, path_str));
+            changed = true;
         }
 
-        writer.commit().map_err(|e| e.to_string())?;
+        if changed {
+            writer.commit()?;
+            self.reader.reload()?;
+        }
         Ok(())
     }
 
-    fn index_directory_recursive(
-        &self,
-        writer: &mut IndexWriter,
-        dir: &PathBuf,
-        notes_root: &PathBuf,
-    ) -> Result<(), String> {
-        let entries = std::fs::read_dir(dir).map_err(|e| e.to_string())?;
+    /// Drop everything and index the vault from scratch. Returns the number of notes indexed.
+    pub fn rebuild(&self, notes_path: &PathBuf) -> Result<u64, GitNotesError> {
+        {
+            let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+            writer.delete_all_documents()?;
+            writer.commit()?;
+        }
+        self.sync_files(notes_path)?;
+        self.reader.reload()?;
+        Ok(self.reader.searcher().num_docs())
+    }
+
+    /// Read back the path, mtime and size of every indexed document.
+    fn indexed_files(&self) -> Result<HashMap<String, IndexedFile>, GitNotesError> {
+        self.reader.reload()?;
+        let searcher = self.reader.searcher();
+
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            ?;
+
+        let mut files = HashMap::new();
+        for address in addresses {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let path = match doc.get_first(self.path_field).and_then(|v| v.as_str()) {
+                Some(p) => p.to_string(),
+                None => continue,
+            };
+            files.insert(
+                path,
+                IndexedFile {
+                    mtime: doc.get_first(self.mtime_field).and_then(|v| v.as_u64()).unwrap_or(0),
+                    size: doc.get_first(self.size_field).and_then(|v| v.as_u64()).unwrap_or(0),
+                },
+            );
+        }
+        Ok(files)
+    }
+
+    fn collect_markdown_files(dir: &PathBuf, files: &mut Vec<PathBuf>) -> Result<(), GitNotesError> {
+        let entries = std::fs::read_dir(dir)?;
 
         for entry in entries {
-            let entry = entry.map_err(|e| e.to_string())?;
+            let entry = entry?;
             let path = entry.path();
 
             if path.is_dir() {
@@ -106,10 +372,10 @@ impl SearchIndex {
                     .map(|n| n.starts_with('.'))
                     .unwrap_or(false)
                 {
-                    self.index_directory_recursive(writer, &path, notes_root)?;
+                    Self::collect_markdown_files(&path, files)?;
                 }
             } else if path.extension().map(|e| e == "md").unwrap_or(false) {
-                self.add_file_to_index(writer, &path, notes_root)?;
+                files.push(path);
             }
         }
         Ok(())
@@ -119,9 +385,12 @@ impl SearchIndex {
         &self,
         writer: &mut IndexWriter,
         path: &PathBuf,
-        _notes_root: &PathBuf,
-    ) -> Result<(), String> {
-        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        notes_root: &PathBuf,
+        git_author: Option<&String>,
+    ) -> Result<(), GitNotesError> {
+        let content = std::fs::read_to_string(path).map_err(|e| GitNotesError::io(path, e))?;
+        let (mtime, size) = file_stamp(path);
+        let (fm, body) = frontmatter::split(&content);
 
         let filename = path
             .file_stem()
@@ -131,7 +400,7 @@ impl SearchIndex {
 
         // Compute section as first directory after notes root (not sub-folder name)
         let section = path
-            .strip_prefix(_notes_root)
+            .strip_prefix(notes_root)
             .ok()
             .and_then(|rel| rel.components().next())
             .and_then(|c| c.as_os_str().to_str())
@@ -144,127 +413,566 @@ impl SearchIndex {
         doc.add_text(self.path_field, &path_str);
         doc.add_text(self.filename_field, &filename);
         doc.add_text(self.section_field, &section);
-        doc.add_text(self.content_field, &content);
+        // The body alone, on the lines it has in the file, so snippet line numbers still match
+        let frontmatter_lines = content[..content.len() - body.len()].matches('\n').count();
+        doc.add_text(
+            self.content_field,
+            format!("{}{}", "\n".repeat(frontmatter_lines), body),
+        );
+        for heading in headings(body) {
+            doc.add_text(self.headings_field, heading);
+        }
+        doc.add_u64(self.mtime_field, mtime);
+        doc.add_u64(self.size_field, size);
+        for tag in tags::extract_tags(&content) {
+            doc.add_facet(self.tags_field, tag_facet(&tag));
+        }
+
+        let timestamp = |key: &str| {
+            fm.as_ref()
+                .and_then(|fm| fm.get_str(key))
+                .map(crate::iso_to_timestamp)
+                .filter(|ts| *ts > 0)
+        };
+        let file_created = std::fs::metadata(path)
+            .and_then(|m| m.created())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        doc.add_u64(
+            self.created_field,
+            timestamp("created")
+                .or(file_created)
+                .unwrap_or(mtime / 1000),
+        );
+        doc.add_u64(
+            self.modified_field,
+            timestamp("modified").unwrap_or(mtime / 1000),
+        );
+        doc.add_text(self.section_key_field, section.to_lowercase());
+
+        // The note's own `type`, else its section's
+        let note_type = fm
+            .as_ref()
+            .and_then(|fm| fm.get_str("type"))
+            .map(String::from)
+            .or_else(|| section_type(&notes_root.join(&section)));
+        if let Some(note_type) = note_type {
+            doc.add_text(self.type_field, note_type.to_lowercase());
+        }
 
-        writer.add_document(doc).map_err(|e| e.to_string())?;
+        let frontmatter_fields = [
+            ("author", self.author_field),
+            ("title", self.title_field),
+            ("company", self.company_field),
+            ("role", self.role_field),
+            ("emails", self.email_field),
+        ];
+        for (key, field) in frontmatter_fields {
+            if let Some(value) = fm.as_ref().and_then(|fm| fm.fields().get(key)) {
+                for text in frontmatter_strings(value) {
+                    doc.add_text(field, text);
+                }
+            }
+        }
+        if let Some(author) = git_author {
+            doc.add_text(self.author_field, author);
+        }
+
+        writer.add_document(doc)?;
         Ok(())
     }
 
-    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+    /// Search with the query language in `query.rs`. A query that is only filters lists
+    /// every matching note, most relevant by `weights` first; an empty one matches nothing.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        weights: &SearchSettings,
+    ) -> Result<Vec<SearchResult>, GitNotesError> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let parsed = query::parse(query, now)?;
+        let widenable = parsed
+            .clauses
+            .iter()
+            .any(|c| !c.negated && matches!(c.kind, ClauseKind::Text(_)));
+
+        // Exact matches first, then prefix matches to fill up, and typos only for a near miss
         let searcher = self.reader.searcher();
+        let mut results = Vec::new();
+        let mut seen = HashSet::new();
+        for kind in [MatchKind::Exact, MatchKind::Prefix, MatchKind::Fuzzy] {
+            let enough = match kind {
+                MatchKind::Exact => false,
+                MatchKind::Prefix => !widenable || results.len() >= limit,
+                MatchKind::Fuzzy => results.len() >= FEW_HITS.min(limit),
+            };
+            if enough {
+                break;
+            }
+            let Some(compiled) = self.compile(&parsed, kind, weights)? else {
+                break;
+            };
+            let highlighter = self.highlighter(&parsed, &*compiled, kind)?;
+            // Each kind's query matches everything the one before did
+            for (_score, address) in
+                searcher.search(&compiled, &self.ranked(limit, weights, now))?
+            {
+                if results.len() < limit && seen.insert(address) {
+                    results.push(self.result_for(&searcher, address, Some(&highlighter))?);
+                }
+            }
+        }
+        Ok(results)
+    }
 
-        let query_parser =
-            QueryParser::for_index(&self.index, vec![self.filename_field, self.content_field]);
+    /// The top `limit` notes, with scores raised for recently modified and pinned notes.
+    fn ranked(
+        &self,
+        limit: usize,
+        weights: &SearchSettings,
+        now: u64,
+    ) -> impl Collector<Fruit = Vec<(Score, DocAddress)>> {
+        let pinned = self.pinned_paths();
+        let recency_boost = weights.recency_boost;
+        let half_life = weights.recency_half_life_days.max(f32::MIN_POSITIVE) * DAY_SECS;
+        let pinned_boost = weights.pinned_boost;
+        TopDocs::with_limit(limit).tweak_score(move |segment: &SegmentReader| {
+            let modified = segment.fast_fields().u64("modified").ok();
+            let paths = segment.fast_fields().str("path").ok().flatten();
+            let pinned_ords: HashSet<u64> = paths
+                .as_ref()
+                .map(|paths| {
+                    pinned
+                        .iter()
+                        .filter_map(|path| paths.dictionary().term_ord(path).ok().flatten())
+                        .collect()
+                })
+                .unwrap_or_default();
+            move |doc: DocId, score: Score| {
+                let mut score = score;
+                if let Some(modified) = modified.as_ref().and_then(|m| m.first(doc)) {
+                    let age = now.saturating_sub(modified) as f32;
+                    score *= 1.0 + recency_boost * 0.5f32.powf(age / half_life);
+                }
+                let is_pinned = paths
+                    .as_ref()
+                    .is_some_and(|paths| paths.term_ords(doc).any(|o| pinned_ords.contains(&o)));
+                if is_pinned {
+                    score *= pinned_boost;
+                }
+                score
+            }
+        })
+    }
 
-        let parsed_query = query_parser.parse_query(query).map_err(|e| e.to_string())?;
+    /// Paths of the notes listed in each section's `pinned`.
+    fn pinned_paths(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.notes_path) else {
+            return vec![];
+        };
+        let mut pinned = Vec::new();
+        for entry in entries.flatten() {
+            let section = entry.path();
+            if !section.is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            for filename in crate::load_section_metadata(&section).pinned {
+                pinned.push(section.join(filename).to_string_lossy().to_string());
+            }
+        }
+        pinned
+    }
 
-        let top_docs = searcher
-            .search(&parsed_query, &TopDocs::with_limit(limit))
-            .map_err(|e| e.to_string())?;
+    /// Where words can match, with how much each counts. Frontmatter counts like the body.
+    fn text_fields(&self, weights: &SearchSettings) -> Vec<(Field, Score)> {
+        vec![
+            (self.filename_field, weights.filename_boost),
+            (self.headings_field, weights.headings_boost),
+            (self.content_field, weights.content_boost),
+            (self.title_field, weights.content_boost),
+            (self.company_field, weights.content_boost),
+            (self.role_field, weights.content_boost),
+            (self.email_field, weights.content_boost),
+        ]
+    }
 
-        let mut results = Vec::new();
+    /// Every clause must hold; `None` if the query has nothing to match. `kind` loosens how
+    /// words match, but never exclusions.
+    fn compile(
+        &self,
+        parsed: &SearchQuery,
+        kind: MatchKind,
+        weights: &SearchSettings,
+    ) -> Result<Option<Box<dyn Query>>, GitNotesError> {
+        let fields = self.text_fields(weights);
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for clause in &parsed.clauses {
+            let query: Box<dyn Query> = match &clause.kind {
+                ClauseKind::Text(text) if !clause.negated && kind != MatchKind::Exact => {
+                    match self.loose_query(text, kind, weights)? {
+                        Some(query) => query,
+                        None => continue,
+                    }
+                }
+                ClauseKind::Text(text) | ClauseKind::Phrase(text) => {
+                    match self.text_query(&fields, text)? {
+                        Some(query) => query,
+                        None => continue,
+                    }
+                }
+                ClauseKind::Author(value)
+                | ClauseKind::Title(value)
+                | ClauseKind::Company(value)
+                | ClauseKind::Role(value)
+                | ClauseKind::Email(value) => {
+                    let field = match &clause.kind {
+                        ClauseKind::Author(_) => self.author_field,
+                        ClauseKind::Title(_) => self.title_field,
+                        ClauseKind::Company(_) => self.company_field,
+                        ClauseKind::Role(_) => self.role_field,
+                        _ => self.email_field,
+                    };
+                    match self.text_query(&[(field, 1.0)], value)? {
+                        Some(query) => query,
+                        None => continue,
+                    }
+                }
+                ClauseKind::Section(section) => exact_query(self.section_key_field, section),
+                ClauseKind::Type(note_type) => exact_query(self.type_field, note_type),
+                ClauseKind::Tag(tag) => self.tag_query(tag),
+                ClauseKind::Created(range) => date_query("created", range),
+                ClauseKind::Modified(range) => date_query("modified", range),
+            };
+            let occur = if clause.negated {
+                Occur::MustNot
+            } else {
+                Occur::Must
+            };
+            clauses.push((occur, query));
+        }
 
-        for (_score, doc_address) in top_docs {
-            let doc: TantivyDocument = searcher.doc(doc_address).map_err(|e| e.to_string())?;
+        if clauses.is_empty() {
+            return Ok(None);
+        }
+        // Exclusions alone match nothing in tantivy; start from every note
+        if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+            clauses.push((Occur::Must, Box::new(AllQuery)));
+        }
+        Ok(Some(Box::new(BooleanQuery::new(clauses))))
+    }
 
-            let path = doc
-                .get_first(self.path_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+    /// Words or a phrase in any of `fields`, tokenized like the indexed text. Quoting keeps
+    /// tantivy's own syntax (AND, field:, ^) from applying to the user's words.
+    fn text_query(
+        &self,
+        fields: &[(Field, Score)],
+        text: &str,
+    ) -> Result<Option<Box<dyn Query>>, GitNotesError> {
+        let text = text.replace(['"', '\\'], " ");
+        if !text.chars().any(char::is_alphanumeric) {
+            return Ok(None);
+        }
+        let mut parser =
+            QueryParser::for_index(&self.index, fields.iter().map(|(f, _)| *f).collect());
+        for (field, boost) in fields {
+            parser.set_field_boost(*field, *boost);
+        }
+        Ok(Some(parser.parse_query(&format!("\"{}\"", text.trim()))?))
+    }
 
-            let filename = doc
-                .get_first(self.filename_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+    /// A word matched exactly, or at the start of a word in the note's name or headings, or
+    /// (for `Fuzzy`) with a typo or two anywhere.
+    fn loose_query(
+        &self,
+        text: &str,
+        kind: MatchKind,
+        weights: &SearchSettings,
+    ) -> Result<Option<Box<dyn Query>>, GitNotesError> {
+        let words = self.words(text)?;
+        let fields = self.text_fields(weights);
+        let Some(exact) = self.text_query(&fields, text)? else {
+            return Ok(None);
+        };
+        // Only overlong words, which aren't indexed
+        if words.is_empty() {
+            return Ok(Some(exact));
+        }
+        let mut alternatives: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Should, exact)];
+        // Names and headings only; every other word in a note starts with something
+        let prefix_fields = [
+            (self.filename_field, weights.filename_boost),
+            (self.headings_field, weights.headings_boost),
+        ];
+        for (field, boost) in prefix_fields {
+            let terms = words
+                .iter()
+                .map(|word| Term::from_field_text(field, word))
+                .collect();
+            let query = Box::new(PhrasePrefixQuery::new(terms));
+            alternatives.push((Occur::Should, Box::new(BoostQuery::new(query, boost))));
+        }
+        if kind == MatchKind::Fuzzy {
+            let every_word = words
+                .iter()
+                .map(|word| {
+                    let any_field = fields
+                        .iter()
+                        .map(|(field, boost)| {
+                            let term = Term::from_field_text(*field, word);
+                            let query = FuzzyTermQuery::new(term, typo_distance(word), true);
+                            let query = BoostQuery::new(Box::new(query), *boost);
+                            (Occur::Should, Box::new(query) as Box<dyn Query>)
+                        })
+                        .collect();
+                    (
+                        Occur::Must,
+                        Box::new(BooleanQuery::new(any_field)) as Box<dyn Query>,
+                    )
+                })
+                .collect();
+            alternatives.push((Occur::Should, Box::new(BooleanQuery::new(every_word))));
+        }
+        Ok(Some(Box::new(BooleanQuery::new(alternatives))))
+    }
 
-            let section = doc
-                .get_first(self.section_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+    fn tokenizer(&self) -> Result<TextAnalyzer, GitNotesError> {
+        Ok(self.index.tokenizer_for_field(self.content_field)?)
+    }
 
-            let content = doc
-                .get_first(self.content_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
+    /// `text` split and lowercased the way notes are indexed.
+    fn words(&self, text: &str) -> Result<Vec<String>, GitNotesError> {
+        let mut tokenizer = self.tokenizer()?;
+        let mut stream = tokenizer.token_stream(text);
+        let mut words = Vec::new();
+        while let Some(token) = stream.next() {
+            words.push(token.text.clone());
+        }
+        Ok(words)
+    }
+
+    fn highlighter(
+        &self,
+        parsed: &SearchQuery,
+        compiled: &dyn Query,
+        kind: MatchKind,
+    ) -> Result<Highlighter, GitNotesError> {
+        let mut terms = BTreeMap::new();
+        compiled.query_terms(&mut |term, _| {
+            if term.field() == self.content_field {
+                if let Some(text) = term.value().as_str() {
+                    terms.insert(text.to_string(), 1.0);
+                }
+            }
+        });
+        let mut words = Vec::new();
+        for clause in &parsed.clauses {
+            if let ClauseKind::Text(text) = &clause.kind {
+                if !clause.negated {
+                    words.extend(self.words(text)?);
+                }
+            }
+        }
+        Ok(Highlighter {
+            terms,
+            words,
+            kind,
+            tokenizer: self.tokenizer()?,
+            field: self.content_field,
+        })
+    }
 
-            // Generate snippet around first match
-            let (snippet, match_line) = self.generate_snippet(content, query);
+    /// Notes about the same things as the one at `path`: alike in wording, then ranked up for
+    /// sharing tags, links and section. Leaves out the note itself and its whispers.
+    pub fn related_notes(
+        &self,
+        path: &Path,
+        limit: usize,
+        links: &LinkIndex,
+    ) -> Result<Vec<RelatedNote>, GitNotesError> {
+        let searcher = self.reader.searcher();
+        let path_str = path.to_string_lossy().to_string();
+        let this = searcher.search(
+            &TermQuery::new(
+                Term::from_field_text(self.path_field, &path_str),
+                IndexRecordOption::Basic,
+            ),
+            &TopDocs::with_limit(1),
+        )?;
+        // Not indexed yet
+        let Some((_, address)) = this.first() else {
+            return Ok(vec![]);
+        };
+        let doc: TantivyDocument = searcher.doc(*address)?;
 
-            results.push(SearchResult {
-                path,
-                filename,
-                section,
-                snippet,
-                match_line,
+        // Terms that appear in the note and at least one other
+        let text_fields = [self.filename_field, self.content_field]
+            .iter()
+            .map(|field| {
+                let values = doc.get_all(*field).cloned().collect();
+                (*field, values)
+            })
+            .collect();
+        let query = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(2)
+            .with_min_term_frequency(1)
+            .with_min_word_length(3)
+            .with_document_fields(text_fields);
+        let candidates = searcher.search(
+            &query,
+            &TopDocs::with_limit((limit + 1) * RELATED_CANDIDATES),
+        )?;
+
+        let tags = stored_tags(&doc, self.tags_field);
+        let section = doc.get_first(self.section_field).and_then(|v| v.as_str());
+        let linked_to: HashSet<String> = links
+            .outgoing_links(path)
+            .into_iter()
+            .filter_map(|link| link.target)
+            .collect();
+        let linked_from: HashSet<String> = links
+            .backlinks(path)
+            .into_iter()
+            .map(|link| link.source)
+            .collect();
+
+        let mut related = Vec::new();
+        for (score, candidate) in candidates {
+            let other: TantivyDocument = searcher.doc(candidate)?;
+            let field = |field: Field| {
+                other
+                    .get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let other_path = field(self.path_field);
+            if other_path == path_str || is_whisper_of(Path::new(&other_path), path) {
+                continue;
+            }
+
+            let shared_tags: Vec<String> = stored_tags(&other, self.tags_field)
+                .into_iter()
+                .filter(|tag| tags.contains(tag))
+                .collect();
+            let direct = linked_to.contains(&other_path) || linked_from.contains(&other_path);
+            let shared_targets = links
+                .outgoing_links(Path::new(&other_path))
+                .into_iter()
+                .filter_map(|link| link.target)
+                .collect::<HashSet<_>>()
+                .intersection(&linked_to)
+                .count();
+            let shared_links = usize::from(direct) + shared_targets;
+            let same_section = section == Some(field(self.section_field).as_str());
+
+            let boost = 1.0
+                + RELATED_TAG_BOOST * shared_tags.len() as f32
+                + RELATED_LINK_BOOST * shared_links as f32
+                + if same_section {
+                    RELATED_SECTION_BOOST
+                } else {
+                    0.0
+                };
+            related.push(RelatedNote {
+                path: other_path,
+                filename: field(self.filename_field),
+                section: field(self.section_field),
+                score: score * boost,
+                shared_tags,
+                shared_links,
             });
         }
+        related.sort_by(|a, b| b.score.total_cmp(&a.score));
+        related.truncate(limit);
+        Ok(related)
+    }
 
+    /// Every note with `tag` or a tag nested under it, by path.
+    pub fn notes_with_tag(&self, tag: &str) -> Result<Vec<SearchResult>, GitNotesError> {
+        let Some(tag) = tags::normalize(tag) else {
+            return Ok(vec![]);
+        };
+        let searcher = self.reader.searcher();
+        let addresses = searcher.search(&self.tag_query(&tag), &DocSetCollector)?;
+        let mut results = addresses
+            .into_iter()
+            .map(|address| self.result_for(&searcher, address, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        results.sort_by(|a, b| a.path.cmp(&b.path));
         Ok(results)
     }
 
-    fn generate_snippet(&self, content: &str, query: &str) -> (Option<String>, Option<usize>) {
-        let query_lower = query.to_lowercase();
-        let content_lower = content.to_lowercase();
-
-        if let Some(pos) = content_lower.find(&query_lower) {
-            // Find line number
-            let line_num = content[..pos].matches('\n').count();
-
-            // Find start of line containing match (for cleaner context)
-            let line_start = content[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
-
-            // Start from beginning of line, but skip leading whitespace
-            let trimmed_start = content[line_start..pos]
-                .find(|c: char| !c.is_whitespace())
-                .map(|i| line_start + i)
-                .unwrap_or(line_start);
-
-            // If line prefix is too long (>15 chars), start closer to match
-            let start = if pos - trimmed_start > 15 {
-                // Start ~10 chars before match
-                content[..pos]
-                    .char_indices()
-                    .rev()
-                    .nth(10)
-                    .map(|(i, _)| i)
-                    .unwrap_or(trimmed_start)
-            } else {
-                trimmed_start
-            };
-
-            // Send plenty of chars, CSS will truncate to fit available width
-            let end_offset = pos + query.len();
-            let end = content[end_offset..]
-                .char_indices()
-                .nth(500)
-                .map(|(i, _)| end_offset + i)
-                .unwrap_or(content.len());
-
-            let mut snippet = String::new();
-            if start > line_start {
-                snippet.push_str("...");
+    /// All tags in the vault with how many notes carry them, parents before their children.
+    pub fn list_tags(&self) -> Result<Vec<TagCount>, GitNotesError> {
+        let searcher = self.reader.searcher();
+        let mut tags = Vec::new();
+        // One pass per nesting level, counting the children of the level above
+        let mut level = vec![Facet::root()];
+        while !level.is_empty() {
+            let mut collector = FacetCollector::for_field("tags");
+            for facet in &level {
+                collector.add_facet(facet.clone());
             }
+            let counts = searcher.search(&AllQuery, &collector)?;
+            let mut next = Vec::new();
+            for parent in &level {
+                for (facet, count) in counts.get(parent.clone()) {
+                    tags.push(TagCount {
+                        tag: facet.to_path().join("/"),
+                        count,
+                    });
+                    next.push(facet.clone());
+                }
+            }
+            level = next;
+        }
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+        Ok(tags)
+    }
+
+    /// Matches notes tagged `tag` or anything below it; facets index every ancestor.
+    fn tag_query(&self, tag: &str) -> Box<dyn Query> {
+        Box::new(TermQuery::new(
+            Term::from_facet(self.tags_field, &tag_facet(tag)),
+            IndexRecordOption::Basic,
+        ))
+    }
 
-            // Get snippet and clean it up
-            let raw_snippet = content[start..end].trim();
-            // Stop at newline to keep it clean
-            let clean_snippet = raw_snippet.split('\n').next().unwrap_or(raw_snippet);
-            snippet.push_str(clean_snippet);
+    fn result_for(
+        &self,
+        searcher: &Searcher,
+        address: DocAddress,
+        highlighter: Option<&Highlighter>,
+    ) -> Result<SearchResult, GitNotesError> {
+        let doc: TantivyDocument = searcher.doc(address)?;
+        let field = |field: Field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
 
-            if end < content.len() && !clean_snippet.ends_with("...") {
-                snippet.push_str("...");
-            }
+        let fragments = highlighter
+            .map(|highlighter| highlighter.fragments(&field(self.content_field)))
+            .unwrap_or_default();
 
-            (Some(snippet), Some(line_num))
-        } else {
-            (None, None)
-        }
+        Ok(SearchResult {
+            path: field(self.path_field),
+            filename: field(self.filename_field),
+            section: field(self.section_field),
+            snippet: fragments.first().map(|f| f.text.clone()),
+            match_line: fragments.first().map(|f| f.line),
+            fragments,
+            match_kind: highlighter.map_or(MatchKind::Exact, |h| h.kind),
+        })
     }
 
-    pub fn index_file(&self, path: &PathBuf, notes_root: &PathBuf) -> Result<(), String> {
+    pub fn index_file(&self, path: &PathBuf, notes_root: &PathBuf) -> Result<(), GitNotesError> {
         let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
 
         // Remove old version first
@@ -273,75 +981,782 @@ impl SearchIndex {
         writer.delete_term(term);
 
         // Add new version
-        self.add_file_to_index(&mut writer, path, notes_root)?;
-        writer.commit().map_err(|e| e.to_string())?;
+        let authors = self.git_authors(notes_root, std::slice::from_ref(path));
+        self.add_file_to_index(&mut writer, path, notes_root, authors.get(path))?;
+        writer.commit()?;
         Ok(())
     }
 
-    pub fn remove_file(&self, path: &PathBuf) -> Result<(), String> {
+    /// Last commit author of each file that has one, keyed by the file's path. Cached authors
+    /// are used and only the newest `AUTHOR_WALK_LIMIT` commits are searched for the rest.
+    fn git_authors(&self, notes_path: &Path, files: &[PathBuf]) -> HashMap<PathBuf, String> {
+        let Ok(repo) = git::open(notes_path) else {
+            return HashMap::new();
+        };
+        let Ok(mut cache) = self.authors.lock() else {
+            return HashMap::new();
+        };
+        let head = git::head(&repo).map(|c| c.full_hash);
+        if cache.head != head {
+            cache.head = head;
+            cache.authors.clear();
+        }
+
+        let missing: Vec<&PathBuf> = files
+            .iter()
+            .filter(|path| !cache.authors.contains_key(*path))
+            .collect();
+        if !missing.is_empty() {
+            let by_rel: HashMap<String, &PathBuf> = missing
+                .iter()
+                .filter_map(|path| Some((git::relative_path(&repo, path).ok()?, *path)))
+                .collect();
+            let rel_paths: HashSet<String> = by_rel.keys().cloned().collect();
+            let mut found = git::last_authors(&repo, &rel_paths, Some(AUTHOR_WALK_LIMIT));
+            for (rel, path) in by_rel {
+                cache.authors.insert(path.to_path_buf(), found.remove(&rel));
+            }
+        }
+
+        files
+            .iter()
+            .filter_map(|path| Some((path.clone(), cache.authors.get(path)?.clone()?)))
+            .collect()
+    }
+
+    pub fn remove_file(&self, path: &PathBuf) -> Result<(), GitNotesError> {
         let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
         let path_str = path.to_string_lossy().to_string();
         let term = tantivy::Term::from_field_text(self.path_field, &path_str);
         writer.delete_term(term);
-        writer.commit().map_err(|e| e.to_string())?;
+        writer.commit()?;
         Ok(())
     }
+}
 
-    pub fn start_watcher(
-        search_index: Arc<SearchIndex>,
-        notes_path: PathBuf,
-    ) -> Result<(), String> {
-        let notes_path_clone = notes_path.clone();
-
-        std::thread::spawn(move || {
-            let (tx, rx) = std::sync::mpsc::channel();
+/// What to highlight in results: the query's own terms and, for prefix and fuzzy results, the
+/// words in each note that those matched.
+struct Highlighter {
+    terms: BTreeMap<String, Score>,
+    words: Vec<String>,
+    kind: MatchKind,
+    tokenizer: TextAnalyzer,
+    field: Field,
+}
 
-            let mut debouncer = match new_debouncer(Duration::from_millis(500), tx) {
-                Ok(d) => d,
-                Err(e) => {
-                    eprintln!("Failed to create debouncer: {}", e);
-                    return;
+impl Highlighter {
+    fn fragments(&self, content: &str) -> Vec<SnippetFragment> {
+        let mut terms = self.terms.clone();
+        if self.kind != MatchKind::Exact {
+            let mut tokenizer = self.tokenizer.clone();
+            let mut stream = tokenizer.token_stream(content);
+            while let Some(token) = stream.next() {
+                if self
+                    .words
+                    .iter()
+                    .any(|word| self.loosely_matches(word, &token.text))
+                {
+                    terms.insert(token.text.clone(), 1.0);
                 }
-            };
+            }
+        }
+        let generator =
+            SnippetGenerator::new(terms, self.tokenizer.clone(), self.field, FRAGMENT_CHARS);
+        snippet_fragments(&generator, content)
+    }
 
-            if let Err(e) = debouncer
-                .watcher()
-                .watch(&notes_path, notify::RecursiveMode::Recursive)
-            {
-                eprintln!("Failed to watch directory {:?}: {}", notes_path, e);
-                return;
+    fn loosely_matches(&self, word: &str, token: &str) -> bool {
+        token.starts_with(word)
+            || (self.kind == MatchKind::Fuzzy
+                && edit_distance(word, token) <= typo_distance(word) as usize)
+    }
+}
+
+const DAY_SECS: f32 = 24.0 * 60.0 * 60.0;
+
+/// Typos allowed in a word; none in short words, where nearly everything is one edit away.
+fn typo_distance(word: &str) -> u8 {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Insertions, deletions, substitutions and swaps of neighbours between two words, counted
+/// like tantivy's fuzzy matching with transpositions.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
             }
+        }
+    }
+    d[a.len()][b.len()]
+}
 
-            for result in rx {
-                match result {
-                    Ok(events) => {
-                        for event in events {
-                            let path = event.path;
-
-                            // Only handle .md files
-                            if !path.extension().map(|e| e == "md").unwrap_or(false) {
-                                continue;
-                            }
-
-                            match event.kind {
-                                DebouncedEventKind::Any => {
-                                    if path.exists() {
-                                        let _ = search_index.index_file(&path, &notes_path_clone);
-                                    } else {
-                                        let _ = search_index.remove_file(&path);
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Watch error: {:?}", e);
-                    }
-                }
+fn stored_tags(doc: &TantivyDocument, tags_field: Field) -> Vec<String> {
+    doc.get_all(tags_field)
+        .filter_map(|value| match value {
+            OwnedValue::Facet(facet) => Some(facet.to_path().join("/")),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `path` is one of `note`'s whispers, `.whispers/<note name>.<character>.md` next to it.
+fn is_whisper_of(path: &Path, note: &Path) -> bool {
+    let (Some(stem), Some(parent)) = (note.file_stem(), note.parent()) else {
+        return false;
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    path.parent() == Some(&parent.join(".whispers"))
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+}
+
+/// The text in a frontmatter value: a string, each string in a list, or each item's `value`
+/// in a list like rolodex `emails: [{label, value}]`.
+fn frontmatter_strings(value: &serde_yaml::Value) -> Vec<&str> {
+    match value {
+        serde_yaml::Value::String(text) => vec![text.as_str()],
+        serde_yaml::Value::Sequence(items) => items
+            .iter()
+            .filter_map(|item| item.as_str().or_else(|| item.get("value")?.as_str()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// ATX headings (`## Title`) outside code fences, without the `#`s.
+fn headings(body: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            let text = trimmed.trim_start_matches('#');
+            let level = trimmed.len() - text.len();
+            if (1..=6).contains(&level) && (text.is_empty() || text.starts_with(' ')) {
+                found.push(text.trim().trim_end_matches('#').trim_end());
             }
-        });
+        }
+    }
+    found
+}
 
-        Ok(())
+/// The lines of `content` with matched terms, at most `MAX_FRAGMENTS` of them in note order.
+/// Lines bringing in a term not highlighted yet are picked first, so every matched term shows
+/// up when there is room.
+fn snippet_fragments(generator: &SnippetGenerator, content: &str) -> Vec<SnippetFragment> {
+    let mut candidates = Vec::new();
+    for (line, text) in content.lines().enumerate() {
+        let snippet = generator.snippet(text);
+        if snippet.highlighted().is_empty() {
+            continue;
+        }
+        // A line that fits is a single fragment starting at its first byte, but tantivy cuts
+        // it off after the last word; show all of it, without the indentation. Tantivy's
+        // limit is in bytes, so compare byte lengths or the ranges won't line up
+        let fragment = if text.len() <= FRAGMENT_CHARS {
+            text.trim_end()
+        } else {
+            snippet.fragment()
+        };
+        let indent = fragment.len() - fragment.trim_start().len();
+        let fragment = &fragment[indent..];
+        // Tantivy's ranges are bytes into the untrimmed fragment
+        let ranges: Vec<Range<usize>> = snippet
+            .highlighted()
+            .iter()
+            .filter_map(|range| {
+                let range = range.start.checked_sub(indent)?..range.end.checked_sub(indent)?;
+                fragment.get(range.clone()).map(|_| range)
+            })
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        let chars = |byte: usize| fragment[..byte].chars().count();
+        let terms: HashSet<String> = ranges
+            .iter()
+            .map(|range| fragment[range.clone()].to_lowercase())
+            .collect();
+        let fragment = SnippetFragment {
+            text: fragment.to_string(),
+            line,
+            highlights: ranges
+                .iter()
+                .map(|range| Highlight {
+                    start: chars(range.start),
+                    end: chars(range.end),
+                })
+                .collect(),
+        };
+        candidates.push((fragment, terms));
+    }
+
+    let mut seen = HashSet::new();
+    let mut picked = Vec::new();
+    for (i, (_, terms)) in candidates.iter().enumerate() {
+        if picked.len() < MAX_FRAGMENTS && terms.iter().any(|term| !seen.contains(term)) {
+            seen.extend(terms.iter().cloned());
+            picked.push(i);
+        }
+    }
+    for i in 0..candidates.len() {
+        if picked.len() < MAX_FRAGMENTS && !picked.contains(&i) {
+            picked.push(i);
+        }
+    }
+    picked.sort_unstable();
+
+    let mut candidates: Vec<Option<SnippetFragment>> = candidates
+        .into_iter()
+        .map(|(fragment, _)| Some(fragment))
+        .collect();
+    picked
+        .into_iter()
+        .filter_map(|i| candidates[i].take())
+        .collect()
+}
+
+fn tag_facet(tag: &str) -> Facet {
+    Facet::from_path(tag.split('/'))
+}
+
+fn exact_query(field: Field, value: &str) -> Box<dyn Query> {
+    Box::new(TermQuery::new(
+        Term::from_field_text(field, value),
+        IndexRecordOption::Basic,
+    ))
+}
+
+fn date_query(field: &str, range: &DateRange) -> Box<dyn Query> {
+    Box::new(RangeQuery::new_u64_bounds(
+        field.to_string(),
+        range.after.map_or(Bound::Unbounded, Bound::Included),
+        range.before.map_or(Bound::Unbounded, Bound::Excluded),
+    ))
+}
+
+/// `type` from a section's `.section.md`; the rolodex section is typed by its name.
+fn section_type(section_dir: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(section_dir.join(".section.md")).ok();
+    let declared = content.as_deref().and_then(|content| {
+        let (fm, _) = frontmatter::split(content);
+        fm?.get_str("type").map(String::from)
+    });
+    declared.or_else(|| {
+        section_dir
+            .file_name()
+            .filter(|name| *name == "rolodex")
+            .map(|_| "rolodex".to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    /// A vault with the given notes in one section, indexed into a folder next to it.
+    fn fixture(name: &str, notes: &[(&str, &str)]) -> (TempDir, SearchIndex) {
+        let root = TempDir::new(&format!("search-{}", name));
+        let vault = root.join("vault");
+        let index_path = root.join("index");
+        fs::create_dir_all(vault.join("notes")).unwrap();
+        fs::create_dir_all(&index_path).unwrap();
+        for (file, content) in notes {
+            fs::write(vault.join("notes").join(file), content).unwrap();
+        }
+        let index = SearchIndex::new(&index_path, &vault).unwrap();
+        index.reader.reload().unwrap();
+        (root, index)
+    }
+
+    fn search(index: &SearchIndex, query: &str) -> Vec<SearchResult> {
+        index.search(query, 10, &SearchSettings::default()).unwrap()
+    }
+
+    /// The highlighted parts of a fragment, by character range.
+    fn highlighted(fragment: &SnippetFragment) -> Vec<String> {
+        let chars: Vec<char> = fragment.text.chars().collect();
+        fragment
+            .highlights
+            .iter()
+            .map(|h| chars[h.start..h.end].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn highlights_every_word_of_a_multi_word_query() {
+        let (_root, index) = fixture(
+            "multi",
+            &[(
+                "trip.md",
+                "# Trip\n\nPacked the tent.\n\nThe river was cold.\n",
+            )],
+        );
+        let results = search(&index, "river tent");
+        assert_eq!(results.len(), 1);
+        let fragments = &results[0].fragments;
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(
+            (fragments[0].line, highlighted(&fragments[0])),
+            (2, vec!["tent".into()])
+        );
+        assert_eq!(
+            (fragments[1].line, highlighted(&fragments[1])),
+            (4, vec!["river".into()])
+        );
+        assert_eq!(results[0].snippet.as_deref(), Some("Packed the tent."));
+        assert_eq!(results[0].match_line, Some(2));
+    }
+
+    #[test]
+    fn stale_index_is_only_wiped_once_nobody_holds_it() {
+        let (root, index) = fixture("schema", &[("a.md", "# A\n\nhello world\n")]);
+        let index_path = root.join("index");
+        let vault = root.join("vault");
+        fs::write(index_path.join(SCHEMA_VERSION_FILE), "0").unwrap();
+        let files = fs::read_dir(&index_path).unwrap().count();
+
+        let held = SearchIndex::new(&index_path, &vault);
+        assert!(matches!(held, Err(GitNotesError::IndexLocked { .. })));
+        assert_eq!(fs::read_dir(&index_path).unwrap().count(), files);
+        assert_eq!(search(&index, "hello").len(), 1);
+
+        drop(index);
+        let index = SearchIndex::new(&index_path, &vault).unwrap();
+        index.reader.reload().unwrap();
+        assert_eq!(
+            fs::read_to_string(index_path.join(SCHEMA_VERSION_FILE)).unwrap(),
+            SCHEMA_VERSION.to_string()
+        );
+        assert_eq!(search(&index, "hello").len(), 1);
+    }
+
+    #[test]
+    fn ranges_count_characters_in_unicode_notes() {
+        // İ lowercases to two characters, and the emoji and CJK text are multi-byte
+        let (_root, index) = fixture(
+            "unicode",
+            &[(
+                "städte.md",
+                "İİİ Straße 🎉 café\n  東京 und Straße\nnothing here\nCafé au lait\n",
+            )],
+        );
+        let results = search(&index, "café straße");
+        assert_eq!(results.len(), 1);
+        let fragments = &results[0].fragments;
+        assert_eq!(fragments.len(), 3);
+
+        assert_eq!(fragments[0].line, 0);
+        assert_eq!(
+            fragments[0].highlights,
+            vec![
+                Highlight { start: 4, end: 10 },
+                Highlight { start: 13, end: 17 }
+            ]
+        );
+        assert_eq!(highlighted(&fragments[0]), vec!["Straße", "café"]);
+        assert_eq!(fragments[1].line, 1);
+        assert_eq!(fragments[1].text, "東京 und Straße");
+        assert_eq!(highlighted(&fragments[1]), vec!["Straße"]);
+        assert_eq!(fragments[2].line, 3);
+        assert_eq!(highlighted(&fragments[2]), vec!["Café"]);
+    }
+
+    #[test]
+    fn long_multi_byte_lines_use_tantivys_fragment() {
+        // Fewer than FRAGMENT_CHARS characters, but more bytes
+        let cyrillic = format!("  {}цель", "слово ".repeat(20));
+        let cjk = format!("{}目標", "東京の朝。".repeat(14));
+        assert!(cyrillic.chars().count() <= FRAGMENT_CHARS && cyrillic.len() > FRAGMENT_CHARS);
+        assert!(cjk.chars().count() <= FRAGMENT_CHARS && cjk.len() > FRAGMENT_CHARS);
+        let content = format!("{}\n{}\n", cyrillic, cjk);
+        let (_root, index) = fixture("long-unicode", &[("long.md", &content)]);
+
+        let results = search(&index, "цель");
+        assert_eq!(results.len(), 1);
+        let fragments = &results[0].fragments;
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].line, 0);
+        assert!(fragments[0].text.ends_with("цель"));
+        assert_eq!(highlighted(&fragments[0]), vec!["цель"]);
+
+        let results = search(&index, "目標");
+        assert_eq!(results.len(), 1);
+        let fragments = &results[0].fragments;
+        assert_eq!(fragments[0].line, 1);
+        assert_eq!(highlighted(&fragments[0]), vec!["目標"]);
+    }
+
+    #[test]
+    fn phrases_and_exclusions_highlight_only_what_matched() {
+        let (_root, index) = fixture(
+            "phrase",
+            &[
+                ("a.md", "Grüße aus München\nviele Grüße\n"),
+                ("b.md", "Grüße aus Berlin\n"),
+            ],
+        );
+        let results = search(&index, "\"grüße aus\" -berlin");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "a");
+        let lines: Vec<usize> = results[0].fragments.iter().map(|f| f.line).collect();
+        assert_eq!(lines, vec![0, 1]);
+        assert_eq!(highlighted(&results[0].fragments[0]), vec!["Grüße", "aus"]);
+    }
+
+    #[test]
+    fn prefers_lines_with_terms_not_shown_yet() {
+        let mut content = "alpha\n".repeat(MAX_FRAGMENTS + 2);
+        content.push_str("beta\n");
+        let (_root, index) = fixture("spread", &[("many.md", &content)]);
+        let results = search(&index, "alpha beta");
+        let fragments = &results[0].fragments;
+        assert_eq!(fragments.len(), MAX_FRAGMENTS);
+        assert_eq!(fragments.last().unwrap().line, MAX_FRAGMENTS + 2);
+        assert_eq!(highlighted(fragments.last().unwrap()), vec!["beta"]);
+    }
+
+    #[test]
+    fn filter_only_queries_have_no_fragments() {
+        let (_root, index) = fixture("filters", &[("x.md", "# X\n\nsection content\n")]);
+        let results = search(&index, "section:notes");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].fragments.is_empty());
+        assert_eq!(results[0].snippet, None);
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_fuzzy() {
+        let (_root, index) = fixture(
+            "tiers",
+            &[
+                ("cluster.md", "# Cluster\n\nRuns kubernetes.\n"),
+                ("kubernetes-setup.md", "# Setup\n\nNodes and pods.\n"),
+                (
+                    "ops.md",
+                    "# Ops\n\n## Kubernetes upgrades\n\nDrain first.\n",
+                ),
+                ("typo.md", "# Typo\n\nOur kubernets notes.\n"),
+            ],
+        );
+        let results = search(&index, "kubernetes");
+        let found: Vec<(&str, MatchKind)> = results
+            .iter()
+            .map(|r| (r.filename.as_str(), r.match_kind))
+            .collect();
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[3], ("typo", MatchKind::Fuzzy));
+        assert!(found[..3].iter().all(|(_, kind)| *kind == MatchKind::Exact));
+
+        let results = search(&index, "kube");
+        let mut prefixed: Vec<&str> = results.iter().map(|r| r.filename.as_str()).collect();
+        prefixed.sort();
+        assert_eq!(prefixed, vec!["kubernetes-setup", "ops"]);
+        assert!(results.iter().all(|r| r.match_kind == MatchKind::Prefix));
+        let heading = results.iter().find(|r| r.filename == "ops").unwrap();
+        assert_eq!(heading.match_line, Some(2));
+    }
+
+    #[test]
+    fn finds_misspellings_when_nothing_matches_exactly() {
+        let (_root, index) = fixture(
+            "typos",
+            &[("deploy.md", "Deploying to Kubernetes on Friday\n")],
+        );
+        let results = search(&index, "kubernets");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].match_kind, MatchKind::Fuzzy);
+        assert_eq!(highlighted(&results[0].fragments[0]), vec!["Kubernetes"]);
+        // Too short to guess at
+        assert!(search(&index, "fri").is_empty());
+        assert!(search(&index, "frdiya").is_empty());
+        assert_eq!(search(&index, "firday").len(), 1);
+    }
+
+    #[test]
+    fn short_queries_match_the_start_of_names() {
+        let (_root, index) = fixture(
+            "short",
+            &[("Kyoto trip.md", "plans\n"), ("notes.md", "nothing\n")],
+        );
+        let results = search(&index, "ky");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "Kyoto trip");
+        assert_eq!(results[0].match_kind, MatchKind::Prefix);
+        assert!(results[0].fragments.is_empty());
+        assert_eq!(search(&index, "kyoto -plans").len(), 0);
+    }
+
+    #[test]
+    fn counts_swaps_as_one_edit() {
+        assert_eq!(edit_distance("friday", "firday"), 1);
+        assert_eq!(edit_distance("kubernets", "kubernetes"), 1);
+        assert_eq!(edit_distance("straße", "strasse"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn finds_headings_outside_code() {
+        let body = "# Title\n#tag\n## Sub ##\n```\n# comment\n```\n####### seven\n";
+        assert_eq!(headings(body), vec!["Title", "Sub"]);
+    }
+
+    fn ranking(index: &SearchIndex, query: &str, weights: &SearchSettings) -> Vec<String> {
+        let results = index.search(query, 10, weights).unwrap();
+        results.into_iter().map(|r| r.filename).collect()
+    }
+
+    #[test]
+    fn ranks_names_then_headings_then_body() {
+        let (_root, index) = fixture(
+            "fields",
+            &[
+                (
+                    "mentions.md",
+                    "The budget, the budget and the budget again.\n",
+                ),
+                ("budget.md", "Numbers for next year.\n"),
+                ("planning.md", "# Planning\n\n## Budget\n\nSee the sheet.\n"),
+            ],
+        );
+        let weights = SearchSettings::default();
+        assert_eq!(
+            ranking(&index, "budget", &weights),
+            vec!["budget", "planning", "mentions"]
+        );
+
+        let body_only = SearchSettings {
+            filename_boost: 0.0,
+            headings_boost: 0.0,
+            ..SearchSettings::default()
+        };
+        assert_eq!(ranking(&index, "budget", &body_only)[0], "mentions");
+    }
+
+    #[test]
+    fn ranks_recently_modified_notes_higher() {
+        let day = |days_ago: i64| {
+            (chrono::Utc::now() - chrono::Duration::days(days_ago))
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string()
+        };
+        let note = |days_ago| format!("---\nmodified: {}\n---\nRelease checklist\n", day(days_ago));
+        let (_root, index) = fixture(
+            "recency",
+            &[
+                ("old.md", &note(400)),
+                ("new.md", &note(1)),
+                ("month.md", &note(30)),
+            ],
+        );
+        let weights = SearchSettings::default();
+        assert_eq!(
+            ranking(&index, "checklist", &weights),
+            vec!["new", "month", "old"]
+        );
+        // Filters alone rank by recency too
+        assert_eq!(
+            ranking(&index, "section:notes", &weights),
+            vec!["new", "month", "old"]
+        );
+    }
+
+    #[test]
+    fn ranks_pinned_notes_higher() {
+        let (_root, index) = fixture(
+            "pinned",
+            &[
+                ("a.md", "Wifi password is on the fridge\n"),
+                ("b.md", "Wifi password is on the router\n"),
+            ],
+        );
+        fs::write(
+            index.notes_path.join("notes/.section.md"),
+            "---\npinned:\n  - b.md\n---\n",
+        )
+        .unwrap();
+        let weights = SearchSettings {
+            recency_boost: 0.0,
+            ..SearchSettings::default()
+        };
+        assert_eq!(ranking(&index, "wifi", &weights), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn searches_frontmatter_fields_instead_of_raw_yaml() {
+        let contact = "---\ncreated: 2026-01-02T10:00:00\ncompany: Acme Corp\nrole: CTO\nemails:\n  - label: work\n    value: ada@acme.example\n---\n\nMet at the conference.\n";
+        let (_root, index) = fixture(
+            "frontmatter",
+            &[
+                ("Ada.md", contact),
+                (
+                    "memo.md",
+                    "---\ncreated: 2026-01-03T10:00:00\n---\nAcme shipped late.\n",
+                ),
+            ],
+        );
+        assert!(search(&index, "created").is_empty());
+
+        let names = |query| {
+            let mut names: Vec<String> = search(&index, query)
+                .into_iter()
+                .map(|r| r.filename)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("company:acme"), vec!["Ada"]);
+        assert_eq!(names("acme"), vec!["Ada", "memo"]);
+        assert_eq!(names("role:cto"), vec!["Ada"]);
+        assert_eq!(names("email:ada@acme.example"), vec!["Ada"]);
+        assert_eq!(names("acme -company:acme"), vec!["memo"]);
+
+        // Lines still count from the top of the file
+        let results = search(&index, "conference");
+        assert_eq!(results[0].match_line, Some(9));
+        let results = search(&index, "shipped");
+        assert_eq!(results[0].match_line, Some(3));
+    }
+
+    #[test]
+    fn relates_notes_by_text_then_tags_links_and_section() {
+        let text =
+            "Upgrading the kubernetes cluster: drain the nodes, then bump the control plane.";
+        let (_root, index) = fixture(
+            "related",
+            &[
+                ("upgrade.md", &format!("---\ntags: [ops]\n---\n{}\n", text)),
+                ("tagged.md", &format!("---\ntags: [ops]\n---\n{}\n", text)),
+                ("plain.md", &format!("{}\n", text)),
+                ("linked.md", &format!("{} See [[upgrade]].\n", text)),
+                ("groceries.md", "Milk, eggs and bread.\n"),
+            ],
+        );
+        let vault = index.notes_path.clone();
+        let note = vault.join("notes/upgrade.md");
+        let whisper = vault.join("notes/.whispers/upgrade.skeptic.md");
+        fs::create_dir_all(whisper.parent().unwrap()).unwrap();
+        fs::write(&whisper, format!("{}\n", text)).unwrap();
+        index.index_file(&whisper, &vault).unwrap();
+        index.reader.reload().unwrap();
+
+        let links = LinkIndex::new(&vault);
+        let related = index.related_notes(&note, 10, &links).unwrap();
+        let names: Vec<&str> = related.iter().map(|r| r.filename.as_str()).collect();
+        assert_eq!(names, vec!["linked", "tagged", "plain"]);
+        assert_eq!(related[0].shared_links, 1);
+        assert_eq!(related[1].shared_tags, vec!["ops"]);
+        assert!(related[1].score > related[2].score);
+
+        assert_eq!(index.related_notes(&note, 1, &links).unwrap().len(), 1);
+        let unindexed = vault.join("notes/new.md");
+        assert!(index
+            .related_notes(&unindexed, 10, &links)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn sync_restamps_touched_notes_and_skips_unreadable_ones() {
+        let (_root, index) = fixture("sync", &[("a.md", "alpha\n")]);
+        let vault = index.notes_path.clone();
+        let note = vault.join("notes/a.md");
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&note)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        fs::write(vault.join("notes/latin1.md"), b"caf\xe9\n").unwrap();
+
+        index.sync_files(&vault).unwrap();
+        let indexed = index.indexed_files().unwrap();
+        let (mtime, _) = file_stamp(&note);
+        assert_eq!(indexed[&note.to_string_lossy().to_string()].mtime, mtime);
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(search(&index, "alpha").len(), 1);
+    }
+
+    #[test]
+    fn caches_authors_until_head_moves() {
+        let (_root, index) = fixture("authors", &[("a.md", "alpha\n"), ("b.md", "beta\n")]);
+        let vault = index.notes_path.clone();
+        git::init(&vault).unwrap();
+        let repo = git::tests::configure(&vault);
+        git::commit_all(&repo, "Initial").unwrap();
+        let authors = |query: &str| -> Vec<String> {
+            index.reader.reload().unwrap();
+            search(&index, query)
+                .into_iter()
+                .map(|r| r.filename)
+                .collect()
+        };
+
+        let a = vault.join("notes/a.md");
+        index.index_file(&a, &vault).unwrap();
+        assert_eq!(authors("author:test"), vec!["a"]);
+        assert_eq!(
+            index.authors.lock().unwrap().authors.get(&a),
+            Some(&Some("Test <test@example.com>".to_string()))
+        );
+
+        repo.config()
+            .unwrap()
+            .set_str("user.name", "Grace")
+            .unwrap();
+        let b = vault.join("notes/b.md");
+        fs::write(&b, "beta, edited\n").unwrap();
+        git::commit_all(&repo, "Edit b").unwrap();
+        index.index_file(&b, &vault).unwrap();
+        assert_eq!(authors("author:grace"), vec!["b"]);
+        let cache = index.authors.lock().unwrap();
+        assert_eq!(cache.head, git::head(&repo).map(|c| c.full_hash));
+        assert!(!cache.authors.contains_key(&a));
+    }
+
+    #[test]
+    fn sync_looks_for_authors_only_in_recent_commits() {
+        let (_root, index) = fixture("sync-authors", &[("a.md", "alpha\n"), ("b.md", "beta\n")]);
+        let vault = index.notes_path.clone();
+        git::init(&vault).unwrap();
+        let repo = git::tests::configure(&vault);
+        git::commit_all(&repo, "Initial").unwrap();
+        let b = vault.join("notes/b.md");
+        for i in 0..AUTHOR_WALK_LIMIT {
+            fs::write(&b, format!("beta {}\n", i)).unwrap();
+            git::commit_all(&repo, "Edit b").unwrap();
+        }
+
+        index.rebuild(&vault).unwrap();
+        let found: Vec<String> = search(&index, "author:test")
+            .into_iter()
+            .map(|r| r.filename)
+            .collect();
+        assert_eq!(found, vec!["b"]);
+        let cache = index.authors.lock().unwrap();
+        assert_eq!(cache.authors.get(&vault.join("notes/a.md")), Some(&None));
     }
 }
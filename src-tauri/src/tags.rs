@@ -0,0 +1,144 @@
+// Tags come from a `tags:` list in the frontmatter and from inline `#tag`s in the body, outside
+// code. Nested tags are written with `/` (`#project/alpha`) and also count as their parents.
+
+use crate::frontmatter;
+use serde_yaml::Value;
+
+/// Every tag in a note, normalized, in order of first appearance.
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let (fm, body) = frontmatter::split(content);
+    let mut tags = Vec::new();
+    let mut add = |tag: &str| {
+        if let Some(tag) = normalize(tag) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    };
+
+    match fm.as_ref().and_then(|fm| fm.fields().get("tags")) {
+        Some(Value::Sequence(items)) => {
+            for item in items {
+                match item {
+                    Value::String(s) => add(s),
+                    Value::Number(n) => add(&n.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        // `tags: work, ideas` or `tags: work ideas`
+        Some(Value::String(s)) => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .for_each(&mut add),
+        _ => {}
+    }
+
+    let mut in_fence = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if !in_fence {
+            inline_tags(line).into_iter().for_each(&mut add);
+        }
+    }
+    tags
+}
+
+/// `#tag`s on one line, skipping inline code. A tag starts after whitespace (so headings,
+/// URL fragments and `a#b` don't count) and needs a letter (`#42` is an issue number, `#1-2`
+/// an anchor). Hex colours like `#ff0000` aren't tags either.
+fn inline_tags(line: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut in_code = false;
+    let mut prev: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '#' && !in_code && prev.is_none_or(char::is_whitespace) {
+            let rest = &line[i + 1..];
+            let len = rest.find(|c: char| !is_tag_char(c)).unwrap_or(rest.len());
+            let tag = &rest[..len];
+            if tag.chars().any(char::is_alphabetic) && !is_hex_colour(tag) {
+                found.push(tag);
+            }
+        }
+        prev = Some(c);
+    }
+    found
+}
+
+/// `#f00`, `#ff0000cc` and the like. Only with a digit in it, so words like `#cafe` stay tags.
+fn is_hex_colour(tag: &str) -> bool {
+    matches!(tag.len(), 3 | 4 | 6 | 8)
+        && tag.chars().all(|c| c.is_ascii_hexdigit())
+        && tag.chars().any(|c| c.is_ascii_digit())
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Lowercase, without a leading `#` or empty `/` segments. `None` if nothing is left.
+pub fn normalize(tag: &str) -> Option<String> {
+    let segments: Vec<String> = tag
+        .trim()
+        .trim_start_matches('#')
+        .split('/')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_frontmatter_and_inline_tags_once() {
+        let note = "---\ntags: [Work, ideas, 2026]\n---\n\n#ideas and #Travel, again #work.\n";
+        assert_eq!(extract_tags(note), ["work", "ideas", "2026", "travel"]);
+
+        let note = "---\ntags: work, ideas reading\n---\nNo inline tags\n";
+        assert_eq!(extract_tags(note), ["work", "ideas", "reading"]);
+    }
+
+    #[test]
+    fn skips_code_headings_and_fragments() {
+        let note = "# Heading\n\
+                    See https://example.com/#intro and a#b, `#inline` or #real\n\
+                    ```\n#fenced\n```\n\
+                    ~~~\n#tilde\n~~~\n\
+                    #after-code\n";
+        assert_eq!(extract_tags(note), ["real", "after-code"]);
+    }
+
+    #[test]
+    fn skips_numbers_anchors_and_colours() {
+        assert_eq!(
+            inline_tags("#42 #1-2 #2026/03 #f00 #ff0000 #FF0000cc #1st #cafe #v2"),
+            ["1st", "cafe", "v2"]
+        );
+    }
+
+    #[test]
+    fn nests_tags_with_slashes() {
+        assert_eq!(
+            inline_tags("#project/alpha, #area/home."),
+            ["project/alpha", "area/home"]
+        );
+        assert_eq!(extract_tags("#Project//Alpha/ here"), ["project/alpha"]);
+        assert_eq!(
+            normalize(" #Project / Alpha "),
+            Some("project/alpha".to_string())
+        );
+        assert_eq!(normalize("#/"), None);
+    }
+}
@@ -0,0 +1,39 @@
+// Fixtures shared by the unit tests.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty folder for one test, removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` only has to be unique among the tests; the process id keeps parallel test runs
+    /// apart.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("gitnotes-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
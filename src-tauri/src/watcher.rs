@@ -0,0 +1,448 @@
+use notify::RecommendedWatcher;
+use notify_debouncer_mini::{new_debouncer, DebouncedEventKind, Debouncer};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Handle for a running vault watcher; dropping it stops the watch.
+pub struct VaultWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+/// A change in the vault, with the absolute path it happened at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultChange {
+    NoteCreated(PathBuf),
+    NoteChanged(PathBuf),
+    NoteDeleted(PathBuf),
+    /// A note moved or renamed: its removal and creation seen in the same batch.
+    NoteRenamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// A folder was added or removed; a rename shows up as one of each.
+    SectionChanged(PathBuf),
+    /// A `.section.md`, or the vault's `.gitnotes`.
+    MetadataChanged(PathBuf),
+}
+
+impl VaultChange {
+    pub fn path(&self) -> &Path {
+        match self {
+            VaultChange::NoteCreated(p)
+            | VaultChange::NoteChanged(p)
+            | VaultChange::NoteDeleted(p)
+            | VaultChange::NoteRenamed { to: p, .. }
+            | VaultChange::SectionChanged(p)
+            | VaultChange::MetadataChanged(p) => p,
+        }
+    }
+}
+
+/// Notes and folders seen so far, to tell created from changed and to know what a removed
+/// path used to be.
+#[derive(Default)]
+struct Known {
+    notes: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Known {
+    fn scan(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                self.dirs.insert(path.clone());
+                self.scan(&path);
+            } else if is_markdown(&path) {
+                self.notes.insert(path);
+            }
+        }
+    }
+
+    fn forget_dir(&mut self, dir: &Path) {
+        self.dirs.retain(|p| !p.starts_with(dir));
+        self.notes.retain(|p| !p.starts_with(dir));
+    }
+
+    fn classify(&mut self, notes_path: &Path, path: &Path) -> Option<VaultChange> {
+        let rel = path.strip_prefix(notes_path).ok()?;
+        // Whispers and .git
+        if rel
+            .parent()?
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return None;
+        }
+        let name = rel.file_name()?.to_string_lossy();
+        if name == ".section.md" || rel == Path::new(".gitnotes") {
+            return Some(VaultChange::MetadataChanged(path.to_path_buf()));
+        }
+        // Other dotfiles, including our own temporary files
+        if name.starts_with('.') {
+            return None;
+        }
+
+        if path.is_dir() {
+            if !self.dirs.insert(path.to_path_buf()) {
+                return None;
+            }
+            self.scan(path);
+            Some(VaultChange::SectionChanged(path.to_path_buf()))
+        } else if self.dirs.contains(path) {
+            self.forget_dir(path);
+            Some(VaultChange::SectionChanged(path.to_path_buf()))
+        } else if !is_markdown(path) {
+            None
+        } else if path.exists() {
+            if self.notes.insert(path.to_path_buf()) {
+                Some(VaultChange::NoteCreated(path.to_path_buf()))
+            } else {
+                Some(VaultChange::NoteChanged(path.to_path_buf()))
+            }
+        } else if self.notes.remove(path) {
+            Some(VaultChange::NoteDeleted(path.to_path_buf()))
+        } else {
+            // Created and removed again between two batches
+            None
+        }
+    }
+}
+
+/// Turn a note removed and one created in the same batch into a rename: by file name for a
+/// move, or else by folder when it's the only note left that went each way there.
+fn pair_renames(changes: Vec<VaultChange>) -> Vec<VaultChange> {
+    let deleted: Vec<PathBuf> = changes
+        .iter()
+        .filter_map(|change| match change {
+            VaultChange::NoteDeleted(path) => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+    let created: Vec<PathBuf> = changes
+        .iter()
+        .filter_map(|change| match change {
+            VaultChange::NoteCreated(path) => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+    // (from, to)
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for from in &deleted {
+        if let Some(to) = created.iter().find(|to| {
+            to.file_name() == from.file_name() && !renames.iter().any(|(_, paired)| paired == *to)
+        }) {
+            renames.push((from.clone(), to.clone()));
+        }
+    }
+
+    // What's left over in each folder
+    let unpaired = |paths: &[PathBuf], renames: &[(PathBuf, PathBuf)]| -> Vec<PathBuf> {
+        paths
+            .iter()
+            .filter(|p| !renames.iter().any(|(from, to)| from == *p || to == *p))
+            .cloned()
+            .collect()
+    };
+    let (deleted, created) = (unpaired(&deleted, &renames), unpaired(&created, &renames));
+    let in_folder = |paths: &[PathBuf], dir: Option<&Path>| {
+        paths
+            .iter()
+            .filter(|p| p.parent() == dir)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    for from in &deleted {
+        let dir = from.parent();
+        if let ([_], [to]) = (&in_folder(&deleted, dir)[..], &in_folder(&created, dir)[..]) {
+            renames.push((from.clone(), to.clone()));
+        }
+    }
+
+    changes
+        .into_iter()
+        .filter_map(|change| match change {
+            VaultChange::NoteDeleted(path) if renames.iter().any(|(from, _)| *from == path) => None,
+            VaultChange::NoteCreated(path) => {
+                Some(match renames.iter().find(|(_, to)| *to == path) {
+                    Some((from, _)) => VaultChange::NoteRenamed {
+                        from: from.clone(),
+                        to: path,
+                    },
+                    None => VaultChange::NoteCreated(path),
+                })
+            }
+            change => Some(change),
+        })
+        .collect()
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().map(|e| e == "md").unwrap_or(false)
+}
+
+/// Watch `notes_path` recursively and call `on_change` for every note, folder and metadata
+/// change, along with whether it was this app's own write (see `expect_write`).
+pub fn watch_vault<F>(notes_path: PathBuf, on_change: F) -> Result<VaultWatcher, String>
+where
+    F: Fn(&VaultChange, bool) + Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let mut debouncer = new_debouncer(Duration::from_millis(500), tx)
+        .map_err(|e| format!("Failed to create debouncer: {}", e))?;
+
+    debouncer
+        .watcher()
+        .watch(&notes_path, notify::RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch directory {:?}: {}", notes_path, e))?;
+
+    let mut known = Known::default();
+    known.scan(&notes_path);
+
+    std::thread::spawn(move || {
+        // Ends once the debouncer is dropped and the channel closes
+        for result in rx {
+            match result {
+                Ok(events) => {
+                    // Parents first, so a new folder is scanned before the notes inside it
+                    let mut paths: Vec<PathBuf> = events
+                        .into_iter()
+                        .filter(|event| matches!(event.kind, DebouncedEventKind::Any))
+                        .map(|event| event.path)
+                        .collect();
+                    paths.sort();
+                    paths.dedup();
+
+                    let changes = paths
+                        .iter()
+                        .filter_map(|path| known.classify(&notes_path, path))
+                        .collect();
+                    for change in pair_renames(changes) {
+                        let own_write = match &change {
+                            VaultChange::NoteRenamed { from, to } => {
+                                is_own_write(from) && is_own_write(to)
+                            }
+                            change => is_own_write(change.path()),
+                        };
+                        on_change(&change, own_write);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Watch error: {:?}", e);
+                }
+            }
+        }
+    });
+
+    Ok(VaultWatcher {
+        _debouncer: debouncer,
+    })
+}
+
+// Changes the app made itself, so the frontend isn't told about its own saves. Entries
+// outlive the debounce window and then expire; a match doesn't consume them, because one
+// write can show up in more than one batch.
+const OWN_WRITE_TTL: Duration = Duration::from_secs(10);
+
+enum Expected {
+    Content(u64),
+    Present,
+    Absent,
+}
+
+fn own_writes() -> &'static Mutex<HashMap<PathBuf, (Expected, Instant)>> {
+    static OWN_WRITES: OnceLock<Mutex<HashMap<PathBuf, (Expected, Instant)>>> = OnceLock::new();
+    OWN_WRITES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn expect(path: &Path, expected: Expected) {
+    if let Ok(mut writes) = own_writes().lock() {
+        writes.retain(|_, (_, at)| at.elapsed() < OWN_WRITE_TTL);
+        writes.insert(path.to_path_buf(), (expected, Instant::now()));
+    }
+}
+
+/// The app is about to write `content` to `path`.
+pub fn expect_write(path: &Path, content: &[u8]) {
+    expect(path, Expected::Content(content_hash(content)));
+}
+
+/// The app is about to create `path` (a folder, or a file moved into place).
+pub fn expect_created(path: &Path) {
+    expect(path, Expected::Present);
+}
+
+/// The app is about to remove or move away `path`.
+pub fn expect_removed(path: &Path) {
+    expect(path, Expected::Absent);
+}
+
+/// Whether `path` is in the state the app's last change left it in.
+fn is_own_write(path: &Path) -> bool {
+    let Ok(writes) = own_writes().lock() else {
+        return false;
+    };
+    match writes.get(path) {
+        Some((_, at)) if at.elapsed() >= OWN_WRITE_TTL => false,
+        Some((Expected::Content(hash), _)) => std::fs::read(path)
+            .map(|content| content_hash(&content) == *hash)
+            .unwrap_or(false),
+        Some((Expected::Present, _)) => path.exists(),
+        Some((Expected::Absent, _)) => !path.exists(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::fs;
+
+    fn temp_vault(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("watcher-{}", name));
+        fs::create_dir_all(root.join("work")).unwrap();
+        fs::write(root.join("work").join("plan.md"), "plan").unwrap();
+        root
+    }
+
+    fn scanned(root: &Path) -> Known {
+        let mut known = Known::default();
+        known.scan(root);
+        known
+    }
+
+    #[test]
+    fn tells_created_changed_and_deleted_notes_apart() {
+        let root = temp_vault("notes");
+        let mut known = scanned(&root);
+        let plan = root.join("work").join("plan.md");
+        let idea = root.join("work").join("idea.md");
+
+        assert_eq!(
+            known.classify(&root, &plan),
+            Some(VaultChange::NoteChanged(plan.clone()))
+        );
+        fs::write(&idea, "idea").unwrap();
+        assert_eq!(
+            known.classify(&root, &idea),
+            Some(VaultChange::NoteCreated(idea.clone()))
+        );
+        assert_eq!(
+            known.classify(&root, &idea),
+            Some(VaultChange::NoteChanged(idea.clone()))
+        );
+        fs::remove_file(&idea).unwrap();
+        assert_eq!(
+            known.classify(&root, &idea),
+            Some(VaultChange::NoteDeleted(idea.clone()))
+        );
+        // Already gone, or never seen
+        assert_eq!(known.classify(&root, &idea), None);
+    }
+
+    #[test]
+    fn tracks_folders_and_the_notes_inside_them() {
+        let root = temp_vault("folders");
+        let mut known = scanned(&root);
+        let ideas = root.join("ideas");
+        fs::create_dir(&ideas).unwrap();
+        fs::write(ideas.join("one.md"), "one").unwrap();
+
+        assert_eq!(
+            known.classify(&root, &ideas),
+            Some(VaultChange::SectionChanged(ideas.clone()))
+        );
+        // Scanned along with the folder
+        assert_eq!(
+            known.classify(&root, &ideas.join("one.md")),
+            Some(VaultChange::NoteChanged(ideas.join("one.md")))
+        );
+        assert_eq!(known.classify(&root, &ideas), None);
+
+        fs::remove_dir_all(&ideas).unwrap();
+        assert_eq!(
+            known.classify(&root, &ideas),
+            Some(VaultChange::SectionChanged(ideas.clone()))
+        );
+        assert_eq!(known.classify(&root, &ideas.join("one.md")), None);
+    }
+
+    #[test]
+    fn ignores_hidden_and_other_files() {
+        let root = temp_vault("hidden");
+        let mut known = scanned(&root);
+        let work = root.join("work");
+        for name in [".plan.md.1-0.tmp", "photo.png"] {
+            fs::write(work.join(name), "").unwrap();
+            assert_eq!(known.classify(&root, &work.join(name)), None, "{}", name);
+        }
+        fs::create_dir_all(root.join(".git")).unwrap();
+        assert_eq!(
+            known.classify(&root, &root.join(".git").join("HEAD.md")),
+            None
+        );
+        assert_eq!(known.classify(&root, Path::new("/elsewhere/note.md")), None);
+
+        assert_eq!(
+            known.classify(&root, &work.join(".section.md")),
+            Some(VaultChange::MetadataChanged(work.join(".section.md")))
+        );
+        assert_eq!(
+            known.classify(&root, &root.join(".gitnotes")),
+            Some(VaultChange::MetadataChanged(root.join(".gitnotes")))
+        );
+    }
+
+    #[test]
+    fn pairs_removed_and_created_notes_into_renames() {
+        let path = |p: &str| PathBuf::from("/vault").join(p);
+        let renamed = |from: &str, to: &str| VaultChange::NoteRenamed {
+            from: path(from),
+            to: path(to),
+        };
+
+        // Renamed in its folder, and moved to another one
+        let changes = vec![
+            VaultChange::NoteCreated(path("home/plan.md")),
+            VaultChange::NoteDeleted(path("work/plan.md")),
+            VaultChange::NoteDeleted(path("work/todo.md")),
+            VaultChange::NoteCreated(path("work/tasks.md")),
+            VaultChange::NoteChanged(path("work/idea.md")),
+        ];
+        assert_eq!(
+            pair_renames(changes),
+            vec![
+                renamed("work/plan.md", "home/plan.md"),
+                renamed("work/todo.md", "work/tasks.md"),
+                VaultChange::NoteChanged(path("work/idea.md")),
+            ]
+        );
+
+        // Which of two new notes in a folder was the old one is anyone's guess
+        let changes = vec![
+            VaultChange::NoteDeleted(path("work/todo.md")),
+            VaultChange::NoteCreated(path("work/a.md")),
+            VaultChange::NoteCreated(path("work/b.md")),
+            VaultChange::NoteDeleted(path("home/gone.md")),
+        ];
+        assert_eq!(pair_renames(changes.clone()), changes);
+    }
+}
@@ -10,6 +10,14 @@ export interface ParsedNote {
 
 const FRONTMATTER_REGEX = /^---\r?\n([\s\S]*?)\r?\n---\r?\n?/;
 
+// The YAML each parsed frontmatter came from, and the flat values read from it. Saving
+// writes that YAML back verbatim, so nested values, comments, quoting and key order survive.
+const sources = new WeakMap<FrontMatter, { yaml: string; values: FrontMatter }>();
+
+function escapeRegExp(text: string): string {
+  return text.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+}
+
 export function parseFrontMatter(content: string): ParsedNote {
   const match = content.match(FRONTMATTER_REGEX);
 
@@ -41,10 +49,38 @@ export function parseFrontMatter(content: string): ParsedNote {
     }
   }
 
+  sources.set(frontmatter, { yaml: yamlContent, values: { ...frontmatter } });
   return { frontmatter, body };
 }
 
 export function serializeFrontMatter(frontmatter: FrontMatter, body: string): string {
+  const source = sources.get(frontmatter);
+  if (source) {
+    // Only fields set since parsing (like a filled-in `created`) are written out
+    let yaml = source.yaml;
+    const added: string[] = [];
+    for (const [key, value] of Object.entries(frontmatter)) {
+      if (!value || source.values[key] === value) continue;
+      const line = new RegExp(`^${escapeRegExp(key)}:.*$`, 'm');
+      if (line.test(yaml)) {
+        yaml = yaml.replace(line, `${key}: ${value}`);
+      } else {
+        added.push(`${key}: ${value}`);
+      }
+    }
+    // Fields deleted or emptied since parsing lose their line
+    for (const key of Object.keys(source.values)) {
+      if (frontmatter[key]) continue;
+      yaml = yaml.replace(new RegExp(`^${escapeRegExp(key)}:.*(\\r?\\n|$)`, 'm'), '');
+    }
+    yaml = yaml.replace(/\r?\n$/, '');
+    const lines = [...added, yaml].filter(line => line !== '');
+    if (lines.length === 0) {
+      return body;
+    }
+    return `---\n${lines.join('\n')}\n---\n${body}`;
+  }
+
   const lines: string[] = [];
 
   // Output all frontmatter fields
@@ -33,6 +33,19 @@ interface Note {
   subfolder?: string;
 }
 
+// Returned by rename/move, with the notes whose links to it were rewritten
+export interface MovedNote extends Note {
+  updated_links: string[];
+}
+
+export interface NoteLink {
+  source: string;
+  target: string | null;
+  text: string;
+  kind: 'wiki' | 'markdown';
+  line: number;
+}
+
 interface FileMetadata {
   created: string | null;
 }
@@ -51,7 +64,21 @@ interface Whisper {
   generated: string | null;
 }
 
+// Rejection value of note, section, git and search commands. `code` is stable;
+// `message` is ready to show. Other fields (path, name, kind) depend on the code.
+export interface GitNotesError {
+  code: 'not_found' | 'already_exists' | 'invalid_name' | 'protected' | 'path_outside_vault'
+    | 'git' | 'io' | 'parse' | 'index' | 'index_locked' | 'other';
+  message: string;
+  path?: string;
+  name?: string;
+  kind?: 'not_a_repository' | 'no_upstream' | 'auth_failed' | 'offline' | 'rejected'
+    | 'conflicts' | 'other';
+}
+
 let currentNote: Note | null = null;
+// Version of the current note as last read or saved, so saves can detect changes made elsewhere
+let currentVersion: string | null = null;
 let currentFrontMatter: FrontMatter = {};
 let currentBody: string = '';
 let saveTimeout: number | null = null;
@@ -73,12 +100,61 @@ export async function loadNotes(sectionPath: string): Promise<Note[]> {
   return await invoke('list_notes', { sectionPath });
 }
 
-export async function readNote(path: string): Promise<string> {
+export interface NoteContent {
+  content: string;
+  version: string;
+}
+
+export type WriteOutcome =
+  | { Saved: { version: string } }
+  | {
+      Conflict: {
+        ours: string;
+        theirs: string;
+        theirs_version: string;
+        merged: string | null;
+        clean: boolean;
+      };
+    };
+
+export async function readNote(path: string): Promise<NoteContent> {
   return await invoke('read_note', { path });
 }
 
-export async function writeNote(path: string, content: string): Promise<void> {
-  return await invoke('write_note', { path, content });
+export async function writeNote(
+  path: string,
+  content: string,
+  expectedVersion: string | null = null,
+): Promise<WriteOutcome> {
+  return await invoke('write_note', { path, content, expectedVersion });
+}
+
+export interface TagCount {
+  tag: string;
+  // Notes with this tag or one nested under it (e.g. `project` counts `project/alpha`)
+  count: number;
+}
+
+export async function listTags(): Promise<TagCount[]> {
+  return await invoke('list_tags');
+}
+
+export async function notesWithTag(tag: string): Promise<{ path: string; filename: string; section: string }[]> {
+  return await invoke('notes_with_tag', { tag });
+}
+
+export interface RelatedNote {
+  path: string;
+  filename: string;
+  section: string;
+  score: number;
+  shared_tags: string[];
+  // Links between the two notes, plus notes both link to
+  shared_links: number;
+}
+
+export async function relatedNotes(path: string, limit?: number): Promise<RelatedNote[]> {
+  return await invoke('related_notes', { path, limit });
 }
 
 export async function getFileMetadata(path: string): Promise<FileMetadata> {
@@ -93,6 +169,14 @@ export async function gitCommit(path: string, message: string): Promise<void> {
   return await invoke('git_commit', { path, message });
 }
 
+export async function gitCommitFiles(paths: string[], message: string): Promise<void> {
+  return await invoke('git_commit_files', { paths, message });
+}
+
+export async function getBacklinks(path: string): Promise<NoteLink[]> {
+  return await invoke('get_backlinks', { path });
+}
+
 export async function createNoteSmart(sectionPath: string): Promise<Note> {
   return await invoke('create_note_smart', { sectionPath });
 }
@@ -101,12 +185,12 @@ export async function deleteNote(path: string): Promise<void> {
   return await invoke('delete_note', { path });
 }
 
-export async function renameNote(oldPath: string, newName: string): Promise<Note> {
-  return await invoke('rename_note', { oldPath, newName });
+export async function renameNote(oldPath: string, newName: string, updateLinks = false): Promise<MovedNote> {
+  return await invoke('rename_note', { oldPath, newName, updateLinks });
 }
 
-export async function moveNote(path: string, newSectionPath: string): Promise<Note> {
-  return await invoke('move_note', { path, newSectionPath });
+export async function moveNote(path: string, newSectionPath: string, updateLinks = false): Promise<MovedNote> {
+  return await invoke('move_note', { path, newSectionPath, updateLinks });
 }
 
 export async function createSection(name: string): Promise<Section> {
@@ -267,7 +351,8 @@ export async function loadNoteWithHeader(note: Note) {
   setCurrentNotePath(note.path);
 
   // Read raw content
-  const rawContent = await readNote(note.path);
+  const { content: rawContent, version } = await readNote(note.path);
+  currentVersion = version;
 
   // Parse front matter
   const parsed = parseFrontMatter(rawContent);
@@ -380,11 +465,45 @@ async function flushPendingSave() {
       const parsed = parseFrontMatter(fullContent);
       currentBody = parsed.body;
       const contentToSave = serializeFrontMatter(currentFrontMatter, currentBody);
-      await writeNote(currentNote.path, contentToSave);
+      await saveCurrentNote(contentToSave);
     }
   }
 }
 
+// Save the current note against the version we last read. Returns false if it changed on
+// disk in the meantime and needs another look; the next save then goes on top of that version.
+async function saveCurrentNote(content: string): Promise<boolean> {
+  if (!currentNote) return false;
+  let outcome: WriteOutcome;
+  try {
+    outcome = await writeNote(currentNote.path, content, currentVersion);
+  } catch (err) {
+    if ((err as GitNotesError).code !== 'not_found') throw err;
+    currentVersion = null;
+    alert(`${currentNote.name} was deleted or moved on disk since it was opened. Saving again will recreate it.`);
+    return false;
+  }
+  if ('Saved' in outcome) {
+    currentVersion = outcome.Saved.version;
+    return true;
+  }
+
+  const { merged, clean, theirs_version } = outcome.Conflict;
+  currentVersion = theirs_version;
+  if (merged === null) {
+    alert(`${currentNote.name} changed on disk since it was opened. Saving again will replace that version.`);
+    return false;
+  }
+
+  const parsed = parseFrontMatter(merged);
+  currentFrontMatter = parsed.frontmatter;
+  currentBody = parsed.body;
+  loadContent(serializeFrontMatter(currentFrontMatter, currentBody));
+  // Conflict markers are left in the editor for the user to resolve
+  if (!clean) return false;
+  return await saveCurrentNote(merged);
+}
+
 // Attach click handlers to whisper pills
 function attachWhisperHandlers() {
   const pills = document.querySelectorAll('.whisper-pill');
@@ -397,7 +516,8 @@ function attachWhisperHandlers() {
         // "Note" pill — restore original note content
         activeWhisper = null;
         if (currentNote) {
-          const rawContent = await readNote(currentNote.path);
+          const { content: rawContent, version } = await readNote(currentNote.path);
+          currentVersion = version;
           const parsed = parseFrontMatter(rawContent);
           currentFrontMatter = parsed.frontmatter;
           currentBody = parsed.body;
@@ -410,7 +530,7 @@ function attachWhisperHandlers() {
         // Whisper pill — flush pending save, load whisper read-only
         await flushPendingSave();
         activeWhisper = character;
-        const whisperContent = await readNote(whisperPath);
+        const { content: whisperContent } = await readNote(whisperPath);
         loadWhisperContent(whisperContent);
         refreshHeaderWithWhispers();
       }
@@ -427,6 +547,7 @@ export function scheduleSave() {
   recordEdit(getCursorPosition(), getScrollTop());
 
   saveTimeout = window.setTimeout(async () => {
+    saveTimeout = null;
     if (currentNote) {
       try {
         const fullContent = getContent();
@@ -438,7 +559,7 @@ export function scheduleSave() {
         // Rebuild with current front matter
         const contentToSave = serializeFrontMatter(currentFrontMatter, currentBody);
 
-        await writeNote(currentNote.path, contentToSave);
+        if (!(await saveCurrentNote(contentToSave))) return;
         setStatus('Saved');
 
         // Count lines changed (approximate)
@@ -664,6 +785,14 @@ async function init() {
       getCurrentWindow().destroy();
     });
 
+    // Pick up edits made outside the app. With unsaved changes, the next save merges them instead.
+    listen<{ path: string }>('note-changed', async ({ payload }) => {
+      if (!currentNote || saveTimeout || activeWhisper) return;
+      if (!currentNote.path.endsWith('/' + payload.path)) return;
+      const { version } = await readNote(currentNote.path);
+      if (version !== currentVersion) await loadNoteWithHeader(currentNote);
+    });
+
     // Save session + commit on Cmd+Q
     listen('quit-requested', async () => {
       await saveSessionState();
@@ -8,6 +8,14 @@ interface SearchResult {
   section: string;
   snippet: string | null;
   match_line: number | null;
+  fragments: SnippetFragment[];
+}
+
+// Highlights are character ranges into `text`
+interface SnippetFragment {
+  text: string;
+  line: number;
+  highlights: { start: number; end: number }[];
 }
 
 interface NoteInfo {
@@ -71,28 +79,8 @@ function loadRecentData(): void {
   }
 }
 
-function fuzzyMatch(query: string, text: string): boolean {
-  const lowerQuery = query.toLowerCase();
-  const lowerText = text.toLowerCase();
-  if (lowerQuery.length === 0) return true;
-  let queryIndex = 0;
-  for (let i = 0; i < lowerText.length && queryIndex < lowerQuery.length; i++) {
-    if (lowerText[i] === lowerQuery[queryIndex]) {
-      queryIndex++;
-    }
-  }
-  return queryIndex === lowerQuery.length;
-}
-
-function filterByFilename(query: string): NoteInfo[] {
-  if (!query) return [];
-  return allNotes
-    .filter(note => fuzzyMatch(query, note.name) || fuzzyMatch(query, note.section))
-    .slice(0, 10);
-}
-
 async function searchContent(query: string): Promise<SearchResult[]> {
-  if (query.length < MIN_CONTENT_SEARCH_LENGTH) return [];
+  if (!query) return [];
   try {
     return await invoke('search_notes', { query });
   } catch (e) {
@@ -125,6 +113,19 @@ function highlightMatch(text: string, query: string): string {
   return escaped.replace(regex, '<mark>$1</mark>');
 }
 
+function renderFragment(fragment: SnippetFragment): string {
+  // Index by code point, like the ranges from the backend
+  const chars = Array.from(fragment.text);
+  let html = '';
+  let pos = 0;
+  for (const { start, end } of fragment.highlights) {
+    html += escapeHtml(chars.slice(pos, start).join(''));
+    html += `<mark>${escapeHtml(chars.slice(start, end).join(''))}</mark>`;
+    pos = end;
+  }
+  return html + escapeHtml(chars.slice(pos).join(''));
+}
+
 function renderDropdown(query: string): void {
   const recentFilesSection = document.getElementById('recent-files-section');
   const recentSearchesSection = document.getElementById('recent-searches-section');
@@ -213,12 +214,12 @@ function renderDropdown(query: string): void {
       });
     }
 
-    // Separate filename matches from content matches
+    // Notes matched by name or heading only have nothing to show from their content
     const filenameMatches: { result: SearchResult | NoteInfo; index: number }[] = [];
     const contentMatches: { result: SearchResult | NoteInfo; index: number }[] = [];
 
     currentResults.forEach((result, i) => {
-      if ('snippet' in result && result.snippet) {
+      if ('fragments' in result && result.fragments.length > 0) {
         contentMatches.push({ result, index: i });
       } else {
         filenameMatches.push({ result, index: i });
@@ -233,13 +234,13 @@ function renderDropdown(query: string): void {
       resultsList.appendChild(header);
 
       filenameMatches.forEach(({ result, index }) => {
-        const note = result as NoteInfo;
+        const sr = result as SearchResult;
         const li = document.createElement('li');
         li.className = index === selectedIndex ? 'selected' : '';
         li.innerHTML = `
           <div class="result-header">
-            <span class="result-section">${escapeHtml(truncateSection(note.section))}</span>
-            <span class="result-filename">${escapeHtml(note.name)}</span>
+            <span class="result-section">${escapeHtml(truncateSection(sr.section))}</span>
+            <span class="result-filename">${escapeHtml(sr.filename)}</span>
           </div>
         `;
         li.addEventListener('click', () => selectResult(index));
@@ -263,7 +264,7 @@ function renderDropdown(query: string): void {
             <span class="result-section">${escapeHtml(truncateSection(sr.section))}</span>
             <span class="result-filename">${escapeHtml(sr.filename)}</span>
           </div>
-          <div class="result-snippet">${highlightMatch(sr.snippet || '', query)}</div>
+          ${sr.fragments.map(f => `<div class="result-snippet">${renderFragment(f)}</div>`).join('')}
         `;
         li.addEventListener('click', () => selectResult(index));
         resultsList.appendChild(li);
@@ -380,24 +381,18 @@ export function initSearchBar(onSelect?: (result: SearchResult | NoteInfo, match
       clearTimeout(searchTimeout);
     }
 
-    if (query.length < MIN_CONTENT_SEARCH_LENGTH) {
-      // Immediate filename filtering
-      currentResults = filterByFilename(query);
+    if (!query) {
+      currentResults = [];
       renderDropdown(query);
-    } else {
-      // Debounced content search
-      searchTimeout = window.setTimeout(async () => {
-        const filenameMatches = filterByFilename(query);
-        const contentMatches = await searchContent(query);
-
-        // Combine: filename matches first, then content matches (deduplicated)
-        const seenPaths = new Set(filenameMatches.map(p => p.path));
-        const uniqueContentMatches = contentMatches.filter(r => !seenPaths.has(r.path) && r.path !== currentNotePath);
-
-        currentResults = [...filenameMatches, ...uniqueContentMatches];
-        renderDropdown(query);
-      }, 150);
+      return;
     }
+
+    // Debounced search; names, headings and typos are matched by the backend
+    searchTimeout = window.setTimeout(async () => {
+      const results = await searchContent(query);
+      currentResults = results.filter(r => r.path !== currentNotePath);
+      renderDropdown(query);
+    }, 150);
   });
 
 
@@ -1,9 +1,11 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { ask } from '@tauri-apps/plugin-dialog';
 import {
   loadSections, loadNotes, setCurrentNote, setStatus, clearPendingSave,
   createNoteSmart, deleteNote, renameNote, createSection, deleteSection, moveNote,
   loadNoteWithHeader, setSectionMetadata, saveSectionOrder,
-  gitCommit, flashCommitted, getCurrentNote
+  gitCommit, gitCommitFiles, getBacklinks, flashCommitted, getCurrentNote, MovedNote
 } from './main';
 import { loadContent, updateHeaderData, focusEditor, renderContactCard } from './editor';
 import { showContextMenu } from './contextmenu';
@@ -48,26 +50,61 @@ let dragState: {
 let skipNextSectionClick = false;
 let renameAttempted = false;
 
+// Reload the section's notes after the open one went away, and open the first
+async function showFirstNote() {
+  if (!currentSection) return;
+  const notes = await loadNotes(currentSection.path);
+  renderNotes(notes);
+  if (notes.length > 0) {
+    await selectNote(notes[0]);
+  } else {
+    setCurrentNote(null);
+    loadContent('');
+    renderContactCard(null);
+    updateHeaderData({ title: '', createdDate: null, modifiedInfo: null });
+  }
+}
+
 async function handleDeleteNote(note: Note) {
   try {
     await deleteNote(note.path);
-    if (currentSection) {
-      const notes = await loadNotes(currentSection.path);
-      renderNotes(notes);
-      if (notes.length > 0) {
-        await selectNote(notes[0]);
-      } else {
-        setCurrentNote(null);
-        loadContent('');
-        renderContactCard(null);
-        updateHeaderData({ title: '', createdDate: null, modifiedInfo: null });
-      }
-    }
+    await showFirstNote();
   } catch (err) {
     console.error('Delete error:', err);
   }
 }
 
+// Links to a note break when it's renamed or moved; offer to rewrite them if there are any
+async function confirmLinkUpdates(path: string): Promise<boolean> {
+  try {
+    const sources = new Set((await getBacklinks(path)).map(link => link.source));
+    sources.delete(path);
+    if (sources.size === 0) return false;
+    const linking = sources.size === 1 ? '1 other note links' : `${sources.size} other notes link`;
+    return await ask(`${linking} to this note. Update those links?`, {
+      title: 'Update Links',
+      kind: 'info',
+      okLabel: 'Update Links',
+      cancelLabel: 'Leave Them',
+    });
+  } catch (err) {
+    console.error('Backlinks error:', err);
+    return false;
+  }
+}
+
+// With auto-commit on, commit a rename or move together with the notes whose links followed it
+async function commitMove(oldPath: string, moved: MovedNote, message: string) {
+  if (!(await getAutoCommit())) return;
+  try {
+    await gitCommitFiles([oldPath, moved.path, ...moved.updated_links], message);
+    flashCommitted();
+    await refreshGitStatus();
+  } catch (err) {
+    console.error('Commit error:', err);
+  }
+}
+
 function startRename(note: Note, li: HTMLElement) {
   const input = document.createElement('input');
   input.type = 'text';
@@ -88,7 +125,10 @@ function startRename(note: Note, li: HTMLElement) {
     let renamedNote: Note | null = null;
     if (newName && newName !== note.name) {
       try {
-        renamedNote = await renameNote(note.path, newName);
+        const updateLinks = await confirmLinkUpdates(note.path);
+        const moved = await renameNote(note.path, newName, updateLinks);
+        renamedNote = moved;
+        await commitMove(note.path, moved, `Rename ${note.name} to ${newName}`);
       } catch (err) {
         console.error('Rename error:', err);
       }
@@ -166,6 +206,70 @@ async function refreshCurrentNotes() {
   renderNotes(notes);
 }
 
+// Sent by the vault watcher for changes made outside the app; `path` is relative to the vault
+interface VaultChangeEvent {
+  path: string;
+  // Where a renamed note used to be
+  from?: string;
+}
+
+function inCurrentSection(relPath: string): boolean {
+  return !!currentSection && relPath.split('/')[0] === currentSection.name;
+}
+
+async function reloadSections() {
+  sections = await loadSections();
+  const section = currentSection && sections.find(s => s.path === currentSection!.path);
+  if (section) {
+    currentSection = section;
+    renderSections();
+    await refreshCurrentNotes();
+  } else if (sections.length > 0) {
+    await selectSection(sections[0]);
+  } else {
+    renderSections();
+  }
+}
+
+function listenForVaultChanges() {
+  listen<VaultChangeEvent>('note-created', async ({ payload }) => {
+    if (inCurrentSection(payload.path)) await refreshCurrentNotes();
+  });
+  listen<VaultChangeEvent>('note-deleted', async ({ payload }) => {
+    if (!inCurrentSection(payload.path)) return;
+    const note = getCurrentNote();
+    if (note && note.path.endsWith('/' + payload.path)) {
+      await showFirstNote();
+    } else {
+      await refreshCurrentNotes();
+    }
+  });
+  // The open note follows a move or rename, so unsaved edits land at the new path
+  listen<VaultChangeEvent>('note-renamed', async ({ payload }) => {
+    const from = payload.from;
+    if (!from) return;
+    const note = getCurrentNote();
+    if (note && note.path.endsWith('/' + from)) {
+      const filename = payload.path.split('/').pop()!;
+      setCurrentNote({
+        ...note,
+        path: note.path.slice(0, -from.length) + payload.path,
+        filename,
+        name: filename.replace(/\.md$/, ''),
+      });
+    }
+    if (!inCurrentSection(from) && !inCurrentSection(payload.path)) return;
+    await refreshCurrentNotes();
+    const open = getCurrentNote();
+    document.querySelectorAll<HTMLElement>('#pages-list li').forEach(li => {
+      li.classList.toggle('active', li.dataset.path === open?.path);
+    });
+  });
+  // Titles, colors, order and pins all live in metadata
+  listen('section-changed', reloadSections);
+  listen('metadata-changed', reloadSections);
+}
+
 let listenersInitialized = false;
 
 export async function initSidebar(restoreSection?: string) {
@@ -186,6 +290,8 @@ export async function initSidebar(restoreSection?: string) {
   if (listenersInitialized) return;
   listenersInitialized = true;
 
+  listenForVaultChanges();
+
   document.getElementById('add-page-btn')?.addEventListener('click', async () => {
     if (!currentSection) return;
     try {
@@ -427,7 +533,9 @@ function renderSections() {
       const notePath = e.dataTransfer?.getData('text/plain');
       if (notePath && section.path !== currentSection?.path) {
         try {
-          await moveNote(notePath, section.path);
+          const updateLinks = await confirmLinkUpdates(notePath);
+          const moved = await moveNote(notePath, section.path, updateLinks);
+          await commitMove(notePath, moved, `Move ${moved.name} to ${section.title || section.name}`);
           if (currentSection) {
             const notes = await loadNotes(currentSection.path);
             renderNotes(notes);